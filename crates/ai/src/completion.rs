@@ -0,0 +1,58 @@
+use crate::{ChatCompletionsRequest, ChatMessage};
+
+/// Chars of text before the caret sent with a completion request.
+pub const DEFAULT_COMPLETION_PREFIX_CHARS: usize = 4_000;
+/// Chars of text after the caret sent with a completion request.
+pub const DEFAULT_COMPLETION_SUFFIX_CHARS: usize = 1_000;
+
+const COMPLETION_INSTRUCTIONS: &str = "You complete code. Reply with only the text to insert at \
+<CURSOR>, without explanations or code fences. Reply with nothing if no completion fits.";
+
+/// Build a streaming request for the text to insert between `prefix` and `suffix`.
+///
+/// Only the end of `prefix` and the start of `suffix` are sent, up to
+/// `DEFAULT_COMPLETION_PREFIX_CHARS` and `DEFAULT_COMPLETION_SUFFIX_CHARS`. Without a
+/// suffix the model continues the prefix.
+pub fn build_completion_request(model: String, prefix: &str, suffix: Option<&str>) -> ChatCompletionsRequest {
+    let prefix_len = prefix.chars().count();
+    let prefix: String = prefix
+        .chars()
+        .skip(prefix_len.saturating_sub(DEFAULT_COMPLETION_PREFIX_CHARS))
+        .collect();
+    let suffix: String = suffix
+        .unwrap_or("")
+        .chars()
+        .take(DEFAULT_COMPLETION_SUFFIX_CHARS)
+        .collect();
+    ChatCompletionsRequest {
+        model,
+        messages: vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: COMPLETION_INSTRUCTIONS.to_string(),
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: format!("{prefix}<CURSOR>{suffix}"),
+            },
+        ],
+        temperature: Some(0.0),
+        max_tokens: Some(256),
+        stream: Some(true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completion_request_marks_cursor() {
+        let prefix = "x".repeat(DEFAULT_COMPLETION_PREFIX_CHARS + 10) + "fn main() {";
+        let request = build_completion_request("m".to_string(), &prefix, Some("\n}"));
+        let user = &request.messages[1].content;
+        assert!(user.ends_with("fn main() {<CURSOR>\n}"));
+        assert_eq!(user.chars().count(), DEFAULT_COMPLETION_PREFIX_CHARS + "<CURSOR>\n}".len());
+        assert_eq!(request.stream, Some(true));
+    }
+}
@@ -0,0 +1,165 @@
+use crate::{ChatCompletionsRequest, ChatMessage};
+use std::ops::Range;
+
+/// Default budget for code context, in chars.
+pub const DEFAULT_CONTEXT_CHARS: usize = 12_000;
+
+/// The code the user is looking at, sent along with a question for grounding.
+#[derive(Debug, Clone, Default)]
+pub struct CodeContext {
+    pub filename: Option<String>,
+    pub language: Option<String>,
+    pub text: String,
+    /// Selected char range within `text`, if any; an empty range is the cursor.
+    pub selection: Option<Range<usize>>,
+}
+
+impl CodeContext {
+    /// The part of `text` to send, at most `max_chars` chars, as a char range.
+    ///
+    /// The selection is always kept (cut to the budget if it alone is too large), then
+    /// widened to whole lines and grown by alternating lines above and below while they
+    /// fit. Without a selection the document is taken from the top.
+    pub fn excerpt_range(&self, max_chars: usize) -> Range<usize> {
+        let chars: Vec<char> = self.text.chars().collect();
+        let len = chars.len();
+        let Some(selection) = self.selection.clone() else {
+            return 0..len.min(max_chars);
+        };
+        let start = selection.start.min(len);
+        let end = selection.end.clamp(start, len);
+        if end - start >= max_chars {
+            return start..start + max_chars;
+        }
+
+        let line_start = |pos: usize| chars[..pos].iter().rposition(|c| *c == '\n').map_or(0, |i| i + 1);
+        let line_end = |pos: usize| chars[pos..].iter().position(|c| *c == '\n').map_or(len, |i| pos + i + 1);
+        let (mut lo, mut hi) = (start, end);
+        let whole = (line_start(lo), if hi > lo && chars[hi - 1] == '\n' { hi } else { line_end(hi) });
+        if whole.1 - whole.0 <= max_chars {
+            (lo, hi) = whole;
+        }
+        loop {
+            let mut grew = false;
+            if lo > 0 {
+                let above = line_start(lo - 1);
+                if hi - above <= max_chars {
+                    lo = above;
+                    grew = true;
+                }
+            }
+            if hi < len {
+                let below = line_end(hi);
+                if below - lo <= max_chars {
+                    hi = below;
+                    grew = true;
+                }
+            }
+            if !grew {
+                return lo..hi;
+            }
+        }
+    }
+
+    /// A system message describing the file and carrying the excerpt in a fenced block.
+    pub fn to_message(&self, max_chars: usize) -> ChatMessage {
+        let range = self.excerpt_range(max_chars);
+        let excerpt: String = self.text.chars().skip(range.start).take(range.len()).collect();
+        let line_of = |pos: usize| self.text.chars().take(pos).filter(|c| *c == '\n').count() + 1;
+        let total_lines = self.text.lines().count().max(1);
+
+        let mut content = String::from("The user is working in the following code.\n");
+        if let Some(filename) = &self.filename {
+            content.push_str(&format!("File: {filename}\n"));
+        }
+        if let Some(language) = &self.language {
+            content.push_str(&format!("Language: {language}\n"));
+        }
+        let last_line = line_of(range.end.saturating_sub(1).max(range.start));
+        content.push_str(&format!("Lines {}-{} of {}", line_of(range.start), last_line, total_lines));
+        match &self.selection {
+            Some(selection) if selection.is_empty() => {
+                content.push_str(&format!(" (cursor on line {})", line_of(selection.start)));
+            }
+            Some(selection) => content.push_str(&format!(
+                " (selection on lines {}-{})",
+                line_of(selection.start),
+                line_of(selection.end.saturating_sub(1).max(selection.start))
+            )),
+            None => {}
+        }
+        let fence_lang = self.language.as_deref().unwrap_or("").to_lowercase();
+        content.push_str(&format!(":\n```{fence_lang}\n{excerpt}"));
+        if !excerpt.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str("```");
+
+        ChatMessage {
+            role: "system".to_string(),
+            content,
+        }
+    }
+}
+
+/// Build a streaming request asking `user_message` about `context`, keeping the code
+/// context within `max_context_chars`.
+pub fn build_context_request(
+    model: String,
+    context: &CodeContext,
+    user_message: &str,
+    max_context_chars: usize,
+) -> ChatCompletionsRequest {
+    ChatCompletionsRequest {
+        model,
+        messages: vec![
+            context.to_message(max_context_chars),
+            ChatMessage {
+                role: "user".to_string(),
+                content: user_message.to_string(),
+            },
+        ],
+        temperature: None,
+        max_tokens: None,
+        stream: Some(true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_request_keeps_selection_within_budget() {
+        let text: String = (0..50).map(|i| format!("let v{i} = {i};\n")).collect();
+        let selected = "let v25 = 25;\n";
+        let start = text.find(selected).unwrap();
+        let context = CodeContext {
+            filename: Some("main.rs".to_string()),
+            language: Some("Rust".to_string()),
+            selection: Some(start..start + selected.len()),
+            text,
+        };
+
+        let range = context.excerpt_range(60);
+        assert!(range.len() <= 60);
+        assert!(range.start <= start && start + selected.len() <= range.end);
+
+        let request = build_context_request("m".to_string(), &context, "What does this do?", 60);
+        assert_eq!(request.messages.len(), 2);
+        let system = &request.messages[0].content;
+        assert!(system.contains("File: main.rs"));
+        assert!(system.contains("```rust\n"));
+        assert!(system.contains(selected));
+        assert!(!system.contains("let v0 = 0;"));
+        assert!(system.contains("(selection on lines 26-26)"));
+        assert_eq!(request.messages[1].content, "What does this do?");
+
+        let caret = CodeContext { selection: Some(start..start), ..context.clone() };
+        assert!(caret.excerpt_range(60).contains(&start));
+        assert!(caret.to_message(60).content.contains("(cursor on line 26)"));
+
+        let no_selection = CodeContext { selection: None, ..context };
+        assert_eq!(no_selection.excerpt_range(30), 0..30);
+    }
+}
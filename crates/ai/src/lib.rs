@@ -1,4 +1,15 @@
+pub mod completion;
+pub mod context;
+pub mod tokens;
+
+pub use completion::{
+    build_completion_request, DEFAULT_COMPLETION_PREFIX_CHARS, DEFAULT_COMPLETION_SUFFIX_CHARS,
+};
+pub use context::{build_context_request, CodeContext, DEFAULT_CONTEXT_CHARS};
+pub use tokens::{estimate_tokens, truncate_to_tokens, CHARS_PER_TOKEN};
+
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use thiserror::Error;
 use tokio::sync::mpsc;
 
@@ -10,13 +21,68 @@ pub enum AiError {
     #[error("keyring error: {0}")]
     Keyring(String),
 
+    #[error("unauthorized: check the OpenRouter API key ({body})")]
+    Unauthorized { body: String },
+
+    #[error("rate limited: {body}")]
+    RateLimited { retry_after: Option<Duration>, body: String },
+
+    #[error("server error {status}: {body}")]
+    ServerError { status: u16, body: String },
+
+    #[error("api error {status}: {body}")]
+    Api { status: u16, body: String },
+
+    #[error("network error: {0}")]
+    Network(String),
+
     #[error("http error: {0}")]
-    Http(#[from] reqwest::Error),
+    Http(reqwest::Error),
 
     #[error("json error: {0}")]
     Json(#[from] serde_json::Error),
 }
 
+impl From<reqwest::Error> for AiError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_connect() || e.is_timeout() {
+            AiError::Network(e.to_string())
+        } else {
+            AiError::Http(e)
+        }
+    }
+}
+
+impl AiError {
+    /// Classify a non-success HTTP response by status code.
+    pub fn from_status(status: u16, retry_after: Option<&str>, body: String) -> Self {
+        match status {
+            401 | 403 => AiError::Unauthorized { body },
+            429 => AiError::RateLimited {
+                retry_after: retry_after.and_then(|v| v.trim().parse().ok()).map(Duration::from_secs),
+                body,
+            },
+            500..=599 => AiError::ServerError { status, body },
+            _ => AiError::Api { status, body },
+        }
+    }
+}
+
+/// Turn a non-success response into a structured error, keeping its body text.
+async fn check_status(resp: reqwest::Response) -> Result<reqwest::Response, AiError> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+    let retry_after = resp
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let body = resp.text().await.unwrap_or_default();
+    Err(AiError::from_status(status.as_u16(), retry_after.as_deref(), body))
+}
+
 #[derive(Debug, Clone)]
 pub struct KeyStore {
     service: String,
@@ -60,18 +126,32 @@ impl KeyStore {
     }
 }
 
+const OPENROUTER_BASE_URL: &str = "https://openrouter.ai/api/v1";
+
 #[derive(Debug, Clone)]
 pub struct OpenRouterClient {
     http: reqwest::Client,
+    base_url: String,
 }
 
 impl OpenRouterClient {
     pub fn new() -> Result<Self, AiError> {
         Ok(Self {
             http: reqwest::Client::new(),
+            base_url: OPENROUTER_BASE_URL.to_string(),
         })
     }
 
+    /// Point the client at a different OpenAI-compatible endpoint.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    fn completions_url(&self) -> String {
+        format!("{}/chat/completions", self.base_url.trim_end_matches('/'))
+    }
+
     pub async fn chat_completions(
         &self,
         api_key: &str,
@@ -79,13 +159,13 @@ impl OpenRouterClient {
     ) -> Result<ChatCompletionsResponse, AiError> {
         let resp = self
             .http
-            .post("https://openrouter.ai/api/v1/chat/completions")
+            .post(self.completions_url())
             .bearer_auth(api_key)
             .header("Content-Type", "application/json")
             .json(&request)
             .send()
-            .await?
-            .error_for_status()?;
+            .await?;
+        let resp = check_status(resp).await?;
 
         Ok(resp.json::<ChatCompletionsResponse>().await?)
     }
@@ -100,64 +180,17 @@ impl OpenRouterClient {
 
         let resp = self
             .http
-            .post("https://openrouter.ai/api/v1/chat/completions")
+            .post(self.completions_url())
             .bearer_auth(api_key)
             .header("Content-Type", "application/json")
             .json(&request)
             .send()
-            .await?
-            .error_for_status()?;
+            .await?;
+        let resp = check_status(resp).await?;
 
         let (tx, rx) = mpsc::channel(buffer);
 
-        tokio::spawn(async move {
-            let mut stream = resp.bytes_stream();
-            let mut buf: Vec<u8> = Vec::new();
-
-            while let Some(item) = futures_util::StreamExt::next(&mut stream).await {
-                match item {
-                    Ok(chunk) => {
-                        buf.extend_from_slice(&chunk);
-
-                        while let Some((event, rest)) = split_sse_event(&buf) {
-                            buf = rest;
-
-                            let data = match sse_extract_data(event.as_slice()) {
-                                Ok(v) => v,
-                                Err(e) => {
-                                    let _ = tx.send(Err(e)).await;
-                                    return;
-                                }
-                            };
-
-                            if data == "[DONE]" {
-                                return;
-                            }
-
-                            match serde_json::from_str::<ChatCompletionsStreamResponse>(&data) {
-                                Ok(r) => {
-                                    for choice in r.choices {
-                                        if let Some(delta) = choice.delta.and_then(|d| d.content) {
-                                            if !delta.is_empty() {
-                                                let _ = tx.send(Ok(delta)).await;
-                                            }
-                                        }
-                                    }
-                                }
-                                Err(e) => {
-                                    let _ = tx.send(Err(AiError::Json(e))).await;
-                                    return;
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        let _ = tx.send(Err(AiError::Http(e))).await;
-                        return;
-                    }
-                }
-            }
-        });
+        tokio::spawn(forward_sse_deltas(resp.bytes_stream(), tx));
 
         Ok(rx)
     }
@@ -257,6 +290,66 @@ pub struct ChatStreamDelta {
     pub content: Option<String>,
 }
 
+/// Forward the content deltas of an SSE chat stream to `tx`.
+///
+/// Stops reading (and drops the body stream) as soon as the receiver is gone, so an
+/// abandoned request does not keep downloading tokens.
+async fn forward_sse_deltas<S, B, E>(stream: S, tx: mpsc::Sender<Result<String, AiError>>)
+where
+    S: futures_util::Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+    E: Into<AiError>,
+{
+    let mut stream = std::pin::pin!(stream);
+    let mut buf: Vec<u8> = Vec::new();
+
+    while !tx.is_closed() {
+        let Some(item) = futures_util::StreamExt::next(&mut stream).await else {
+            return;
+        };
+        let chunk = match item {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                let _ = tx.send(Err(e.into())).await;
+                return;
+            }
+        };
+        buf.extend_from_slice(chunk.as_ref());
+
+        while let Some((event, rest)) = split_sse_event(&buf) {
+            buf = rest;
+
+            let data = match sse_extract_data(event.as_slice()) {
+                Ok(v) => v,
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+            };
+
+            if data == "[DONE]" {
+                return;
+            }
+
+            match serde_json::from_str::<ChatCompletionsStreamResponse>(&data) {
+                Ok(r) => {
+                    for choice in r.choices {
+                        if let Some(delta) = choice.delta.and_then(|d| d.content) {
+                            if !delta.is_empty() && tx.send(Ok(delta)).await.is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(AiError::Json(e))).await;
+                    return;
+                }
+            }
+        }
+    }
+}
+
 fn split_sse_event(buf: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
     let mut i = 0;
     while i < buf.len() {
@@ -292,3 +385,67 @@ fn sse_extract_data(event: &[u8]) -> Result<String, AiError> {
 
     Ok(data_lines.join("\n"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_rate_limited_response_parses_retry_after() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            use std::io::{Read, Write};
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut request = [0u8; 4096];
+            let _ = socket.read(&mut request).unwrap();
+            let body = "slow down";
+            let response = format!(
+                "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 7\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            socket.write_all(response.as_bytes()).unwrap();
+        });
+
+        let client = OpenRouterClient::new().unwrap().with_base_url(format!("http://{addr}/v1"));
+        let request = ChatCompletionsRequest {
+            model: "m".to_string(),
+            messages: Vec::new(),
+            temperature: None,
+            max_tokens: None,
+            stream: None,
+        };
+        let err = client.chat_completions("key", request).await.unwrap_err();
+        server.join().unwrap();
+        match err {
+            AiError::RateLimited { retry_after, body } => {
+                assert_eq!(retry_after, Some(Duration::from_secs(7)));
+                assert_eq!(body, "slow down");
+            }
+            other => panic!("expected RateLimited, got {other:?}"),
+        }
+        assert!(matches!(AiError::from_status(401, None, String::new()), AiError::Unauthorized { .. }));
+        assert!(matches!(AiError::from_status(503, None, String::new()), AiError::ServerError { status: 503, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_stream_stops_when_receiver_dropped() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&polls);
+        let chunks = futures_util::stream::iter(0..100).map(move |i| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let event = format!("data: {{\"choices\":[{{\"index\":0,\"delta\":{{\"content\":\"t{i}\"}}}}]}}\n\n");
+            Ok::<_, AiError>(event.into_bytes())
+        });
+        let (tx, mut rx) = mpsc::channel(1);
+        let task = tokio::spawn(forward_sse_deltas(chunks, tx));
+
+        assert_eq!(rx.recv().await.unwrap().unwrap(), "t0");
+        drop(rx);
+        task.await.unwrap();
+        assert!(polls.load(Ordering::SeqCst) <= 3);
+    }
+}
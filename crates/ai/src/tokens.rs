@@ -0,0 +1,47 @@
+//! Rough token counts for sizing model context.
+
+/// Average chars per token assumed by `estimate_tokens`.
+pub const CHARS_PER_TOKEN: usize = 4;
+
+/// Approximate number of tokens `text` uses: one per `CHARS_PER_TOKEN` chars, rounded up.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// The longest prefix of `text` that ends at a line boundary and fits in
+/// `max_tokens`. Text that already fits is returned whole; if even the first line is
+/// too long the result is empty.
+pub fn truncate_to_tokens(text: &str, max_tokens: usize) -> &str {
+    if estimate_tokens(text) <= max_tokens {
+        return text;
+    }
+    let max_chars = max_tokens * CHARS_PER_TOKEN;
+    let mut end = 0;
+    for (chars, (byte, c)) in text.char_indices().enumerate() {
+        if chars >= max_chars {
+            break;
+        }
+        if c == '\n' {
+            end = byte + 1;
+        }
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_at_line_boundary_within_budget() {
+        let text = "fn a() {}\nfn b() {}\nfn c() {}\n";
+        assert_eq!(estimate_tokens(text), 8);
+        assert_eq!(truncate_to_tokens(text, 8), text);
+
+        let cut = truncate_to_tokens(text, 6);
+        assert_eq!(cut, "fn a() {}\nfn b() {}\n");
+        assert!(estimate_tokens(cut) <= 6);
+        assert_eq!(truncate_to_tokens(text, 4), "fn a() {}\n");
+        assert_eq!(truncate_to_tokens("é".repeat(40).as_str(), 3), "");
+    }
+}
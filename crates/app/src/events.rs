@@ -3,7 +3,10 @@
 //! Provides a channel-based system for background services to communicate
 //! with the Slint UI thread safely.
 
-use std::sync::Arc;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 
@@ -31,6 +34,11 @@ pub enum UiEvent {
         filename: String,
         is_dirty: bool,
     },
+    /// Writing a file to disk failed
+    FileSaveFailed {
+        filename: String,
+        error: String,
+    },
     /// Explorer tree needs refresh
     ExplorerRefresh,
     /// AI chat response chunk received
@@ -55,6 +63,109 @@ pub enum UiEvent {
     StatusUpdate {
         message: String,
     },
+    /// A long-running task made progress; `total` is `None` when unknown
+    Progress {
+        id: u64,
+        label: String,
+        done: usize,
+        total: Option<usize>,
+    },
+    /// A long-running task finished
+    ProgressComplete {
+        id: u64,
+    },
+}
+
+impl UiEvent {
+    /// The outcome of saving `filename`: `FileSaveStatus` when the write succeeded,
+    /// `FileSaveFailed` with the error otherwise.
+    pub fn from_save_result<E: std::fmt::Display>(filename: String, result: Result<(), E>) -> Self {
+        match result {
+            Ok(()) => UiEvent::FileSaveStatus { filename, is_dirty: false },
+            Err(e) => UiEvent::FileSaveFailed { filename, error: e.to_string() },
+        }
+    }
+
+    /// Name of the variant, for logging and counters.
+    #[allow(dead_code)]
+    pub fn name(&self) -> &'static str {
+        match self {
+            UiEvent::EditorContentChanged { .. } => "EditorContentChanged",
+            UiEvent::CursorMoved { .. } => "CursorMoved",
+            UiEvent::FileLoaded { .. } => "FileLoaded",
+            UiEvent::FileSaveStatus { .. } => "FileSaveStatus",
+            UiEvent::FileSaveFailed { .. } => "FileSaveFailed",
+            UiEvent::ExplorerRefresh => "ExplorerRefresh",
+            UiEvent::ChatResponseChunk { .. } => "ChatResponseChunk",
+            UiEvent::ChatResponseComplete => "ChatResponseComplete",
+            UiEvent::ChatError { .. } => "ChatError",
+            UiEvent::DiffAvailable { .. } => "DiffAvailable",
+            UiEvent::DiffHunkResolved { .. } => "DiffHunkResolved",
+            UiEvent::StatusUpdate { .. } => "StatusUpdate",
+            UiEvent::Progress { .. } => "Progress",
+            UiEvent::ProgressComplete { .. } => "ProgressComplete",
+        }
+    }
+
+    /// Errors and completion signals, which bypass throttling and the regular queue.
+    /// Chat chunks go the same way so a completion can never overtake them.
+    pub fn is_priority(&self) -> bool {
+        matches!(
+            self,
+            UiEvent::ChatError { .. }
+                | UiEvent::FileSaveFailed { .. }
+                | UiEvent::ChatResponseChunk { .. }
+                | UiEvent::ChatResponseComplete
+        )
+    }
+
+    /// Text to show in the status bar for this event, if it has any.
+    pub fn status_message(&self) -> Option<String> {
+        match self {
+            UiEvent::FileLoaded { filename, .. } => Some(format!("Loaded: {filename}")),
+            UiEvent::FileSaveStatus { filename, is_dirty } => {
+                let status = if *is_dirty { "Modified" } else { "Saved" };
+                Some(format!("{filename}: {status}"))
+            }
+            UiEvent::FileSaveFailed { filename, error } => {
+                Some(format!("Failed to save {filename}: {error}"))
+            }
+            UiEvent::DiffAvailable { hunk_count } => Some(format!("{hunk_count} diff hunks available")),
+            UiEvent::DiffHunkResolved { remaining } => Some(format!("{remaining} diff hunks remaining")),
+            UiEvent::StatusUpdate { message } => Some(message.clone()),
+            UiEvent::Progress { label, done, total: Some(total), .. } => {
+                Some(format!("{label} ({done}/{total})"))
+            }
+            UiEvent::Progress { label, done, total: None, .. } => Some(format!("{label} ({done})")),
+            _ => None,
+        }
+    }
+}
+
+/// Tracks which progress task, if any, the status bar is showing, so finishing one
+/// task does not clear a message that arrived since.
+#[derive(Debug, Default)]
+pub struct StatusTracker {
+    progress: Option<u64>,
+}
+
+impl StatusTracker {
+    /// The status bar text after `event`, or `None` to leave it as it is.
+    pub fn apply(&mut self, event: &UiEvent) -> Option<String> {
+        if let UiEvent::ProgressComplete { id } = event {
+            if self.progress != Some(*id) {
+                return None;
+            }
+            self.progress = None;
+            return Some(String::new());
+        }
+        let message = event.status_message()?;
+        self.progress = match event {
+            UiEvent::Progress { id, .. } => Some(*id),
+            _ => None,
+        };
+        Some(message)
+    }
 }
 
 /// Configuration for event throttling.
@@ -64,6 +175,8 @@ pub struct ThrottleConfig {
     pub editor_repaint_interval: Duration,
     /// Minimum interval between cursor update events  
     pub cursor_update_interval: Duration,
+    /// Minimum interval between progress events
+    pub progress_update_interval: Duration,
 }
 
 impl Default for ThrottleConfig {
@@ -71,14 +184,67 @@ impl Default for ThrottleConfig {
         Self {
             editor_repaint_interval: Duration::from_millis(16), // ~60fps
             cursor_update_interval: Duration::from_millis(50),  // 20 updates/sec
+            progress_update_interval: Duration::from_millis(100),
         }
     }
 }
 
+/// Hooks for watching traffic through the event bridge.
+pub trait EventObserver: Send + Sync {
+    /// An event was queued; `queue_depth` counts regular events waiting, this one included.
+    fn on_send(&self, _event: &UiEvent, _queue_depth: usize) {}
+
+    /// A throttled event was superseded by a newer one of the same kind and dropped.
+    fn on_throttled(&self, _event: &UiEvent) {}
+}
+
+/// Event counts collected by `EventStats`.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventCounters {
+    /// Events sent, by variant name
+    pub sent: HashMap<&'static str, usize>,
+    /// Events dropped by throttling, by variant name
+    pub throttled: HashMap<&'static str, usize>,
+    /// Most regular events seen waiting in the queue at once
+    pub max_queue_depth: usize,
+}
+
+/// An observer that counts events; read it with `snapshot`.
+#[allow(dead_code)]
+#[derive(Debug, Default)]
+pub struct EventStats {
+    counters: Mutex<EventCounters>,
+}
+
+impl EventStats {
+    #[allow(dead_code)]
+    pub fn snapshot(&self) -> EventCounters {
+        self.counters.lock().unwrap().clone()
+    }
+}
+
+impl EventObserver for EventStats {
+    fn on_send(&self, event: &UiEvent, queue_depth: usize) {
+        let mut counters = self.counters.lock().unwrap();
+        *counters.sent.entry(event.name()).or_default() += 1;
+        counters.max_queue_depth = counters.max_queue_depth.max(queue_depth);
+    }
+
+    fn on_throttled(&self, event: &UiEvent) {
+        *self.counters.lock().unwrap().throttled.entry(event.name()).or_default() += 1;
+    }
+}
+
 /// Tracks last event times for throttling.
 struct ThrottleState {
     last_editor_repaint: Option<Instant>,
     last_cursor_update: Option<Instant>,
+    /// Last delivered progress update, per task id
+    last_progress_update: HashMap<u64, Instant>,
+    /// Latest suppressed event of each throttled kind (of each task, for progress),
+    /// delivered once its interval has passed so the final update of a burst is not lost.
+    pending: Vec<UiEvent>,
 }
 
 impl ThrottleState {
@@ -86,6 +252,87 @@ impl ThrottleState {
         Self {
             last_editor_repaint: None,
             last_cursor_update: None,
+            last_progress_update: HashMap::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Pass `event` through if it may be delivered now, otherwise hold it back in
+    /// place of any earlier suppressed event it supersedes.
+    fn admit(
+        &mut self,
+        event: UiEvent,
+        config: &ThrottleConfig,
+        observer: Option<&dyn EventObserver>,
+    ) -> Option<UiEvent> {
+        if let Some(index) = self.pending.iter().position(|p| Self::supersedes(&event, p)) {
+            let dropped = self.pending.remove(index);
+            if let Some(observer) = observer {
+                observer.on_throttled(&dropped);
+            }
+        }
+        if self.should_emit(&event, config) {
+            Some(event)
+        } else {
+            self.pending.push(event);
+            None
+        }
+    }
+
+    /// Whether `event` replaces the held-back `pending`: the same kind of event, and for
+    /// progress the same task.
+    fn supersedes(event: &UiEvent, pending: &UiEvent) -> bool {
+        match (event, pending) {
+            (UiEvent::Progress { id, .. }, UiEvent::Progress { id: pending_id, .. }) => id == pending_id,
+            _ => std::mem::discriminant(event) == std::mem::discriminant(pending),
+        }
+    }
+
+    /// When the earliest held-back event may be delivered.
+    fn next_due(&self, config: &ThrottleConfig) -> Option<Instant> {
+        self.pending.iter().map(|event| self.due(event, config)).min()
+    }
+
+    /// Remove and return a held-back event whose interval has passed.
+    fn take_due(&mut self, config: &ThrottleConfig) -> Option<UiEvent> {
+        let now = Instant::now();
+        let index = self.pending.iter().position(|event| self.due(event, config) <= now)?;
+        let event = self.pending.remove(index);
+        self.should_emit(&event, config);
+        Some(event)
+    }
+
+    fn due(&self, event: &UiEvent, config: &ThrottleConfig) -> Instant {
+        let (last, interval) = match event {
+            UiEvent::EditorContentChanged { .. } => {
+                (self.last_editor_repaint, config.editor_repaint_interval)
+            }
+            UiEvent::CursorMoved { .. } => (self.last_cursor_update, config.cursor_update_interval),
+            UiEvent::Progress { id, .. } => {
+                (self.last_progress_update.get(id).copied(), config.progress_update_interval)
+            }
+            _ => (None, Duration::ZERO),
+        };
+        last.map_or_else(Instant::now, |last| last + interval)
+    }
+
+    /// Check if `event` may be delivered now. Progress is throttled per task, and an
+    /// update that reaches its total is never dropped, so the last update of a task
+    /// always arrives.
+    fn should_emit(&mut self, event: &UiEvent, config: &ThrottleConfig) -> bool {
+        match event {
+            UiEvent::EditorContentChanged { .. } => self.should_emit_editor_repaint(config),
+            UiEvent::CursorMoved { .. } => self.should_emit_cursor_update(config),
+            UiEvent::Progress { id, done, total, .. } => {
+                let finished = total.is_some_and(|total| *done >= total);
+                self.should_emit_progress_update(*id, config) || finished
+            }
+            UiEvent::ProgressComplete { id } => {
+                self.last_progress_update.remove(id);
+                true
+            }
+            // All other events pass through without throttling
+            _ => true,
         }
     }
 
@@ -110,25 +357,50 @@ impl ThrottleState {
             }
         }
     }
+
+    fn should_emit_progress_update(&mut self, id: u64, config: &ThrottleConfig) -> bool {
+        let now = Instant::now();
+        match self.last_progress_update.get(&id) {
+            Some(last) if now.duration_since(*last) < config.progress_update_interval => false,
+            _ => {
+                self.last_progress_update.insert(id, now);
+                true
+            }
+        }
+    }
 }
 
 /// Sender side of the event bridge - used by background services.
 #[derive(Clone)]
 pub struct EventSender {
     tx: mpsc::Sender<UiEvent>,
+    /// Unbounded path for events that must not wait behind queued repaints
+    priority_tx: mpsc::UnboundedSender<UiEvent>,
     #[allow(dead_code)]
     config: Arc<ThrottleConfig>,
+    observer: Option<Arc<dyn EventObserver>>,
 }
 
 impl EventSender {
-    /// Send an event to the UI thread.
+    /// Send an event to the UI thread. Priority events skip the regular queue.
     pub async fn send(&self, event: UiEvent) -> Result<(), mpsc::error::SendError<UiEvent>> {
+        self.observe_send(&event);
+        if event.is_priority() {
+            return self.priority_tx.send(event);
+        }
         self.tx.send(event).await
     }
 
     /// Try to send an event without blocking.
     #[allow(dead_code)]
     pub fn try_send(&self, event: UiEvent) -> Result<(), mpsc::error::TrySendError<UiEvent>> {
+        self.observe_send(&event);
+        if event.is_priority() {
+            return self
+                .priority_tx
+                .send(event)
+                .map_err(|e| mpsc::error::TrySendError::Closed(e.0));
+        }
         self.tx.try_send(event)
     }
 
@@ -137,57 +409,69 @@ impl EventSender {
     pub fn config(&self) -> &ThrottleConfig {
         &self.config
     }
+
+    fn observe_send(&self, event: &UiEvent) {
+        if let Some(observer) = &self.observer {
+            let queued = self.tx.max_capacity() - self.tx.capacity();
+            let depth = if event.is_priority() { queued } else { queued + 1 };
+            observer.on_send(event, depth);
+        }
+    }
 }
 
 /// Receiver side of the event bridge - used by the UI thread.
 pub struct EventReceiver {
     rx: mpsc::Receiver<UiEvent>,
+    priority_rx: mpsc::UnboundedReceiver<UiEvent>,
     throttle_state: ThrottleState,
     config: Arc<ThrottleConfig>,
+    observer: Option<Arc<dyn EventObserver>>,
 }
 
 impl EventReceiver {
     /// Receive the next event, applying throttling rules.
+    ///
+    /// Priority events are delivered before anything in the regular queue. A throttled
+    /// event that was held back is delivered once its interval has passed, even if
+    /// nothing else arrives.
     pub async fn recv(&mut self) -> Option<UiEvent> {
         loop {
-            let event = self.rx.recv().await?;
-            
-            // Apply throttling based on event type
-            let should_emit = match &event {
-                UiEvent::EditorContentChanged { .. } => {
-                    self.throttle_state.should_emit_editor_repaint(&self.config)
-                }
-                UiEvent::CursorMoved { .. } => {
-                    self.throttle_state.should_emit_cursor_update(&self.config)
-                }
-                // All other events pass through without throttling
-                _ => true,
+            if let Ok(event) = self.priority_rx.try_recv() {
+                return Some(event);
+            }
+            if let Some(event) = self.throttle_state.take_due(&self.config) {
+                return Some(event);
+            }
+            let due = self.throttle_state.next_due(&self.config);
+            let event = tokio::select! {
+                biased;
+                Some(event) = self.priority_rx.recv() => return Some(event),
+                event = self.rx.recv() => event,
+                _ = tokio::time::sleep_until(due.unwrap_or_else(Instant::now).into()), if due.is_some() => continue,
             };
-
-            if should_emit {
+            // Once every sender is gone, hand out whatever is still held back.
+            let Some(event) = event else {
+                return self.throttle_state.pending.pop();
+            };
+            if let Some(event) = self.throttle_state.admit(event, &self.config, self.observer.as_deref()) {
                 return Some(event);
             }
-            // If throttled, continue to next event
         }
     }
 
     /// Try to receive an event without blocking.
     #[allow(dead_code)]
     pub fn try_recv(&mut self) -> Result<UiEvent, mpsc::error::TryRecvError> {
+        if let Ok(event) = self.priority_rx.try_recv() {
+            return Ok(event);
+        }
         loop {
-            let event = self.rx.try_recv()?;
-            
-            let should_emit = match &event {
-                UiEvent::EditorContentChanged { .. } => {
-                    self.throttle_state.should_emit_editor_repaint(&self.config)
-                }
-                UiEvent::CursorMoved { .. } => {
-                    self.throttle_state.should_emit_cursor_update(&self.config)
-                }
-                _ => true,
+            let event = match self.rx.try_recv() {
+                Ok(event) => event,
+                Err(e) => return self.throttle_state.take_due(&self.config).ok_or(e),
             };
 
-            if should_emit {
+            if let Some(event) = self.throttle_state.admit(event, &self.config, self.observer.as_deref()) {
                 return Ok(event);
             }
         }
@@ -205,19 +489,33 @@ impl EventReceiver {
 pub fn create_event_bridge(
     buffer_size: usize,
     config: Option<ThrottleConfig>,
+) -> (EventSender, EventReceiver) {
+    create_observed_event_bridge(buffer_size, config, None)
+}
+
+/// Create an event bridge that reports sends and throttled drops to `observer`.
+pub fn create_observed_event_bridge(
+    buffer_size: usize,
+    config: Option<ThrottleConfig>,
+    observer: Option<Arc<dyn EventObserver>>,
 ) -> (EventSender, EventReceiver) {
     let (tx, rx) = mpsc::channel(buffer_size);
+    let (priority_tx, priority_rx) = mpsc::unbounded_channel();
     let config = Arc::new(config.unwrap_or_default());
 
     let sender = EventSender {
         tx,
+        priority_tx,
         config: Arc::clone(&config),
+        observer: observer.clone(),
     };
 
     let receiver = EventReceiver {
         rx,
+        priority_rx,
         throttle_state: ThrottleState::new(),
         config,
+        observer,
     };
 
     (sender, receiver)
@@ -263,6 +561,74 @@ where
     })
 }
 
+/// Why an event processor stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessorExit {
+    /// Every sender was dropped and the queue drained
+    Closed,
+    /// The handler panicked while handling an event
+    HandlerPanicked,
+}
+
+/// Like `spawn_event_processor`, but the handle resolves to why the loop ended and
+/// `on_close` is called with the same value once it has.
+///
+/// A panic in the handler stops the processor at the next event, since the handler
+/// runs later on the UI thread.
+#[allow(dead_code)]
+pub fn spawn_event_processor_with_exit<W, F, C>(
+    receiver: EventReceiver,
+    weak_window: slint::Weak<W>,
+    handler: F,
+    on_close: C,
+) -> tokio::task::JoinHandle<ProcessorExit>
+where
+    W: slint::ComponentHandle + 'static,
+    F: Fn(&W, UiEvent) + Send + Sync + Clone + 'static,
+    C: FnOnce(ProcessorExit) + Send + 'static,
+{
+    let panicked = Arc::new(AtomicBool::new(false));
+    let dispatch = {
+        let panicked = Arc::clone(&panicked);
+        move |event: UiEvent| {
+            let weak = weak_window.clone();
+            let handler = handler.clone();
+            let flag = Arc::clone(&panicked);
+            invoke_ui_update(move || {
+                if let Some(window) = weak.upgrade() {
+                    let result = std::panic::catch_unwind(AssertUnwindSafe(|| handler(&window, event)));
+                    if result.is_err() {
+                        flag.store(true, Ordering::SeqCst);
+                    }
+                }
+            });
+            !panicked.load(Ordering::SeqCst)
+        }
+    };
+    tokio::spawn(process_events(receiver, dispatch, on_close))
+}
+
+/// Feed events to `dispatch` until the channel closes or `dispatch` reports that the
+/// handler failed.
+async fn process_events<D, C>(mut receiver: EventReceiver, mut dispatch: D, on_close: C) -> ProcessorExit
+where
+    D: FnMut(UiEvent) -> bool,
+    C: FnOnce(ProcessorExit),
+{
+    let exit = loop {
+        match receiver.recv().await {
+            Some(event) => {
+                if !dispatch(event) {
+                    break ProcessorExit::HandlerPanicked;
+                }
+            }
+            None => break ProcessorExit::Closed,
+        }
+    };
+    on_close(exit);
+    exit
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -282,6 +648,20 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_save_failure_reaches_status_message() {
+        let (sender, mut receiver) = create_event_bridge(16, None);
+        let result: Result<(), String> = Err("permission denied".to_string());
+        sender.send(UiEvent::from_save_result("main.rs".to_string(), result)).await.unwrap();
+
+        let event = receiver.recv().await.unwrap();
+        assert!(matches!(event, UiEvent::FileSaveFailed { .. }));
+        assert_eq!(
+            event.status_message().as_deref(),
+            Some("Failed to save main.rs: permission denied")
+        );
+    }
+
     #[tokio::test]
     async fn test_throttling() {
         let config = ThrottleConfig {
@@ -302,4 +682,152 @@ mod tests {
             _ => panic!("unexpected event"),
         }
     }
+
+    #[tokio::test]
+    async fn test_progress_is_throttled_but_final_update_arrives() {
+        let (sender, mut receiver) = create_event_bridge(16, None);
+        for done in 1..=5 {
+            let label = "Searching".to_string();
+            sender.send(UiEvent::Progress { id: 7, label, done, total: Some(5) }).await.unwrap();
+        }
+        sender.send(UiEvent::ProgressComplete { id: 7 }).await.unwrap();
+
+        let mut delivered = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            delivered.push(event);
+        }
+        assert_eq!(delivered.len(), 3);
+        assert!(matches!(delivered[0], UiEvent::Progress { done: 1, .. }));
+        assert!(matches!(delivered[1], UiEvent::Progress { done: 5, .. }));
+        assert!(matches!(delivered[2], UiEvent::ProgressComplete { id: 7 }));
+    }
+
+    #[tokio::test]
+    async fn test_progress_is_throttled_per_task() {
+        let (sender, mut receiver) = create_event_bridge(16, None);
+        for id in [1, 2] {
+            let label = format!("Task {id}");
+            sender.send(UiEvent::Progress { id, label, done: 1, total: Some(5) }).await.unwrap();
+        }
+
+        let mut delivered = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            delivered.push(event);
+        }
+        assert!(matches!(delivered[..], [UiEvent::Progress { id: 1, .. }, UiEvent::Progress { id: 2, .. }]));
+    }
+
+    #[tokio::test]
+    async fn test_held_back_progress_is_kept_per_task() {
+        let config = ThrottleConfig {
+            progress_update_interval: Duration::from_millis(30),
+            ..Default::default()
+        };
+        let (sender, mut receiver) = create_event_bridge(16, Some(config));
+        for done in 1..=2 {
+            for id in [1, 2] {
+                let label = format!("Task {id}");
+                sender.send(UiEvent::Progress { id, label, done, total: Some(5) }).await.unwrap();
+            }
+        }
+        while receiver.try_recv().is_ok() {}
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        let mut flushed = Vec::new();
+        for _ in 0..2 {
+            match tokio::time::timeout(Duration::from_secs(1), receiver.recv()).await.unwrap() {
+                Some(UiEvent::Progress { id, done, .. }) => flushed.push((id, done)),
+                other => panic!("unexpected event: {other:?}"),
+            }
+        }
+        flushed.sort();
+        assert_eq!(flushed, vec![(1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn test_progress_complete_keeps_newer_status() {
+        let mut status = StatusTracker::default();
+        let progress = UiEvent::Progress { id: 3, label: "Indexing".to_string(), done: 1, total: None };
+        assert_eq!(status.apply(&progress).as_deref(), Some("Indexing (1)"));
+        assert_eq!(status.apply(&UiEvent::ProgressComplete { id: 4 }), None);
+        assert_eq!(status.apply(&UiEvent::ProgressComplete { id: 3 }).as_deref(), Some(""));
+
+        status.apply(&progress);
+        let failed = UiEvent::FileSaveFailed { filename: "a.rs".to_string(), error: "denied".to_string() };
+        assert!(status.apply(&failed).is_some());
+        assert_eq!(status.apply(&UiEvent::ProgressComplete { id: 3 }), None);
+        assert_eq!(status.apply(&UiEvent::ExplorerRefresh), None);
+    }
+
+    #[tokio::test]
+    async fn test_suppressed_event_is_flushed_after_interval() {
+        let config = ThrottleConfig {
+            cursor_update_interval: Duration::from_millis(30),
+            ..Default::default()
+        };
+        let (sender, mut receiver) = create_event_bridge(16, Some(config));
+        sender.send(UiEvent::CursorMoved { line: 1, column: 0 }).await.unwrap();
+        sender.send(UiEvent::CursorMoved { line: 2, column: 4 }).await.unwrap();
+
+        assert!(matches!(receiver.recv().await, Some(UiEvent::CursorMoved { line: 1, .. })));
+        assert!(receiver.try_recv().is_err());
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        let event = tokio::time::timeout(Duration::from_secs(1), receiver.recv()).await.unwrap();
+        assert!(matches!(event, Some(UiEvent::CursorMoved { line: 2, column: 4 })));
+    }
+
+    #[tokio::test]
+    async fn test_priority_events_skip_queued_cursor_updates() {
+        let (sender, mut receiver) = create_event_bridge(64, None);
+        for i in 0..50 {
+            sender.send(UiEvent::CursorMoved { line: i, column: 0 }).await.unwrap();
+        }
+        sender.send(UiEvent::ChatError { message: "rate limited".to_string() }).await.unwrap();
+
+        match receiver.recv().await {
+            Some(UiEvent::ChatError { message }) => assert_eq!(message, "rate limited"),
+            other => panic!("unexpected event: {other:?}"),
+        }
+        assert!(matches!(receiver.recv().await, Some(UiEvent::CursorMoved { line: 0, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_processor_reports_closed_channel() {
+        let (sender, receiver) = create_event_bridge(16, None);
+        let (close_tx, close_rx) = tokio::sync::oneshot::channel();
+        let handle = tokio::spawn(process_events(receiver, |_| true, move |exit| {
+            let _ = close_tx.send(exit);
+        }));
+
+        sender.send(UiEvent::ExplorerRefresh).await.unwrap();
+        drop(sender);
+
+        assert_eq!(handle.await.unwrap(), ProcessorExit::Closed);
+        assert_eq!(close_rx.await.unwrap(), ProcessorExit::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_observer_counts_throttled_cursor_events() {
+        let stats = Arc::new(EventStats::default());
+        let config = ThrottleConfig {
+            cursor_update_interval: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let observer: Arc<dyn EventObserver> = stats.clone();
+        let (sender, mut receiver) = create_observed_event_bridge(16, Some(config), Some(observer));
+        for i in 0..5 {
+            sender.send(UiEvent::CursorMoved { line: i, column: 0 }).await.unwrap();
+        }
+
+        let mut delivered = 0;
+        while receiver.try_recv().is_ok() {
+            delivered += 1;
+        }
+        // One delivered, the newest held back for the flush, the rest dropped.
+        let counters = stats.snapshot();
+        assert_eq!(counters.sent["CursorMoved"], 5);
+        assert_eq!(counters.throttled["CursorMoved"], 5 - delivered - 1);
+        assert_eq!(counters.max_queue_depth, 5);
+    }
 }
@@ -3,6 +3,7 @@
 pub mod events;
 
 pub use events::{
-    create_event_bridge, invoke_ui_update, spawn_event_processor,
-    EventReceiver, EventSender, ThrottleConfig, UiEvent,
+    create_event_bridge, create_observed_event_bridge, invoke_ui_update, spawn_event_processor,
+    spawn_event_processor_with_exit, EventCounters, EventObserver, EventReceiver, EventSender,
+    EventStats, ProcessorExit, StatusTracker, ThrottleConfig, UiEvent,
 };
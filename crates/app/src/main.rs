@@ -1,10 +1,8 @@
-use directories::ProjectDirs;
-use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
 mod events;
-use events::{create_event_bridge, invoke_ui_update, UiEvent};
+use events::{create_event_bridge, invoke_ui_update, StatusTracker, UiEvent};
 
 slint::include_modules!();
 
@@ -15,39 +13,44 @@ struct EditorState {
     tabs: Vec<OpenTab>,
     /// Currently active tab index
     active_index: Option<usize>,
+    /// Columns per tab stop when showing file content
+    tab_width: usize,
+    /// Id given to the next opened tab
+    next_document_id: editor_core::DocumentId,
+    /// Autosave task, when the workspace enables autosave
+    autosave: Option<tokio::sync::mpsc::Sender<workspace::AutosaveRequest>>,
 }
 
 #[derive(Debug, Clone)]
 struct OpenTab {
-    path: PathBuf,
+    document_id: editor_core::DocumentId,
+    document: workspace::FileDocument,
     filename: String,
-    content: String,
-    dirty: bool,
     language: String,
 }
 
 impl EditorState {
-    fn new() -> Self {
-        Self::default()
+    fn new(tab_width: usize) -> Self {
+        Self { tab_width: tab_width.max(1), ..Self::default() }
     }
 
     fn open_file(&mut self, path: PathBuf) -> Result<usize, String> {
         // Check if already open
-        if let Some(idx) = self.tabs.iter().position(|t| t.path == path) {
+        if let Some(idx) = self.tabs.iter().position(|t| t.document.path == path) {
             self.active_index = Some(idx);
             return Ok(idx);
         }
         // Read file content
-        let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let document = workspace::FileDocument::open(&path).map_err(|e| e.to_string())?;
         let filename = path.file_name()
             .map(|s| s.to_string_lossy().to_string())
             .unwrap_or_else(|| "untitled".to_string());
         let language = detect_language(&path);
+        self.next_document_id += 1;
         let tab = OpenTab {
-            path,
+            document_id: self.next_document_id,
+            document,
             filename,
-            content,
-            dirty: false,
             language,
         };
         self.tabs.push(tab);
@@ -57,7 +60,7 @@ impl EditorState {
     }
 
     fn close_tab(&mut self, path: &Path) -> bool {
-        if let Some(idx) = self.tabs.iter().position(|t| t.path == path) {
+        if let Some(idx) = self.tabs.iter().position(|t| t.document.path == path) {
             self.tabs.remove(idx);
             if self.tabs.is_empty() {
                 self.active_index = None;
@@ -77,8 +80,53 @@ impl EditorState {
         self.active_index.and_then(|i| self.tabs.get(i))
     }
 
+    /// Save the active tab, returning the resulting save event. Unchanged content is
+    /// not rewritten, and a file modified on disk since it was opened is not overwritten.
+    fn save_active(&mut self) -> Option<UiEvent> {
+        let tab = self.active_index.and_then(|i| self.tabs.get_mut(i))?;
+        let result = tab.document.save().map(|_| ());
+        if result.is_ok() {
+            if let Some(autosave) = &self.autosave {
+                let _ = autosave.try_send(workspace::AutosaveRequest::Cancel { document_id: tab.document_id });
+            }
+        }
+        Some(UiEvent::from_save_result(tab.filename.clone(), result))
+    }
+
+    /// Insert typed text at the active tab's carets and queue an autosave of the result.
+    /// Returns false, changing nothing, for keys that don't produce text.
+    fn type_text(&mut self, text: &str) -> bool {
+        let is_text = |c: char| !c.is_control() || c == '\n' || c == '\t';
+        // Slint reports arrows and other special keys as private-use characters
+        if text.is_empty() || !text.chars().all(|c| is_text(c) && !('\u{f700}'..='\u{f8ff}').contains(&c)) {
+            return false;
+        }
+        let Some(tab) = self.active_index.and_then(|i| self.tabs.get_mut(i)) else {
+            return false;
+        };
+        tab.document.buffer.apply_text_to_selections(text);
+        if let Some(autosave) = &self.autosave {
+            let _ = autosave.try_send(workspace::AutosaveRequest::Edited {
+                document_id: tab.document_id,
+                path: Some(tab.document.path.clone()),
+                text: tab.document.buffer.doc.to_string(),
+            });
+        }
+        true
+    }
+
+    /// Record that the autosave task wrote a tab, returning the save event.
+    fn mark_saved(&mut self, document_id: editor_core::DocumentId) -> Option<UiEvent> {
+        let tab = self.tabs.iter_mut().find(|t| t.document_id == document_id)?;
+        let result = tab.document.sync_written();
+        Some(match result {
+            Ok(()) => UiEvent::FileSaveStatus { filename: tab.filename.clone(), is_dirty: tab.document.is_dirty() },
+            Err(e) => UiEvent::FileSaveFailed { filename: tab.filename.clone(), error: e.to_string() },
+        })
+    }
+
     fn set_active_by_path(&mut self, path: &Path) {
-        if let Some(idx) = self.tabs.iter().position(|t| t.path == path) {
+        if let Some(idx) = self.tabs.iter().position(|t| t.document.path == path) {
             self.active_index = Some(idx);
         }
     }
@@ -143,8 +191,12 @@ fn main() -> Result<(), slint::PlatformError> {
         }),
     ));
 
+    // User preferences shared by all workspaces
+    let global_settings = workspace::GlobalSettings::load();
+    window.set_editor_font_size(global_settings.font_size);
+
     // Editor state for managing open tabs
-    let editor_state = Arc::new(Mutex::new(EditorState::new()));
+    let editor_state = Arc::new(Mutex::new(EditorState::new(global_settings.tab_width)));
 
     // Build initial file tree and update UI
     {
@@ -217,22 +269,120 @@ fn main() -> Result<(), slint::PlatformError> {
     // Create the event bridge for UI-thread communication
     let (event_sender, mut event_receiver) = create_event_bridge(256, None);
 
+    // Handle save (Ctrl+S) - write the active tab and report the outcome
+    {
+        let editor_clone = Arc::clone(&editor_state);
+        let weak_file_save = weak.clone();
+        let event_tx = event_sender.clone();
+        window.on_save_file(move || {
+            let mut editor = editor_clone.lock().unwrap();
+            let Some(event) = editor.save_active() else {
+                return;
+            };
+            let _ = event_tx.try_send(event);
+            if let Some(w) = weak_file_save.upgrade() {
+                update_editor_ui(&w, &editor);
+            }
+        });
+    }
+
+    // Handle typing into the active tab
+    {
+        let editor_clone = Arc::clone(&editor_state);
+        let weak_typed = weak.clone();
+        window.on_text_typed(move |text| {
+            let mut editor = editor_clone.lock().unwrap();
+            if editor.type_text(&text) {
+                if let Some(w) = weak_typed.upgrade() {
+                    update_editor_ui(&w, &editor);
+                }
+            }
+        });
+    }
+
     // Spawn the event processor task
     {
         let weak_events = weak.clone();
+        let workspace_events = Arc::clone(&workspace);
+        let status_events = Arc::new(Mutex::new(StatusTracker::default()));
         handle.spawn(async move {
             while let Some(event) = event_receiver.recv().await {
                 let weak = weak_events.clone();
+                let workspace = Arc::clone(&workspace_events);
+                let status = Arc::clone(&status_events);
                 invoke_ui_update(move || {
                     if let Some(w) = weak.upgrade() {
-                        handle_ui_event(&w, event);
+                        handle_ui_event(&w, &workspace, &status, event);
                     }
                 });
             }
         });
     }
 
-    let initial_model = load_config().model;
+    // Autosave edited tabs once edits pause, if the workspace settings enable it
+    let autosave_delay = workspace.lock().unwrap().settings().autosave_delay();
+    if let Some(delay) = autosave_delay {
+        let (request_tx, request_rx) = tokio::sync::mpsc::channel(64);
+        let (saved_tx, mut saved_rx) = tokio::sync::mpsc::channel(64);
+        handle.spawn(workspace::run_autosave(request_rx, saved_tx, delay));
+        editor_state.lock().unwrap().autosave = Some(request_tx);
+
+        let editor_saved = Arc::clone(&editor_state);
+        let weak_autosave = weak.clone();
+        let event_tx = event_sender.clone();
+        handle.spawn(async move {
+            while let Some(event) = saved_rx.recv().await {
+                let ui_event = match event {
+                    editor_core::Event::DocumentSaved { document_id } => {
+                        let Some(ui_event) = editor_saved.lock().unwrap().mark_saved(document_id) else {
+                            continue;
+                        };
+                        let weak = weak_autosave.clone();
+                        let editor = Arc::clone(&editor_saved);
+                        invoke_ui_update(move || {
+                            if let Some(w) = weak.upgrade() {
+                                update_editor_ui(&w, &editor.lock().unwrap());
+                            }
+                        });
+                        ui_event
+                    }
+                    editor_core::Event::Error { message } => UiEvent::StatusUpdate { message },
+                    _ => continue,
+                };
+                let _ = event_tx.send(ui_event).await;
+            }
+        });
+    }
+
+    // Keep the explorer in sync with changes on disk
+    {
+        let watch_rx = {
+            let mut ws = workspace.lock().unwrap();
+            if let Err(e) = ws.start_watching() {
+                eprintln!("Failed to watch workspace: {e}");
+            }
+            ws.watch_events()
+        };
+        if let Some(watch_rx) = watch_rx {
+            let (core_tx, mut core_rx) = tokio::sync::mpsc::channel(64);
+            handle.spawn(workspace::run_tree_refresh(
+                Arc::clone(&workspace),
+                watch_rx,
+                core_tx,
+                std::time::Duration::from_millis(100),
+            ));
+            let event_tx = event_sender.clone();
+            handle.spawn(async move {
+                while let Some(event) = core_rx.recv().await {
+                    if let editor_core::Event::WorkspaceTreeUpdated = event {
+                        let _ = event_tx.send(UiEvent::ExplorerRefresh).await;
+                    }
+                }
+            });
+        }
+    }
+
+    let initial_model = global_settings.model().to_string();
     window.set_model_id(initial_model.clone().into());
     window.set_model_status(format!("Model: {initial_model}").into());
 
@@ -245,11 +395,9 @@ fn main() -> Result<(), slint::PlatformError> {
 
             handle_model.spawn(async move {
                 let status = tokio::task::spawn_blocking(move || {
-                    let cfg = AppConfig { model: model.clone() };
-                    match save_config(&cfg) {
-                        Ok(()) => Ok(model),
-                        Err(e) => Err(e),
-                    }
+                    let mut settings = workspace::GlobalSettings::load();
+                    settings.default_model = Some(model.clone());
+                    settings.save().map(|()| model)
                 })
                 .await
                 .ok();
@@ -359,16 +507,59 @@ fn main() -> Result<(), slint::PlatformError> {
         });
     }
 
+    // Chat service records the conversation; its events drive the chat panel
+    let chat_root = workspace.lock().unwrap().root().to_path_buf();
+    let chat = {
+        let (chat_tx, mut chat_rx) = tokio::sync::mpsc::channel(128);
+        let event_tx = event_sender.clone();
+        handle.spawn(async move {
+            while let Some(event) = chat_rx.recv().await {
+                let ui_event = match event {
+                    editor_core::Event::AiStreamDelta { delta, .. } => {
+                        UiEvent::ChatResponseChunk { content: delta }
+                    }
+                    editor_core::Event::ChatMessageAdded {
+                        role: editor_core::ChatRole::Assistant,
+                        ..
+                    } => UiEvent::ChatResponseComplete,
+                    editor_core::Event::Error { message } => UiEvent::ChatError { message },
+                    _ => continue,
+                };
+                let _ = event_tx.send(ui_event).await;
+            }
+        });
+        let history = workspace::ConversationHistory::load(&chat_root)
+            .map(workspace::ConversationHistory::into_state)
+            .unwrap_or_default();
+        // Show the restored conversation, since its turns are sent with the next message
+        if let Some(conversation) = history.active_conversation.and_then(|id| history.conversation(id)) {
+            window.set_chat_output(chat_transcript(conversation).into());
+        }
+        Arc::new(tokio::sync::Mutex::new(editor_core::ChatService::with_state(history, chat_tx)))
+    };
+
     // Chat handler using the event bridge for UI updates
     {
         let event_tx = event_sender.clone();
+        let editor_chat = Arc::clone(&editor_state);
         window.on_send_chat(move |message: slint::SharedString| {
             let message: String = message.into();
             let ai_service = ai_service.clone();
+            let chat = Arc::clone(&chat);
+            let code_context = editor_chat.lock().unwrap().active_tab().map(|tab| {
+                let (start, end) = tab.document.buffer.selections.primary_range();
+                ai::CodeContext {
+                    filename: Some(tab.filename.clone()),
+                    language: Some(tab.language.clone()),
+                    text: tab.document.buffer.doc.to_string(),
+                    selection: Some(start..end),
+                }
+            });
             let weak = weak.clone();
             let event_tx = event_tx.clone();
+            let chat_root = chat_root.clone();
 
-            let mut model = default_model();
+            let mut model = workspace::DEFAULT_MODEL_ID.to_string();
 
             if let Some(w) = weak.upgrade() {
                 let current = w.get_chat_output().to_string();
@@ -378,44 +569,52 @@ fn main() -> Result<(), slint::PlatformError> {
             }
 
             handle.spawn(async move {
-                let request = ai::ChatCompletionsRequest {
-                    model,
-                    messages: vec![ai::ChatMessage {
-                        role: "user".to_string(),
-                        content: message,
-                    }],
-                    temperature: None,
-                    max_tokens: None,
-                    stream: Some(true),
+                // Read the history and reserve the reply under one lock, so concurrent
+                // sends can't pick the same conversation or interleave their replies
+                let reserved = {
+                    let mut chat = chat.lock().await;
+                    let history = chat.state().active_conversation.map(|id| chat.history(id)).unwrap_or_default();
+                    chat.begin_reply(None, message.clone()).await.map(|reply| (reply, history))
                 };
-
-                let mut rx = match ai_service.send_chat_stream(request, 128).await {
-                    Ok(rx) => rx,
+                let (reply, history) = match reserved {
+                    Ok(reserved) => reserved,
                     Err(e) => {
-                        let _ = event_tx.send(UiEvent::ChatError {
-                            message: e.to_string(),
-                        }).await;
+                        let _ = event_tx.send(UiEvent::ChatError { message: e.to_string() }).await;
                         return;
                     }
                 };
+                let user_message = ai::ChatMessage {
+                    role: "user".to_string(),
+                    content: message.clone(),
+                };
+                let mut request = match &code_context {
+                    Some(context) => ai::build_context_request(model, context, &message, ai::DEFAULT_CONTEXT_CHARS),
+                    None => ai::ChatCompletionsRequest {
+                        model,
+                        messages: vec![user_message],
+                        temperature: None,
+                        max_tokens: None,
+                        stream: Some(true),
+                    },
+                };
+                let history = history.into_iter().map(|m| ai::ChatMessage {
+                    role: chat_role_name(m.role).to_string(),
+                    content: m.content,
+                });
+                request.messages.splice(0..0, history);
 
-                while let Some(item) = rx.recv().await {
-                    match item {
-                        Ok(delta) => {
-                            let _ = event_tx.send(UiEvent::ChatResponseChunk {
-                                content: delta,
-                            }).await;
-                        }
-                        Err(e) => {
-                            let _ = event_tx.send(UiEvent::ChatError {
-                                message: e.to_string(),
-                            }).await;
-                            return;
-                        }
+                match ai_service.send_chat_stream(request, 128).await {
+                    Ok(rx) => {
+                        let _ = editor_core::ChatService::stream_reply_shared(&chat, reply, rx).await;
+                    }
+                    Err(e) => {
+                        chat.lock().await.abort_reply(reply, e.to_string()).await;
                     }
                 }
-
-                let _ = event_tx.send(UiEvent::ChatResponseComplete).await;
+                let history = workspace::ConversationHistory::from_state(chat_root, chat.lock().await.state());
+                if let Err(e) = history.save() {
+                    eprintln!("Failed to save conversations: {e}");
+                }
             });
         });
     }
@@ -423,52 +622,38 @@ fn main() -> Result<(), slint::PlatformError> {
     window.run()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct AppConfig {
-    model: String,
-}
-
-fn default_model() -> String {
-    "openai/gpt-4o-mini".to_string()
-}
-
-fn config_path() -> Option<PathBuf> {
-    let dirs = ProjectDirs::from("dev", "text_editor", "ai_code_editor")?;
-    Some(dirs.config_dir().join("config.json"))
-}
-
-fn load_config() -> AppConfig {
-    let Some(path) = config_path() else {
-        return AppConfig {
-            model: default_model(),
-        };
-    };
-
-    let data = std::fs::read_to_string(path);
-    match data {
-        Ok(s) => serde_json::from_str::<AppConfig>(&s)
-            .ok()
-            .unwrap_or(AppConfig {
-                model: default_model(),
-            }),
-        Err(_) => AppConfig {
-            model: default_model(),
-        },
+/// A conversation in the chat panel's format, as built up by sending messages.
+fn chat_transcript(conversation: &editor_core::Conversation) -> String {
+    let mut out = String::new();
+    for message in &conversation.messages {
+        match message.role {
+            editor_core::ChatRole::User => out.push_str(&format!("You: {}\n\n", message.content)),
+            editor_core::ChatRole::Assistant => out.push_str(&format!("Assistant: {}\n\n", message.content)),
+            editor_core::ChatRole::System => {}
+        }
     }
+    out
 }
 
-fn save_config(cfg: &AppConfig) -> Result<(), String> {
-    let path = config_path().ok_or("no config directory")?;
-    if let Some(parent) = path.parent() {
-        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+fn chat_role_name(role: editor_core::ChatRole) -> &'static str {
+    match role {
+        editor_core::ChatRole::System => "system",
+        editor_core::ChatRole::User => "user",
+        editor_core::ChatRole::Assistant => "assistant",
     }
-    let json = serde_json::to_string_pretty(cfg).map_err(|e| e.to_string())?;
-    std::fs::write(path, json).map_err(|e| e.to_string())
 }
 
 /// Handle UI events from the event bridge.
 /// This function is called on the UI thread via invoke_from_event_loop.
-fn handle_ui_event(window: &AppWindow, event: UiEvent) {
+fn handle_ui_event(
+    window: &AppWindow,
+    workspace: &Mutex<workspace::WorkspaceService>,
+    status: &Mutex<StatusTracker>,
+    event: UiEvent,
+) {
+    if let Some(message) = status.lock().unwrap().apply(&event) {
+        window.set_status_message(message.into());
+    }
     match event {
         UiEvent::EditorContentChanged { start_line, end_line } => {
             // Editor content updates are handled via update_editor_ui
@@ -477,16 +662,12 @@ fn handle_ui_event(window: &AppWindow, event: UiEvent) {
         UiEvent::CursorMoved { line, column } => {
             window.set_cursor_position(format!("Ln {line}, Col {column}").into());
         }
-        UiEvent::FileLoaded { filename, language } => {
-            window.set_status_message(format!("Loaded: {filename}").into());
+        UiEvent::FileLoaded { language, .. } => {
             window.set_language(language.into());
         }
-        UiEvent::FileSaveStatus { filename, is_dirty } => {
-            let status = if is_dirty { "Modified" } else { "Saved" };
-            window.set_status_message(format!("{filename}: {status}").into());
-        }
         UiEvent::ExplorerRefresh => {
-            // Explorer refresh is handled directly by workspace watcher
+            let ws = workspace.lock().unwrap();
+            update_file_tree(window, &ws);
         }
         UiEvent::ChatResponseChunk { content } => {
             let current = window.get_chat_output().to_string();
@@ -500,15 +681,14 @@ fn handle_ui_event(window: &AppWindow, event: UiEvent) {
             let current = window.get_chat_output().to_string();
             window.set_chat_output(format!("{current}\nError: {message}\n\n").into());
         }
-        UiEvent::DiffAvailable { hunk_count } => {
-            window.set_status_message(format!("{hunk_count} diff hunks available").into());
-        }
-        UiEvent::DiffHunkResolved { remaining } => {
-            window.set_status_message(format!("{remaining} diff hunks remaining").into());
-        }
-        UiEvent::StatusUpdate { message } => {
-            window.set_status_message(message.into());
-        }
+        // Only shown in the status bar
+        UiEvent::FileSaveStatus { .. }
+        | UiEvent::FileSaveFailed { .. }
+        | UiEvent::DiffAvailable { .. }
+        | UiEvent::DiffHunkResolved { .. }
+        | UiEvent::StatusUpdate { .. }
+        | UiEvent::Progress { .. }
+        | UiEvent::ProgressComplete { .. } => {}
     }
 }
 
@@ -518,8 +698,8 @@ fn update_editor_ui(window: &AppWindow, editor: &EditorState) {
     let tabs: Vec<TabData> = editor.tabs.iter().map(|tab| {
         TabData {
             filename: tab.filename.clone().into(),
-            path: tab.path.to_string_lossy().to_string().into(),
-            dirty: tab.dirty,
+            path: tab.document.path.to_string_lossy().to_string().into(),
+            dirty: tab.document.is_dirty(),
         }
     }).collect();
     let tabs_model = std::rc::Rc::new(slint::VecModel::from(tabs));
@@ -530,12 +710,13 @@ fn update_editor_ui(window: &AppWindow, editor: &EditorState) {
 
     // Update editor lines for active tab
     if let Some(tab) = editor.active_tab() {
-        let lines: Vec<EditorLineData> = tab.content
+        let content = tab.document.buffer.doc.to_string();
+        let lines: Vec<EditorLineData> = content
             .lines()
             .enumerate()
             .map(|(i, line)| EditorLineData {
                 line_num: (i + 1) as i32,
-                content: line.to_string().into(),
+                content: expand_tabs(line, editor.tab_width).into(),
                 is_current: false,
             })
             .collect();
@@ -553,6 +734,23 @@ fn update_editor_ui(window: &AppWindow, editor: &EditorState) {
     }
 }
 
+/// Replace tabs in `line` with spaces up to the next multiple of `tab_width` columns.
+fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut col = 0usize;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = tab_width - col % tab_width;
+            out.extend(std::iter::repeat_n(' ', spaces));
+            col += spaces;
+        } else {
+            out.push(c);
+            col += 1;
+        }
+    }
+    out
+}
+
 /// Convert workspace file tree to Slint model and update UI.
 fn update_file_tree(window: &AppWindow, ws: &workspace::WorkspaceService) {
     let flat_items = ws.flat_tree();
@@ -606,3 +804,49 @@ fn get_file_icon(node: &workspace::TreeNode) -> &'static str {
         _ => "📄",
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_typed_tab_is_autosaved_and_marked_clean() {
+        let temp_dir = std::env::temp_dir().join("app_test_autosave_typed");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("notes.txt");
+        fs::write(&path, "hello").unwrap();
+
+        let (request_tx, request_rx) = tokio::sync::mpsc::channel(16);
+        let (saved_tx, mut saved_rx) = tokio::sync::mpsc::channel(16);
+        tokio::spawn(workspace::run_autosave(request_rx, saved_tx, Duration::from_millis(20)));
+        let mut editor = EditorState::new(4);
+        editor.autosave = Some(request_tx);
+        editor.open_file(path.clone()).unwrap();
+
+        assert!(!editor.type_text("\u{f700}"));
+        assert!(editor.type_text("> "));
+        assert!(editor.active_tab().unwrap().document.is_dirty());
+
+        let Some(editor_core::Event::DocumentSaved { document_id }) = saved_rx.recv().await else {
+            panic!("expected DocumentSaved");
+        };
+        assert_eq!(fs::read_to_string(&path).unwrap(), "> hello");
+        let event = editor.mark_saved(document_id);
+        assert!(matches!(event, Some(UiEvent::FileSaveStatus { is_dirty: false, .. })));
+        assert!(!editor.active_tab().unwrap().document.is_dirty());
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_chat_transcript_matches_panel_format() {
+        let mut conversation = editor_core::Conversation::default();
+        conversation.push_message(editor_core::ChatRole::System, "context");
+        conversation.push_message(editor_core::ChatRole::User, "Hi");
+        conversation.push_message(editor_core::ChatRole::Assistant, "Hello!");
+        assert_eq!(chat_transcript(&conversation), "You: Hi\n\nAssistant: Hello!\n\n");
+    }
+}
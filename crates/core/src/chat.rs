@@ -0,0 +1,423 @@
+use crate::code_blocks::proposals_from_message;
+use crate::{
+    ChatMessage, ChatRole, ChatState, Conversation, ConversationId, CoreError, DocumentId, Event,
+    EventSender, PatchProposal, PatchProposalId, Result,
+};
+use std::collections::HashSet;
+use std::fmt::Display;
+use tokio::sync::mpsc;
+
+impl ChatState {
+    pub fn conversation(&self, id: ConversationId) -> Option<&Conversation> {
+        self.conversations.iter().find(|c| c.id == id)
+    }
+
+    /// An id no conversation uses yet.
+    pub fn next_conversation_id(&self) -> ConversationId {
+        self.conversations.iter().map(|c| c.id).max().map_or(1, |id| id + 1)
+    }
+
+    /// The conversation with `id`, created (untitled) if it does not exist yet.
+    pub fn conversation_mut(&mut self, id: ConversationId) -> &mut Conversation {
+        let idx = match self.conversations.iter().position(|c| c.id == id) {
+            Some(idx) => idx,
+            None => {
+                let now = unix_now();
+                self.conversations.push(Conversation {
+                    id,
+                    title: String::new(),
+                    messages: Vec::new(),
+                    created_at: now,
+                    updated_at: now,
+                });
+                self.conversations.len() - 1
+            }
+        };
+        &mut self.conversations[idx]
+    }
+}
+
+/// Longest title `Conversation::derive_title_from` produces, in chars.
+pub const MAX_TITLE_CHARS: usize = 40;
+
+impl Conversation {
+    /// A single-line title from the start of `message`: whitespace runs collapse to one
+    /// space, and text longer than `MAX_TITLE_CHARS` is cut at a word boundary.
+    pub fn derive_title_from(message: &str) -> String {
+        let words: Vec<&str> = message.split_whitespace().collect();
+        let mut title = String::new();
+        for word in words {
+            let sep = usize::from(!title.is_empty());
+            if title.chars().count() + sep + word.chars().count() > MAX_TITLE_CHARS {
+                if title.is_empty() {
+                    title = word.chars().take(MAX_TITLE_CHARS).collect();
+                }
+                title.push('…');
+                break;
+            }
+            if sep == 1 {
+                title.push(' ');
+            }
+            title.push_str(word);
+        }
+        title
+    }
+
+    pub fn push_message(&mut self, role: ChatRole, content: impl Into<String>) {
+        self.messages.push(ChatMessage {
+            role,
+            content: content.into(),
+        });
+        self.updated_at = unix_now();
+    }
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// An assistant reply reserved by `ChatService::begin_reply` for a stream to fill.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PendingReply {
+    pub conversation_id: ConversationId,
+    /// Position of the reply in the conversation's messages
+    index: usize,
+}
+
+/// Owns the chat state and records exchanges with the model, emitting events as the
+/// conversation changes.
+#[derive(Debug)]
+pub struct ChatService {
+    state: ChatState,
+    events: EventSender,
+    next_proposal_id: PatchProposalId,
+    /// Conversations with a reply currently streaming
+    streaming: HashSet<ConversationId>,
+}
+
+impl ChatService {
+    pub fn new(events: EventSender) -> Self {
+        Self::with_state(ChatState::default(), events)
+    }
+
+    pub fn with_state(state: ChatState, events: EventSender) -> Self {
+        Self {
+            state,
+            events,
+            next_proposal_id: 1,
+            streaming: HashSet::new(),
+        }
+    }
+
+    pub fn state(&self) -> &ChatState {
+        &self.state
+    }
+
+    /// Messages of a conversation so far, e.g. to send as context with the next request.
+    pub fn history(&self, conversation_id: ConversationId) -> Vec<ChatMessage> {
+        self.state
+            .conversation(conversation_id)
+            .map(|c| c.messages.clone())
+            .unwrap_or_default()
+    }
+
+    /// Append `user_message` and an assistant reply built from `deltas`.
+    ///
+    /// Emits `ChatMessageAdded` for the user message, then `AiStreamStarted`, an
+    /// `AiStreamDelta` for every delta, `ChatMessageAdded` with the full reply and
+    /// `AiStreamEnded` once the stream ends. If the stream yields an error, a partial
+    /// reply is kept (an empty one is dropped), `Event::Error` and an aborted
+    /// `AiStreamEnded` are emitted and the error returned.
+    pub async fn stream_reply<E: Display>(
+        &mut self,
+        conversation_id: ConversationId,
+        user_message: String,
+        mut deltas: mpsc::Receiver<std::result::Result<String, E>>,
+    ) -> Result<()> {
+        let reply = self.begin_reply(Some(conversation_id), user_message).await?;
+        while let Some(item) = deltas.recv().await {
+            match item {
+                Ok(delta) => self.append_reply(reply, delta).await,
+                Err(e) => return Err(self.abort_reply(reply, e.to_string()).await),
+            }
+        }
+        self.finish_reply(reply).await;
+        Ok(())
+    }
+
+    /// Fill a reply reserved by `begin_reply` from `deltas`, as `stream_reply` does, but
+    /// lock `chat` only while recording each step, so the service stays usable while
+    /// waiting for the model.
+    pub async fn stream_reply_shared<E: Display>(
+        chat: &tokio::sync::Mutex<Self>,
+        reply: PendingReply,
+        mut deltas: mpsc::Receiver<std::result::Result<String, E>>,
+    ) -> Result<()> {
+        while let Some(item) = deltas.recv().await {
+            let mut chat = chat.lock().await;
+            match item {
+                Ok(delta) => chat.append_reply(reply, delta).await,
+                Err(e) => return Err(chat.abort_reply(reply, e.to_string()).await),
+            }
+        }
+        chat.lock().await.finish_reply(reply).await;
+        Ok(())
+    }
+
+    /// Record the user message and an empty assistant reply for a stream to fill.
+    ///
+    /// `None` continues the active conversation, starting a new one if there is none.
+    /// Fails if a reply is already streaming into the conversation, since deltas of
+    /// two replies would otherwise interleave.
+    pub async fn begin_reply(
+        &mut self,
+        conversation_id: Option<ConversationId>,
+        user_message: String,
+    ) -> Result<PendingReply> {
+        let conversation_id = conversation_id
+            .or(self.state.active_conversation)
+            .unwrap_or_else(|| self.state.next_conversation_id());
+        if !self.streaming.insert(conversation_id) {
+            return Err(CoreError::InvalidInput(format!(
+                "a reply is still streaming in conversation {conversation_id}"
+            )));
+        }
+        self.state.active_conversation = Some(conversation_id);
+        let conversation = self.state.conversation_mut(conversation_id);
+        if conversation.title.is_empty() && !conversation.messages.iter().any(|m| m.role == ChatRole::User) {
+            conversation.title = Conversation::derive_title_from(&user_message);
+        }
+        conversation.push_message(ChatRole::User, user_message.clone());
+        conversation.push_message(ChatRole::Assistant, String::new());
+        let reply = PendingReply { conversation_id, index: conversation.messages.len() - 1 };
+        self.emit(Event::ChatMessageAdded {
+            conversation_id,
+            role: ChatRole::User,
+            content: user_message,
+        })
+        .await;
+        self.emit(Event::AiStreamStarted { conversation_id }).await;
+        Ok(reply)
+    }
+
+    async fn append_reply(&mut self, reply: PendingReply, delta: String) {
+        if let Some(message) = self.reply_mut(reply) {
+            message.content.push_str(&delta);
+        }
+        let conversation_id = reply.conversation_id;
+        self.emit(Event::AiStreamDelta { conversation_id, delta }).await;
+    }
+
+    async fn finish_reply(&mut self, reply: PendingReply) {
+        let conversation_id = reply.conversation_id;
+        self.streaming.remove(&conversation_id);
+        let content = self.reply_mut(reply).map(|m| m.content.clone()).unwrap_or_default();
+        self.emit(Event::ChatMessageAdded {
+            conversation_id,
+            role: ChatRole::Assistant,
+            content,
+        })
+        .await;
+        self.emit(Event::AiStreamEnded { conversation_id, aborted: false, error: None }).await;
+    }
+
+    /// End a reply after an error, e.g. when the request or its stream fails, dropping
+    /// the reply if nothing arrived. Returns the error to report.
+    pub async fn abort_reply(&mut self, reply: PendingReply, message: String) -> CoreError {
+        let conversation_id = reply.conversation_id;
+        self.streaming.remove(&conversation_id);
+        if self.reply_mut(reply).is_some_and(|m| m.content.is_empty()) {
+            self.state.conversation_mut(conversation_id).messages.remove(reply.index);
+        }
+        self.emit(Event::Error { message: message.clone() }).await;
+        self.emit(Event::AiStreamEnded {
+            conversation_id,
+            aborted: true,
+            error: Some(message.clone()),
+        })
+        .await;
+        CoreError::Internal(message)
+    }
+
+    /// The assistant message reserved for `reply`.
+    fn reply_mut(&mut self, reply: PendingReply) -> Option<&mut ChatMessage> {
+        self.state
+            .conversation_mut(reply.conversation_id)
+            .messages
+            .get_mut(reply.index)
+            .filter(|m| m.role == ChatRole::Assistant)
+    }
+
+    /// Offer the code blocks of the conversation's latest assistant message as patches to
+    /// `document_id`, emitting `PatchProposed` for each.
+    pub async fn propose_patches(
+        &mut self,
+        conversation_id: ConversationId,
+        document_id: DocumentId,
+        document_text: &str,
+    ) -> Vec<PatchProposal> {
+        let Some(reply) = self
+            .state
+            .conversation(conversation_id)
+            .and_then(|c| c.messages.iter().rev().find(|m| m.role == ChatRole::Assistant))
+        else {
+            return Vec::new();
+        };
+        let proposals =
+            proposals_from_message(&reply.content, document_id, document_text, self.next_proposal_id);
+        self.next_proposal_id += proposals.len() as PatchProposalId;
+        for proposal in proposals.iter() {
+            self.emit(Event::PatchProposed {
+                proposal_id: proposal.id,
+                document_id,
+                patch: proposal.patch.clone(),
+            })
+            .await;
+        }
+        proposals
+    }
+
+    async fn emit(&self, event: Event) {
+        let _ = self.events.send(event).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stream_reply_builds_conversation() {
+        let (event_tx, mut event_rx) = mpsc::channel(16);
+        let mut chat = ChatService::new(event_tx);
+        let (delta_tx, delta_rx) = mpsc::channel::<std::result::Result<String, String>>(8);
+        for delta in ["Hel", "lo", "!"] {
+            delta_tx.send(Ok(delta.to_string())).await.unwrap();
+        }
+        drop(delta_tx);
+
+        chat.stream_reply(3, "Hi".to_string(), delta_rx).await.unwrap();
+
+        let conversation = chat.state().conversation(3).unwrap();
+        let messages: Vec<(ChatRole, &str)> = conversation
+            .messages
+            .iter()
+            .map(|m| (m.role, m.content.as_str()))
+            .collect();
+        assert_eq!(messages, vec![(ChatRole::User, "Hi"), (ChatRole::Assistant, "Hello!")]);
+        assert_eq!(chat.state().active_conversation, Some(3));
+
+        let mut deltas = String::new();
+        let mut added = Vec::new();
+        let mut stream = Vec::new();
+        while let Ok(event) = event_rx.try_recv() {
+            match event {
+                Event::AiStreamDelta { delta, .. } => deltas.push_str(&delta),
+                Event::ChatMessageAdded { role, content, .. } => added.push((role, content)),
+                Event::AiStreamStarted { conversation_id } => stream.push((conversation_id, None)),
+                Event::AiStreamEnded { conversation_id, aborted, error } => {
+                    assert!(deltas == "Hello!" && added.len() == 2);
+                    stream.push((conversation_id, Some((aborted, error))));
+                }
+                other => panic!("unexpected event {other:?}"),
+            }
+        }
+        assert_eq!(stream, vec![(3, None), (3, Some((false, None)))]);
+        assert_eq!(deltas, "Hello!");
+        assert_eq!(
+            added,
+            vec![(ChatRole::User, "Hi".to_string()), (ChatRole::Assistant, "Hello!".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_stream_drops_empty_reply() {
+        let (event_tx, _event_rx) = mpsc::channel(16);
+        let mut chat = ChatService::new(event_tx);
+        let (delta_tx, delta_rx) = mpsc::channel::<std::result::Result<String, String>>(2);
+        delta_tx.send(Err("offline".to_string())).await.unwrap();
+        assert!(chat.stream_reply(1, "Hi".to_string(), delta_rx).await.is_err());
+        let roles: Vec<ChatRole> = chat.history(1).iter().map(|m| m.role).collect();
+        assert_eq!(roles, vec![ChatRole::User]);
+
+        let (delta_tx, delta_rx) = mpsc::channel::<std::result::Result<String, String>>(2);
+        delta_tx.send(Ok("Par".to_string())).await.unwrap();
+        delta_tx.send(Err("offline".to_string())).await.unwrap();
+        assert!(chat.stream_reply(1, "Again".to_string(), delta_rx).await.is_err());
+        assert_eq!(chat.history(1).last().unwrap().content, "Par");
+        assert_eq!(chat.state().next_conversation_id(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_shared_stream_releases_lock_between_deltas() {
+        let (event_tx, _event_rx) = mpsc::channel(16);
+        let chat = std::sync::Arc::new(tokio::sync::Mutex::new(ChatService::new(event_tx)));
+        let reply = chat.lock().await.begin_reply(Some(4), "Hi".to_string()).await.unwrap();
+        let (delta_tx, delta_rx) = mpsc::channel::<std::result::Result<String, String>>(2);
+        let task = tokio::spawn({
+            let chat = std::sync::Arc::clone(&chat);
+            async move { ChatService::stream_reply_shared(&chat, reply, delta_rx).await }
+        });
+
+        delta_tx.send(Ok("Hel".to_string())).await.unwrap();
+        while chat.lock().await.history(4).last().map(|m| m.content.as_str()) != Some("Hel") {
+            tokio::task::yield_now().await;
+        }
+        delta_tx.send(Ok("lo".to_string())).await.unwrap();
+        drop(delta_tx);
+        task.await.unwrap().unwrap();
+        assert_eq!(chat.lock().await.history(4).last().unwrap().content, "Hello");
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_replies_stay_separate() {
+        let (event_tx, _event_rx) = mpsc::channel(32);
+        let chat = tokio::sync::Mutex::new(ChatService::new(event_tx));
+        let first = chat.lock().await.begin_reply(None, "One".to_string()).await.unwrap();
+        assert_eq!(first.conversation_id, 1);
+        assert!(chat.lock().await.begin_reply(None, "Again".to_string()).await.is_err());
+        let second = chat.lock().await.begin_reply(Some(2), "Two".to_string()).await.unwrap();
+
+        let (first_tx, first_rx) = mpsc::channel::<std::result::Result<String, String>>(4);
+        let (second_tx, second_rx) = mpsc::channel::<std::result::Result<String, String>>(4);
+        for (tx, delta) in [(&first_tx, "a"), (&second_tx, "b"), (&first_tx, "c"), (&second_tx, "d")] {
+            tx.send(Ok(delta.to_string())).await.unwrap();
+        }
+        drop((first_tx, second_tx));
+        let (first_done, second_done) = tokio::join!(
+            ChatService::stream_reply_shared(&chat, first, first_rx),
+            ChatService::stream_reply_shared(&chat, second, second_rx),
+        );
+        first_done.unwrap();
+        second_done.unwrap();
+
+        let chat = chat.lock().await;
+        let contents = |id| chat.history(id).into_iter().map(|m| m.content).collect::<Vec<_>>();
+        assert_eq!(contents(1), vec!["One", "ac"]);
+        assert_eq!(contents(2), vec!["Two", "bd"]);
+    }
+
+    #[tokio::test]
+    async fn test_title_from_first_message() {
+        let message = "  How do I make this\nparser handle nested   generics without recursion?";
+        let title = Conversation::derive_title_from(message);
+        assert_eq!(title, "How do I make this parser handle nested…");
+        assert!(!title.contains('\n'));
+
+        let (event_tx, _event_rx) = mpsc::channel(16);
+        let mut chat = ChatService::new(event_tx);
+        let (delta_tx, delta_rx) = mpsc::channel::<std::result::Result<String, String>>(1);
+        drop(delta_tx);
+        chat.stream_reply(1, message.to_string(), delta_rx).await.unwrap();
+        assert_eq!(chat.state().conversation(1).unwrap().title, title);
+
+        let (delta_tx, delta_rx) = mpsc::channel::<std::result::Result<String, String>>(1);
+        drop(delta_tx);
+        chat.state.conversation_mut(2).title = "Generics".to_string();
+        chat.stream_reply(2, message.to_string(), delta_rx).await.unwrap();
+        assert_eq!(chat.state().conversation(2).unwrap().title, "Generics");
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{DocumentId, PatchKind, PatchProposal, PatchProposalId};
+
+/// A fenced code block found in a chat message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// Language hint from the opening fence (e.g. `rust`), if any.
+    pub lang: Option<String>,
+    pub content: String,
+}
+
+/// Extract ``` and ~~~ fenced code blocks from `message`.
+///
+/// A block closes only on a line holding a fence of the same character at least as long
+/// as the opening one, so shorter fences (or inline backticks) inside a block are kept as
+/// content. An unterminated block runs to the end of the message.
+pub fn extract_code_blocks(message: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut open: Option<(char, usize, Option<String>, Vec<&str>)> = None;
+    for line in message.lines() {
+        let trimmed = line.trim_start();
+        match open.as_mut() {
+            None => {
+                if let Some((ch, len, info)) = parse_fence(trimmed) {
+                    let lang = info.split_whitespace().next().map(str::to_string);
+                    open = Some((ch, len, lang, Vec::new()));
+                }
+            }
+            Some((ch, len, _, lines)) => {
+                let closes = parse_fence(trimmed)
+                    .is_some_and(|(c, l, info)| c == *ch && l >= *len && info.is_empty());
+                if closes {
+                    if let Some((_, _, lang, lines)) = open.take() {
+                        blocks.push(CodeBlock { lang, content: join_lines(&lines) });
+                    }
+                } else {
+                    lines.push(line);
+                }
+            }
+        }
+    }
+    if let Some((_, _, lang, lines)) = open {
+        blocks.push(CodeBlock { lang, content: join_lines(&lines) });
+    }
+    blocks
+}
+
+/// Turn the code blocks of an assistant message into patch proposals for a document.
+///
+/// A block replaces the whole document if it starts with the document's first non-blank
+/// line or is at least half as long as the document; otherwise it is offered for
+/// insertion at the cursor. Proposals are numbered from `first_id`.
+pub fn proposals_from_message(
+    message: &str,
+    document_id: DocumentId,
+    document_text: &str,
+    first_id: PatchProposalId,
+) -> Vec<PatchProposal> {
+    let doc_lines = document_text.lines().count();
+    let doc_first = document_text.lines().map(str::trim).find(|l| !l.is_empty());
+    extract_code_blocks(message)
+        .into_iter()
+        .filter(|b| !b.content.trim().is_empty())
+        .enumerate()
+        .map(|(i, block)| {
+            let block_first = block.content.lines().map(str::trim).find(|l| !l.is_empty());
+            let whole_file = doc_lines > 0
+                && (block_first == doc_first || block.content.lines().count() * 2 >= doc_lines);
+            PatchProposal {
+                id: first_id + i as PatchProposalId,
+                document_id,
+                patch: block.content,
+                kind: if whole_file { PatchKind::ReplaceDocument } else { PatchKind::InsertAtCursor },
+            }
+        })
+        .collect()
+}
+
+/// A completed piece of a streamed assistant message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Prose(String),
+    CodeBlock { lang: Option<String>, content: String },
+}
+
+/// Incrementally splits streamed chat deltas into prose and fenced code blocks.
+///
+/// Input is processed a line at a time, so fence markers split across deltas are still
+/// recognized. Segments are returned as soon as they are complete.
+#[derive(Debug, Default)]
+pub struct StreamAssembler {
+    /// Text of the current, unfinished line.
+    pending: String,
+    prose: String,
+    code: Option<OpenFence>,
+}
+
+#[derive(Debug)]
+struct OpenFence {
+    ch: char,
+    len: usize,
+    lang: Option<String>,
+    content: String,
+}
+
+impl StreamAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a delta, returning the segments it completed.
+    pub fn push(&mut self, delta: &str) -> Vec<Segment> {
+        self.pending.push_str(delta);
+        let mut segments = Vec::new();
+        while let Some(newline) = self.pending.find('\n') {
+            let line: String = self.pending.drain(..=newline).collect();
+            self.push_line(&line, &mut segments);
+        }
+        segments
+    }
+
+    /// End the stream, returning any remaining prose and an unterminated code block.
+    pub fn finish(mut self) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        if !self.pending.is_empty() {
+            let line = std::mem::take(&mut self.pending);
+            self.push_line(&line, &mut segments);
+        }
+        if let Some(fence) = self.code.take() {
+            segments.push(Segment::CodeBlock { lang: fence.lang, content: fence.content });
+        }
+        if !self.prose.is_empty() {
+            segments.push(Segment::Prose(std::mem::take(&mut self.prose)));
+        }
+        segments
+    }
+
+    fn push_line(&mut self, line: &str, segments: &mut Vec<Segment>) {
+        let fence = parse_fence(line.trim());
+        match self.code.as_mut() {
+            None => match fence {
+                Some((ch, len, info)) => {
+                    if !self.prose.is_empty() {
+                        segments.push(Segment::Prose(std::mem::take(&mut self.prose)));
+                    }
+                    let lang = info.split_whitespace().next().map(str::to_string);
+                    self.code = Some(OpenFence { ch, len, lang, content: String::new() });
+                }
+                None => self.prose.push_str(line),
+            },
+            Some(open) => {
+                let closes = fence.is_some_and(|(c, l, info)| c == open.ch && l >= open.len && info.is_empty());
+                if closes {
+                    if let Some(fence) = self.code.take() {
+                        segments.push(Segment::CodeBlock { lang: fence.lang, content: fence.content });
+                    }
+                } else {
+                    open.content.push_str(line);
+                }
+            }
+        }
+    }
+}
+
+/// Parse a fence line into its character, length and info string.
+fn parse_fence(line: &str) -> Option<(char, usize, &str)> {
+    let ch = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = line.chars().take_while(|c| *c == ch).count();
+    if len < 3 {
+        return None;
+    }
+    let info = line[len..].trim();
+    (ch == '~' || !info.contains('`')).then_some((ch, len, info))
+}
+
+fn join_lines(lines: &[&str]) -> String {
+    let mut content = lines.join("\n");
+    if !lines.is_empty() {
+        content.push('\n');
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_code_blocks_with_languages() {
+        let message = "Try this:\n```rust\nfn main() {}\n```\nand a note with `inline` code\n\n\
+                       ````markdown\nUse\n```sh\ncargo run\n```\n````\nDone.";
+        let blocks = extract_code_blocks(message);
+        assert_eq!(
+            blocks,
+            vec![
+                CodeBlock { lang: Some("rust".to_string()), content: "fn main() {}\n".to_string() },
+                CodeBlock {
+                    lang: Some("markdown".to_string()),
+                    content: "Use\n```sh\ncargo run\n```\n".to_string(),
+                },
+            ]
+        );
+
+        let doc = "fn main() {}\n\nfn a() {}\nfn b() {}\nfn c() {}\nfn d() {}\nfn e() {}\nfn f() {}\nfn g() {}\n";
+        let proposals = proposals_from_message(message, 4, doc, 10);
+        assert_eq!(proposals.len(), 2);
+        assert_eq!((proposals[0].id, proposals[0].kind), (10, PatchKind::ReplaceDocument));
+        assert_eq!((proposals[1].id, proposals[1].kind), (11, PatchKind::InsertAtCursor));
+        assert_eq!(proposals[1].document_id, 4);
+    }
+
+    #[test]
+    fn test_stream_assembler_handles_split_fence() {
+        let mut assembler = StreamAssembler::new();
+        let mut segments = Vec::new();
+        for delta in ["Here:\n`", "``ru", "st\nlet x", " = 1;\n``", "`\nThat's it."] {
+            segments.extend(assembler.push(delta));
+        }
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Prose("Here:\n".to_string()),
+                Segment::CodeBlock { lang: Some("rust".to_string()), content: "let x = 1;\n".to_string() },
+            ]
+        );
+        assert_eq!(assembler.finish(), vec![Segment::Prose("That's it.".to_string())]);
+    }
+}
@@ -1,3 +1,11 @@
+pub mod chat;
+pub mod code_blocks;
+
+pub use chat::{ChatService, PendingReply};
+pub use code_blocks::{
+    extract_code_blocks, proposals_from_message, CodeBlock, Segment, StreamAssembler,
+};
+
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use thiserror::Error;
@@ -29,6 +37,8 @@ pub type Result<T> = std::result::Result<T, CoreError>;
 pub enum Command {
     OpenWorkspace { path: PathBuf },
     OpenFile { path: PathBuf },
+    /// Open a file with the caret at a zero-based line and column.
+    OpenFileAt { path: PathBuf, line: usize, col: usize },
     SaveFile { document_id: DocumentId },
     CloseFile { document_id: DocumentId },
     CreateFile { path: PathBuf },
@@ -43,11 +53,15 @@ pub enum Command {
 pub enum Event {
     WorkspaceOpened { root: PathBuf },
     WorkspaceTreeUpdated,
-    DocumentOpened { document_id: DocumentId, path: PathBuf, text: String },
+    DocumentOpened { document_id: DocumentId, path: PathBuf, text: String, cursor_char_idx: usize },
     DocumentSaved { document_id: DocumentId },
     DocumentClosed { document_id: DocumentId },
     ChatMessageAdded { conversation_id: ConversationId, role: ChatRole, content: String },
+    AiStreamStarted { conversation_id: ConversationId },
     AiStreamDelta { conversation_id: ConversationId, delta: String },
+    /// The reply stream finished; `aborted` is set when it stopped early, with the
+    /// error if there was one.
+    AiStreamEnded { conversation_id: ConversationId, aborted: bool, error: Option<String> },
     PatchProposed { proposal_id: PatchProposalId, document_id: DocumentId, patch: String },
     Error { message: String },
 }
@@ -77,7 +91,6 @@ pub struct AppState {
     pub chat: ChatState,
     pub diff: DiffState,
     pub theme: ThemeState,
-    pub settings: SettingsState,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -92,7 +105,7 @@ pub struct EditorState {
     pub open_documents: Vec<OpenDocument>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct OpenDocument {
     pub document_id: DocumentId,
     pub path: Option<PathBuf>,
@@ -100,17 +113,6 @@ pub struct OpenDocument {
     pub cursor_char_idx: usize,
 }
 
-impl Default for OpenDocument {
-    fn default() -> Self {
-        Self {
-            document_id: 0,
-            path: None,
-            is_dirty: false,
-            cursor_char_idx: 0,
-        }
-    }
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct ChatState {
     pub active_conversation: Option<ConversationId>,
@@ -122,6 +124,12 @@ pub struct Conversation {
     pub id: ConversationId,
     pub title: String,
     pub messages: Vec<ChatMessage>,
+    /// Seconds since the Unix epoch when the conversation was started.
+    #[serde(default)]
+    pub created_at: u64,
+    /// Seconds since the Unix epoch of the latest message.
+    #[serde(default)]
+    pub updated_at: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -141,14 +149,20 @@ pub struct PatchProposal {
     pub id: PatchProposalId,
     pub document_id: DocumentId,
     pub patch: String,
+    #[serde(default)]
+    pub kind: PatchKind,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct ThemeState {
-    pub theme_name: String,
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum PatchKind {
+    /// Replace the document's whole text with the patch.
+    ReplaceDocument,
+    /// Insert the patch at the cursor.
+    #[default]
+    InsertAtCursor,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct SettingsState {
-    pub model_id: String,
+pub struct ThemeState {
+    pub theme_name: String,
 }
@@ -1,3 +1,4 @@
+use crate::diagnostics::Diagnostic;
 use crate::document::{Document, DocumentSnapshot};
 use crate::history::{Edit, History, Transaction, TransactionKind};
 use crate::selection::{Selection, SelectionSet};
@@ -15,21 +16,61 @@ pub struct ReplaceRange {
     pub inserted: String,
 }
 
+/// Why a set of external edits could not be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApplyError {
+    /// The document changed since the edits were computed.
+    VersionMismatch { expected: u64, actual: u64 },
+    /// Two edits touch the same text.
+    Overlap { first: usize, second: usize },
+    /// An edit range is inverted or extends past the end of the document.
+    OutOfBounds { start_char: usize, end_char: usize },
+}
+
+impl std::fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApplyError::VersionMismatch { expected, actual } => {
+                write!(f, "document version is {actual}, edits were made against {expected}")
+            }
+            ApplyError::Overlap { first, second } => {
+                write!(f, "edits starting at {first} and {second} overlap")
+            }
+            ApplyError::OutOfBounds { start_char, end_char } => {
+                write!(f, "edit range {start_char}..{end_char} is out of bounds")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ApplyError {}
+
 #[derive(Debug, Clone)]
 pub struct Buffer {
     pub doc: Document,
     pub selections: SelectionSet,
     pub history: History,
     pub last_edit_impact: Option<EditImpact>,
+    saved: DocumentSnapshot,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl Buffer {
     pub fn new(text: &str) -> Self {
+        Self::from_document(Document::new(text))
+    }
+
+    /// Wrap an existing document, keeping its rope and version. Its content is the
+    /// saved baseline for `is_modified`.
+    pub fn from_document(doc: Document) -> Self {
+        let saved = doc.snapshot();
         Self {
-            doc: Document::new(text),
+            doc,
             selections: SelectionSet::default(),
             history: History::default(),
             last_edit_impact: None,
+            saved,
+            diagnostics: Vec::new(),
         }
     }
 
@@ -42,15 +83,74 @@ impl Buffer {
         self.history = History::default();
         self.selections.set_single_caret(0);
         self.last_edit_impact = None;
+        self.diagnostics.clear();
+    }
+
+    /// Replace the diagnostics. Ranges are ordered and clamped to the document; they
+    /// then follow edits until replaced again.
+    pub fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        let len = self.doc.len_chars();
+        self.diagnostics = diagnostics
+            .into_iter()
+            .map(|mut d| {
+                let (a, b) = d.range;
+                d.range = (a.min(b).min(len), a.max(b).min(len));
+                d
+            })
+            .collect();
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Record the current content as saved.
+    pub fn mark_saved(&mut self) {
+        self.saved = self.doc.snapshot();
+    }
+
+    /// Document version at the last `mark_saved`, or when the buffer was built.
+    pub fn saved_version(&self) -> u64 {
+        self.saved.version
+    }
+
+    /// Whether the content differs from the last save. Undoing back to the saved
+    /// content counts as unmodified. Until the first save the document the buffer was
+    /// built from counts as saved. Cheap while the version is still the saved one; the
+    /// text is only compared after edits.
+    pub fn is_modified(&self) -> bool {
+        !self.doc.matches_snapshot(&self.saved)
     }
 
     pub fn apply_text_to_selections(&mut self, inserted: &str) {
-        let selections = self.selections.all_including_primary();
+        self.selections.normalize();
+        let count = 1 + self.selections.secondary.len();
+        self.replace_selections(&vec![inserted; count]);
+    }
+
+    /// Replace each selection with its own text, in document order. Returns false
+    /// without editing if `texts` does not have one entry per selection.
+    pub fn apply_texts_to_selections(&mut self, texts: &[&str]) -> bool {
+        self.selections.normalize();
+        if texts.len() != 1 + self.selections.secondary.len() {
+            return false;
+        }
+        self.replace_selections(texts);
+        true
+    }
+
+    /// Expects normalized selections and one text per selection in document order.
+    fn replace_selections(&mut self, texts: &[&str]) {
+        let mut selections: Vec<(Selection, bool)> = Vec::with_capacity(texts.len());
+        selections.push((self.selections.primary, true));
+        selections.extend(self.selections.secondary.iter().map(|s| (*s, false)));
+        selections.sort_by_key(|(s, _)| s.range());
         let mut start_line = usize::MAX;
         let mut end_line = 0usize;
         let mut edits: Vec<Edit> = selections
             .iter()
-            .map(|s| {
+            .zip(texts)
+            .map(|((s, _), inserted)| {
                 let (start, end) = s.range();
                 start_line = start_line.min(self.doc.char_to_line(start));
                 end_line = end_line.max(self.doc.char_to_line(end));
@@ -64,52 +164,48 @@ impl Buffer {
         if edits.iter().all(|e| e.deleted.is_empty() && e.inserted.is_empty()) {
             return;
         }
-        edits.sort_by(|a, b| b.start_char.cmp(&a.start_char));
-        for e in edits.iter() {
-            let delete_end = e.start_char + e.deleted_len_chars();
-            self.doc.replace_range(e.start_char, delete_end, &e.inserted);
-        }
+        // Carets land after each inserted text, shifted by the edits before them.
         let mut new_set = SelectionSet::default();
-        let mut collapsed: Vec<Selection> = selections
-            .iter()
-            .map(|s| {
-                let start = s.range().0;
-                let caret = start + inserted.chars().count();
-                Selection {
-                    anchor: caret,
-                    head: caret,
-                }
-            })
-            .collect();
-        if let Some(p) = collapsed.first().copied() {
-            new_set.primary = p;
-            if collapsed.len() > 1 {
-                new_set.secondary = collapsed.drain(1..).collect();
+        let mut shift = 0isize;
+        for ((_, is_primary), e) in selections.iter().zip(&edits) {
+            let inserted_len = e.inserted.chars().count();
+            let caret = e.start_char.saturating_add_signed(shift) + inserted_len;
+            shift += inserted_len as isize - e.deleted_len_chars() as isize;
+            let sel = Selection { anchor: caret, head: caret };
+            if *is_primary {
+                new_set.primary = sel;
+            } else {
+                new_set.secondary.push(sel);
             }
         }
+        edits.sort_by_key(|e| std::cmp::Reverse(e.start_char));
+        for e in edits.iter() {
+            let delete_end = e.start_char + e.deleted_len_chars();
+            self.replace_doc_range(e.start_char, delete_end, &e.inserted);
+        }
         self.selections = new_set;
-        let kind = if inserted.is_empty() {
+        let kind = if texts.iter().all(|t| t.is_empty()) {
             TransactionKind::Delete
-        } else if selections.iter().all(|s| s.is_caret()) {
+        } else if selections.iter().all(|(s, _)| s.is_caret()) {
             TransactionKind::Insert
         } else {
             TransactionKind::Replace
         };
         let tx = Transaction { kind, edits };
         let allow_coalesce = kind == TransactionKind::Insert
-            && inserted.chars().count() == 1
+            && texts.iter().all(|t| t.chars().count() == 1)
             && self.selections.is_single_caret();
         self.history.push(tx, allow_coalesce);
-        if start_line == usize::MAX {
-            self.last_edit_impact = None;
-        } else {
-            let inserted_newlines = inserted.chars().filter(|c| *c == '\n').count();
-            let extra_lines = inserted_newlines + 1;
-            self.last_edit_impact = Some(EditImpact {
-                start_line,
-                end_line_inclusive: end_line.saturating_add(extra_lines),
-            });
-        }
+        let inserted_newlines = texts
+            .iter()
+            .map(|t| t.chars().filter(|c| *c == '\n').count())
+            .max()
+            .unwrap_or(0);
+        let extra_lines = inserted_newlines + 1;
+        self.last_edit_impact = Some(EditImpact {
+            start_line,
+            end_line_inclusive: end_line.saturating_add(extra_lines),
+        });
     }
 
     pub fn apply_replace_ranges(
@@ -135,10 +231,10 @@ impl Buffer {
                 }
             })
             .collect();
-        edits.sort_by(|a, b| b.start_char.cmp(&a.start_char));
+        edits.sort_by_key(|e| std::cmp::Reverse(e.start_char));
         for e in edits.iter() {
             let delete_end = e.start_char + e.deleted_len_chars();
-            self.doc.replace_range(e.start_char, delete_end, &e.inserted);
+            self.replace_doc_range(e.start_char, delete_end, &e.inserted);
         }
         self.selections = new_selections;
         self.history.push(Transaction { kind, edits }, false);
@@ -152,15 +248,140 @@ impl Buffer {
         }
     }
 
+    /// Apply edits computed against `base_version` as one transaction. The edits are
+    /// rejected without touching the buffer if the document has changed since, or if
+    /// any of them overlap or fall outside the document. Selections are shifted to
+    /// follow the edits.
+    pub fn apply_external_edits(
+        &mut self,
+        base_version: u64,
+        mut edits: Vec<ReplaceRange>,
+    ) -> Result<(), ApplyError> {
+        let actual = self.doc.version();
+        if actual != base_version {
+            return Err(ApplyError::VersionMismatch { expected: base_version, actual });
+        }
+        let len = self.doc.len_chars();
+        if let Some(bad) = edits.iter().find(|e| e.start_char > e.end_char || e.end_char > len) {
+            return Err(ApplyError::OutOfBounds { start_char: bad.start_char, end_char: bad.end_char });
+        }
+        edits.sort_by_key(|e| (e.start_char, e.end_char));
+        if let Some(pair) = edits.windows(2).find(|w| w[1].start_char < w[0].end_char) {
+            return Err(ApplyError::Overlap { first: pair[0].start_char, second: pair[1].start_char });
+        }
+        let selections = self.selections_mapped_through(&edits);
+        self.apply_replace_ranges(edits, TransactionKind::Replace, selections);
+        Ok(())
+    }
+
+    /// Apply `ranges` as one transaction, keeping the current selections: each one is
+    /// shifted by the edits before it, and ends inside a replaced range move to the end
+    /// of its new text. The ranges must not overlap.
+    pub fn apply_replace_ranges_preserving_selections(&mut self, mut ranges: Vec<ReplaceRange>) {
+        ranges.sort_by_key(|r| (r.start_char, r.end_char));
+        let mut selections = self.selections_mapped_through(&ranges);
+        selections.normalize();
+        self.apply_replace_ranges(ranges, TransactionKind::Replace, selections);
+    }
+
+    /// Insert `text` at `line`/`col` (clamped as by `Document::insert_at_line_col`) as
+    /// one transaction, shifting the selections after it.
+    pub fn insert_at_line_col(&mut self, line: usize, col: usize, text: &str) {
+        let char_idx = self.doc.clamped_line_col_to_char(line, col);
+        self.apply_replace_ranges_preserving_selections(vec![ReplaceRange {
+            start_char: char_idx,
+            end_char: char_idx,
+            inserted: text.to_string(),
+        }]);
+    }
+
+    /// The current selections moved to where their text ends up after `edits`, which
+    /// must be sorted and must not overlap. Positions inside a replaced range move to the end of its text.
+    pub(crate) fn selections_mapped_through(&self, edits: &[ReplaceRange]) -> SelectionSet {
+        let map = |pos: usize| -> usize {
+            let mut shifted = pos;
+            for e in edits.iter() {
+                let inserted = e.inserted.chars().count();
+                if pos >= e.end_char {
+                    shifted = shifted + inserted - (e.end_char - e.start_char);
+                } else if pos > e.start_char {
+                    shifted = shifted - (pos - e.start_char) + inserted;
+                }
+            }
+            shifted
+        };
+        let mut selections = self.selections.clone();
+        for s in std::iter::once(&mut selections.primary).chain(selections.secondary.iter_mut()) {
+            s.anchor = map(s.anchor);
+            s.head = map(s.head);
+        }
+        selections
+    }
+
+    /// Replace the whole document with `new_text` as a single undoable transaction.
+    /// Only the span between the common prefix and suffix is edited, so selections
+    /// outside it keep their place in the text; those inside it are clamped to it.
+    pub fn set_text(&mut self, new_text: &str) {
+        let old: Vec<char> = self.doc.to_string().chars().collect();
+        let new: Vec<char> = new_text.chars().collect();
+        let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+        if prefix == old.len() && prefix == new.len() {
+            return;
+        }
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let old_end = old.len() - suffix;
+        let new_end = new.len() - suffix;
+        let map = |c: usize| {
+            if c <= prefix {
+                c
+            } else if c >= old_end {
+                c - old_end + new_end
+            } else {
+                c.min(new_end)
+            }
+        };
+        let map_selection = |s: Selection| Selection { anchor: map(s.anchor), head: map(s.head) };
+        let mut selections = SelectionSet {
+            primary: map_selection(self.selections.primary),
+            secondary: self.selections.secondary.iter().map(|s| map_selection(*s)).collect(),
+        };
+        selections.normalize();
+        self.apply_replace_ranges(
+            vec![ReplaceRange {
+                start_char: prefix,
+                end_char: old_end,
+                inserted: new[prefix..new_end].iter().collect(),
+            }],
+            TransactionKind::Replace,
+            selections,
+        );
+        self.last_edit_impact = Some(EditImpact {
+            start_line: 0,
+            end_line_inclusive: self.doc.len_lines(),
+        });
+    }
+
+    fn replace_doc_range(&mut self, start: usize, end: usize, inserted: &str) {
+        self.doc.replace_range(start, end, inserted);
+        let inserted_len = inserted.chars().count();
+        for d in self.diagnostics.iter_mut() {
+            d.shift(start, end, inserted_len);
+        }
+    }
+
     pub fn undo(&mut self) -> bool {
         let Some(tx) = self.history.undo.pop() else {
             return false;
         };
-        let mut inverse = tx.clone();
-        inverse.edits.sort_by(|a, b| b.start_char.cmp(&a.start_char));
-        for e in inverse.edits.iter() {
+        // Edits were applied in list order, so revert them back to front.
+        for e in tx.edits.iter().rev() {
             let end = e.start_char + e.inserted_len_chars();
-            self.doc.replace_range(e.start_char, end, &e.deleted);
+            self.replace_doc_range(e.start_char, end, &e.deleted);
         }
         self.history.redo.push(tx);
         self.last_edit_impact = None;
@@ -171,14 +392,143 @@ impl Buffer {
         let Some(tx) = self.history.redo.pop() else {
             return false;
         };
-        let mut forward = tx.clone();
-        forward.edits.sort_by(|a, b| b.start_char.cmp(&a.start_char));
-        for e in forward.edits.iter() {
+        for e in tx.edits.iter() {
             let end = e.start_char + e.deleted_len_chars();
-            self.doc.replace_range(e.start_char, end, &e.inserted);
+            self.replace_doc_range(e.start_char, end, &e.inserted);
         }
         self.history.undo.push(tx);
         self.last_edit_impact = None;
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlapping_selections_edit_once() {
+        let mut buffer = Buffer::new("abcdefghij");
+        buffer.selections = SelectionSet {
+            primary: Selection { anchor: 2, head: 5 },
+            secondary: vec![
+                Selection { anchor: 4, head: 7 },
+                Selection { anchor: 3, head: 3 },
+                Selection { anchor: 7, head: 8 },
+            ],
+        };
+        buffer.selections.normalize();
+        assert_eq!(buffer.selections.primary, Selection { anchor: 2, head: 8 });
+        assert!(buffer.selections.secondary.is_empty());
+
+        buffer.apply_text_to_selections("X");
+        assert_eq!(buffer.doc.to_string(), "abXij");
+    }
+
+    #[test]
+    fn test_undo_multi_edit_transaction() {
+        let mut buffer = Buffer::new("ab\ncd");
+        buffer.selections = SelectionSet {
+            primary: Selection { anchor: 0, head: 0 },
+            secondary: vec![Selection { anchor: 3, head: 3 }],
+        };
+        buffer.apply_text_to_selections("--");
+        assert_eq!(buffer.doc.to_string(), "--ab\n--cd");
+        assert!(buffer.undo());
+        assert_eq!(buffer.doc.to_string(), "ab\ncd");
+        assert!(buffer.redo());
+        assert_eq!(buffer.doc.to_string(), "--ab\n--cd");
+    }
+
+    #[test]
+    fn test_external_edits_check_version_and_overlap() {
+        let mut buffer = Buffer::new("let a = 1;\nlet b = 2;\n");
+        buffer.selections.set_single_caret(15);
+        let version = buffer.doc.version();
+        let edit = |start_char, end_char, inserted: &str| ReplaceRange {
+            start_char,
+            end_char,
+            inserted: inserted.to_string(),
+        };
+
+        assert_eq!(
+            buffer.apply_external_edits(version + 1, vec![edit(4, 5, "x")]),
+            Err(ApplyError::VersionMismatch { expected: version + 1, actual: version })
+        );
+        assert_eq!(
+            buffer.apply_external_edits(version, vec![edit(4, 9, "x"), edit(8, 9, "y")]),
+            Err(ApplyError::Overlap { first: 4, second: 8 })
+        );
+        assert_eq!(buffer.doc.to_string(), "let a = 1;\nlet b = 2;\n");
+        assert!(buffer.history.undo.is_empty());
+
+        buffer
+            .apply_external_edits(version, vec![edit(15, 16, "beta"), edit(4, 5, "alpha")])
+            .unwrap();
+        assert_eq!(buffer.doc.to_string(), "let alpha = 1;\nlet beta = 2;\n");
+        assert_eq!(buffer.selections.primary.head, 19);
+        assert_eq!(buffer.history.undo.len(), 1);
+    }
+
+    #[test]
+    fn test_set_text_is_undoable() {
+        let original = "fn main() {\n    println!(\"hi\");\n}\n";
+        let mut buffer = Buffer::new(original);
+        buffer.selections.set_single_caret(20);
+        buffer.set_text("short");
+        assert_eq!(buffer.doc.to_string(), "short");
+        assert_eq!(buffer.selections.primary.head, 5);
+        assert_eq!(buffer.history.undo.len(), 1);
+        assert_eq!(buffer.history.undo[0].kind, TransactionKind::Replace);
+        assert!(buffer.undo());
+        assert_eq!(buffer.doc.to_string(), original);
+    }
+
+    #[test]
+    fn test_is_modified_tracks_saved_content() {
+        let mut buffer = Buffer::new("a");
+        assert!(!buffer.is_modified());
+        buffer.selections.set_single_caret(1);
+        buffer.apply_text_to_selections("b");
+        buffer.mark_saved();
+        assert!(!buffer.is_modified());
+        assert_eq!(buffer.saved_version(), buffer.doc.version());
+
+        buffer.apply_text_to_selections(" c");
+        assert!(buffer.is_modified());
+        buffer.undo();
+        assert_eq!(buffer.doc.to_string(), "ab");
+        assert!(!buffer.is_modified());
+        buffer.undo();
+        assert!(buffer.is_modified());
+    }
+
+    #[test]
+    fn test_from_edited_document_is_unmodified() {
+        let mut doc = Document::new("a");
+        doc.replace_range(1, 1, "b");
+        assert_ne!(doc.version(), 0);
+        let mut buffer = Buffer::from_document(doc);
+        assert!(!buffer.is_modified());
+        assert_eq!(buffer.saved_version(), buffer.doc.version());
+
+        buffer.selections.set_single_caret(2);
+        buffer.apply_text_to_selections("c");
+        assert!(buffer.is_modified());
+    }
+
+    #[test]
+    fn test_replace_ranges_preserving_selections_shifts_caret() {
+        let mut buffer = Buffer::new(&"x".repeat(60));
+        buffer.selections.set_single_caret(50);
+        buffer.apply_replace_ranges_preserving_selections(vec![
+            ReplaceRange { start_char: 55, end_char: 58, inserted: String::new() },
+            ReplaceRange { start_char: 10, end_char: 10, inserted: "abc".to_string() },
+        ]);
+        assert_eq!(buffer.doc.len_chars(), 60);
+        assert_eq!(buffer.doc.slice_to_string(10, 13), "abc");
+        assert_eq!(buffer.selections.primary, Selection { anchor: 53, head: 53 });
+        assert!(buffer.undo());
+        assert_eq!(buffer.doc.to_string(), "x".repeat(60));
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Where `Copy`, `Cut` and `Paste` read and write text, such as the system clipboard.
+///
+/// Engines cloned from one another share their clipboard, so implementations take
+/// `&self` and synchronize internally.
+pub trait Clipboard: std::fmt::Debug + Send + Sync {
+    fn get(&self) -> String;
+    fn set(&self, text: &str);
+}
+
+/// A clipboard kept in memory, used when no system clipboard is plugged in.
+#[derive(Debug, Default)]
+pub struct MemoryClipboard {
+    text: Mutex<String>,
+}
+
+impl Clipboard for MemoryClipboard {
+    fn get(&self) -> String {
+        self.text.lock().map(|t| t.clone()).unwrap_or_default()
+    }
+
+    fn set(&self, text: &str) {
+        if let Ok(mut t) = self.text.lock() {
+            *t = text.to_string();
+        }
+    }
+}
+
+pub const DEFAULT_CLIPBOARD_RING_CAPACITY: usize = 10;
+
+/// Recently copied texts, newest first, for cycling through with `KeyAction::PasteCycle`.
+#[derive(Debug, Clone)]
+pub struct ClipboardRing {
+    entries: VecDeque<String>,
+    capacity: usize,
+}
+
+impl Default for ClipboardRing {
+    fn default() -> Self {
+        Self::new(DEFAULT_CLIPBOARD_RING_CAPACITY)
+    }
+}
+
+impl ClipboardRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Record a copy. Empty texts are ignored and an existing equal entry moves to the front.
+    pub fn push(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.entries.retain(|e| e != text);
+        self.entries.push_front(text.to_string());
+        self.entries.truncate(self.capacity);
+    }
+
+    /// Entry `index` steps back in history; 0 is the most recent copy.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,44 @@
+/// How serious a diagnostic is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+    Hint,
+}
+
+/// A message attached to a char range, such as a compiler error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Char range `(start, end)`; may be empty.
+    pub range: (usize, usize),
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Follow an edit that replaced `start..end` with `inserted_len` chars. Ends inside
+    /// the replaced text move to the end of the inserted text.
+    pub(crate) fn shift(&mut self, start: usize, end: usize, inserted_len: usize) {
+        let map = |pos: usize| {
+            if pos >= end {
+                pos - (end - start) + inserted_len
+            } else if pos > start {
+                start + inserted_len
+            } else {
+                pos
+            }
+        };
+        self.range = (map(self.range.0), map(self.range.1));
+    }
+}
+
+/// The part of a diagnostic on one visual row, in char columns relative to its text.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DiagnosticSpan {
+    pub start_col: usize,
+    pub end_col: usize,
+    pub severity: Severity,
+    /// Index into `Buffer::diagnostics`.
+    pub index: usize,
+}
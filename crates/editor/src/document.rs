@@ -1,4 +1,5 @@
 use ropey::Rope;
+use crate::search::{SearchIter, SearchMatch};
 use crate::selection::LineCol;
 
 #[derive(Debug, Clone)]
@@ -13,6 +14,15 @@ pub struct DocumentSnapshot {
     pub(crate) version: u64,
 }
 
+impl std::fmt::Display for Document {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for chunk in self.rope.chunks() {
+            f.write_str(chunk)?;
+        }
+        Ok(())
+    }
+}
+
 impl Document {
     pub fn new(text: &str) -> Self {
         Self {
@@ -21,10 +31,16 @@ impl Document {
         }
     }
 
+    /// Number of lines, counting the empty line after a final line break. Never zero:
+    /// an empty document has exactly one empty line.
     pub fn len_lines(&self) -> usize {
         self.rope.len_lines()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.rope.len_chars() == 0
+    }
+
     pub fn version(&self) -> u64 {
         self.version
     }
@@ -33,10 +49,6 @@ impl Document {
         self.rope.len_chars()
     }
 
-    pub fn to_string(&self) -> String {
-        self.rope.to_string()
-    }
-
     pub fn line_text(&self, line_idx: usize) -> String {
         if line_idx >= self.rope.len_lines() {
             return String::new();
@@ -57,6 +69,28 @@ impl Document {
         self.rope.line_to_char(next_line)
     }
 
+    /// Chars on the line, excluding its terminator: `\r\n` or any single char ropey
+    /// breaks lines on (`\n`, `\r`, form feed, NEL, ...).
+    pub fn line_len_chars(&self, line_idx: usize) -> usize {
+        if line_idx >= self.rope.len_lines() {
+            return 0;
+        }
+        let line = self.rope.line(line_idx);
+        let mut len = line.len_chars();
+        if len > 0 && is_line_break(line.char(len - 1)) {
+            len -= 1;
+            if len > 0 && line.char(len) == '\n' && line.char(len - 1) == '\r' {
+                len -= 1;
+            }
+        }
+        len
+    }
+
+    /// Char index just before the line's terminator (the line end for the last line).
+    pub fn line_end_char_no_newline(&self, line_idx: usize) -> usize {
+        self.line_start_char(line_idx) + self.line_len_chars(line_idx)
+    }
+
     pub fn snapshot(&self) -> DocumentSnapshot {
         DocumentSnapshot {
             rope: self.rope.clone(),
@@ -64,9 +98,17 @@ impl Document {
         }
     }
 
+    /// Replace the content with the snapshot's. The version moves forward rather than
+    /// back to the snapshot's, so a version never names two different texts.
     pub fn restore(&mut self, snapshot: DocumentSnapshot) {
         self.rope = snapshot.rope;
-        self.version = snapshot.version;
+        self.version = self.version.max(snapshot.version).wrapping_add(1);
+    }
+
+    /// True if the document's content equals the snapshot's. Equal versions imply equal
+    /// content; otherwise the text is compared, so edits that were undone still match.
+    pub fn matches_snapshot(&self, snapshot: &DocumentSnapshot) -> bool {
+        self.version == snapshot.version || self.rope == snapshot.rope
     }
 
     pub fn slice_to_string(&self, start_char: usize, end_char: usize) -> String {
@@ -78,15 +120,43 @@ impl Document {
         self.rope.slice(start..end).to_string()
     }
 
+    /// Text of lines `start_line..end_line`, including their terminators, as one rope
+    /// slice. Both bounds are clamped to the document.
+    pub fn lines_to_string(&self, start_line: usize, end_line: usize) -> String {
+        let start = self.line_start_char(start_line);
+        let end = self.line_start_char(end_line);
+        self.slice_to_string(start, end)
+    }
+
     pub fn line_to_char(&self, line_idx: usize) -> usize {
         self.rope.line_to_char(line_idx)
     }
 
+    pub fn char_to_byte(&self, char_idx: usize) -> usize {
+        self.rope.char_to_byte(char_idx.min(self.rope.len_chars()))
+    }
+
+    pub fn byte_to_char(&self, byte_idx: usize) -> usize {
+        self.rope.byte_to_char(byte_idx.min(self.rope.len_bytes()))
+    }
+
+    pub fn line_to_byte(&self, line_idx: usize) -> usize {
+        self.rope.line_to_byte(line_idx.min(self.rope.len_lines()))
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.rope.len_bytes()
+    }
+
+    /// Line containing `char_idx`. `len_chars()` is valid (the end of the document);
+    /// larger indices are a caller bug, asserted in debug builds and clamped otherwise.
     pub fn char_to_line(&self, char_idx: usize) -> usize {
-        self.rope.char_to_line(char_idx)
+        self.rope.char_to_line(self.clamp_char(char_idx))
     }
 
+    /// Line and column of `char_idx`, with the same clamping contract as `char_to_line`.
     pub fn char_to_line_col(&self, char_idx: usize) -> LineCol {
+        let char_idx = self.clamp_char(char_idx);
         let line = self.rope.char_to_line(char_idx);
         let line_start = self.rope.line_to_char(line);
         LineCol {
@@ -95,17 +165,56 @@ impl Document {
         }
     }
 
+    /// Line and column of `char_idx`, or `None` if it lies past the end of the document.
+    pub fn try_char_to_line_col(&self, char_idx: usize) -> Option<LineCol> {
+        (char_idx <= self.rope.len_chars()).then(|| self.char_to_line_col(char_idx))
+    }
+
+    fn clamp_char(&self, char_idx: usize) -> usize {
+        let len = self.rope.len_chars();
+        debug_assert!(char_idx <= len, "char index {char_idx} past end of document ({len})");
+        char_idx.min(len)
+    }
+
+    /// Char index of `line`/`col`. Out-of-range lines clamp to the end of the document
+    /// and columns clamp to the end of their line.
     pub fn line_col_to_char(&self, line: usize, col: usize) -> usize {
+        let line = line.min(self.rope.len_lines());
         let line_start = self.rope.line_to_char(line);
         let line_end = self.rope.line_to_char((line + 1).min(self.rope.len_lines()));
         (line_start + col).min(line_end)
     }
 
+    /// Lazily find non-overlapping matches of `needle` without materializing the text.
+    pub fn search_iter<'a>(
+        &'a self,
+        needle: &str,
+        case_sensitive: bool,
+    ) -> impl Iterator<Item = SearchMatch> + 'a {
+        SearchIter::new(self.rope.chars(), needle, case_sensitive)
+    }
+
     pub fn insert(&mut self, char_idx: usize, text: &str) {
         self.rope.insert(char_idx, text);
         self.version = self.version.wrapping_add(1);
     }
 
+    /// Insert `text` at `line`/`col` and return the char index it went to. The line
+    /// clamps to the last line and the column to the line's length, before its
+    /// terminator.
+    pub fn insert_at_line_col(&mut self, line: usize, col: usize, text: &str) -> usize {
+        let char_idx = self.clamped_line_col_to_char(line, col);
+        self.insert(char_idx, text);
+        char_idx
+    }
+
+    /// Char index of `line`/`col`, clamping the line to the last line and the column
+    /// to the line's length without its terminator.
+    pub fn clamped_line_col_to_char(&self, line: usize, col: usize) -> usize {
+        let line = line.min(self.rope.len_lines().saturating_sub(1));
+        self.line_start_char(line) + col.min(self.line_len_chars(line))
+    }
+
     pub fn delete_range(&mut self, start_char: usize, end_char: usize) {
         if start_char >= end_char {
             return;
@@ -126,3 +235,114 @@ impl Document {
         self.version = self.version.wrapping_add(1);
     }
 }
+
+fn is_line_break(c: char) -> bool {
+    matches!(c, '\n' | '\r' | '\u{000B}' | '\u{000C}' | '\u{0085}' | '\u{2028}' | '\u{2029}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_char_round_trip() {
+        let doc = Document::new("café\nnaïve → ok\n");
+        for char_idx in 0..=doc.len_chars() {
+            assert_eq!(doc.byte_to_char(doc.char_to_byte(char_idx)), char_idx);
+        }
+        assert_eq!(doc.char_to_byte(4), 5);
+        assert_eq!(doc.line_to_byte(1), 6);
+        assert_eq!(doc.byte_to_char(doc.line_to_byte(2)), doc.line_to_char(2));
+        assert_eq!(doc.char_to_byte(usize::MAX), doc.len_bytes());
+    }
+
+    #[test]
+    fn test_try_char_to_line_col_bounds() {
+        let doc = Document::new("ab\ncd");
+        assert_eq!(doc.try_char_to_line_col(5), Some(LineCol { line: 1, col: 2 }));
+        assert_eq!(doc.try_char_to_line_col(3), Some(LineCol { line: 1, col: 0 }));
+        assert_eq!(doc.try_char_to_line_col(6), None);
+        assert_eq!(doc.line_col_to_char(9, 9), 5);
+    }
+
+    #[test]
+    fn test_restore_does_not_reuse_versions() {
+        let mut doc = Document::new("a");
+        let original = doc.snapshot();
+        doc.replace_range(1, 1, "b");
+        let saved = doc.snapshot();
+        assert!(doc.matches_snapshot(&saved));
+
+        doc.restore(original.clone());
+        assert!(doc.matches_snapshot(&original));
+        assert_ne!(doc.version(), original.version);
+        doc.replace_range(1, 1, "c");
+        assert_ne!(doc.version(), saved.version);
+        assert!(!doc.matches_snapshot(&saved));
+    }
+
+    #[test]
+    fn test_lines_to_string_slices_line_range() {
+        let doc = Document::new("zero\none\ntwo\nthree\nfour\nfive");
+        assert_eq!(doc.lines_to_string(2, 5), "two\nthree\nfour\n");
+        assert_eq!(doc.lines_to_string(4, 99), "four\nfive");
+        assert_eq!(doc.lines_to_string(3, 1), "");
+    }
+
+    #[test]
+    fn test_insert_at_line_col() {
+        let mut doc = Document::new("zero\none\ntwo words\nthree");
+        let version = doc.version();
+        assert_eq!(doc.insert_at_line_col(2, 3, "-"), 12);
+        assert_eq!(doc.to_string(), "zero\none\ntwo- words\nthree");
+        assert_eq!(doc.version(), version + 1);
+        assert_eq!(doc.insert_at_line_col(1, 40, "!"), 8);
+        assert_eq!(doc.insert_at_line_col(9, 0, ">"), 21);
+        assert_eq!(doc.to_string(), "zero\none!\ntwo- words\n>three");
+    }
+
+    #[test]
+    fn test_empty_document_has_one_empty_line() {
+        let doc = Document::new("");
+        assert!(doc.is_empty());
+        assert_eq!(doc.len_lines(), 1);
+        assert_eq!(doc.len_chars(), 0);
+        assert_eq!(doc.line_text(0), "");
+        assert_eq!(doc.line_len_chars(0), 0);
+        assert_eq!((doc.line_start_char(0), doc.line_end_char(0)), (0, 0));
+        assert_eq!(doc.char_to_line_col(0), LineCol { line: 0, col: 0 });
+        assert_eq!(doc.lines_to_string(0, 1), "");
+        assert!(!Document::new("\n").is_empty());
+    }
+
+    #[test]
+    fn test_line_len_excludes_terminator() {
+        let doc = Document::new("ab\r\ncd\n\nef");
+        assert_eq!(doc.line_len_chars(0), 2);
+        assert_eq!(doc.line_end_char_no_newline(1), 6);
+        assert_eq!(doc.line_len_chars(2), 0);
+        assert_eq!(doc.line_end_char_no_newline(3), 10);
+        assert_eq!(doc.line_len_chars(4), 0);
+
+        let doc = Document::new("ab\rcd\u{2028}e");
+        assert_eq!(doc.line_end_char_no_newline(0), 2);
+        assert_eq!(doc.line_end_char_no_newline(1), 5);
+    }
+
+    #[test]
+    fn test_search_iter_across_chunk_boundary() {
+        let filler = "x".repeat(20_000);
+        let boundary = Document::new(&filler).rope.chunks().next().unwrap().len();
+        let mut text = filler.clone();
+        text.replace_range(boundary - 2..boundary + 2, "NeEd");
+        let doc = Document::new(&text);
+        assert_eq!(doc.rope.chunks().next().unwrap().len(), boundary);
+
+        let matches: Vec<SearchMatch> = doc.search_iter("need", false).collect();
+        assert_eq!(
+            matches,
+            vec![SearchMatch { start_char: boundary - 2, end_char: boundary + 2 }]
+        );
+        assert_eq!(doc.search_iter("need", true).count(), 0);
+    }
+}
@@ -0,0 +1,28 @@
+/// Editing behaviour that is independent of layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditConfig {
+    /// Text inserted for one level of indentation.
+    pub indent_unit: String,
+    /// Pressing Enter between an open and close bracket moves the close bracket to
+    /// its own line and indents the empty line between them.
+    pub expand_brackets_on_newline: bool,
+    /// Pressing Enter copies the current line's leading whitespace to the new line.
+    pub auto_indent: bool,
+    /// Strip trailing spaces and tabs from each pasted line.
+    pub trim_trailing_on_paste: bool,
+    /// Dedent a pasted multi-line block to its common indentation, then indent every
+    /// line after the first to match the caret's line.
+    pub reindent_on_paste: bool,
+}
+
+impl Default for EditConfig {
+    fn default() -> Self {
+        Self {
+            indent_unit: "    ".to_string(),
+            expand_brackets_on_newline: true,
+            auto_indent: false,
+            trim_trailing_on_paste: false,
+            reindent_on_paste: false,
+        }
+    }
+}
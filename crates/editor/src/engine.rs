@@ -1,14 +1,29 @@
 use std::collections::HashMap;
-use crate::buffer::{Buffer, ReplaceRange};
+use std::sync::Arc;
+use std::time::Instant;
+use crate::buffer::{Buffer, EditImpact, ReplaceRange};
+use crate::clipboard::{Clipboard, ClipboardRing, MemoryClipboard};
+use crate::diagnostics::DiagnosticSpan;
+use crate::edit_config::EditConfig;
 use crate::history::TransactionKind;
 use crate::keymap::{KeyAction, Keymap, Movement};
 use crate::layout::{
-    EditorViewModel, FontMetrics, LayoutConfig, SelectionSpan, VisualLine, Viewport, split_by_cols,
+    EditorViewModel, FontMetrics, LayoutConfig, SelectionSpan, VisualLine, Viewport, WrapIndent,
+    intersect_selection, leading_visual_cols, merge_selection_spans, overflow_char_col,
+    split_by_cols,
 };
-use crate::search::{SearchDirection, SearchMatch, SearchQuery, byte_to_char_idx, char_to_byte_idx};
-use crate::selection::{Selection, SelectionSet};
+use crate::minimap::{bucket_lines, MinimapRow};
+use crate::search::{
+    ReplaceOptions, ReplaceScope, SearchDirection, SearchMatch, SearchQuery, byte_to_char_idx,
+    char_to_byte_idx, match_case,
+};
+use crate::selection::{LineCol, Selection, SelectionSet};
+use crate::snippet::{Snippet, SnippetSession};
 use crate::text_shaping::{ShapedLine, TextShaper};
-use syntax::{LanguageRegistry, SyntaxHighlighter};
+use syntax::{CharHighlightSpan, LanguageRegistry, SyntaxHighlighter, TokenType};
+
+/// Lines beyond the viewport searched for the indentation of blank lines at its edges.
+const INDENT_GUIDE_SCAN_MARGIN: usize = 200;
 
 #[derive(Debug, Clone)]
 struct CachedLine {
@@ -16,49 +31,133 @@ struct CachedLine {
     shaped: Option<ShapedLine>,
 }
 
+/// The text most recently inserted by `KeyAction::PasteCycle`, so a repeat can swap it
+/// for an older clipboard entry.
+#[derive(Debug, Copy, Clone)]
+struct PasteCycle {
+    start: usize,
+    end: usize,
+    ring_index: usize,
+    doc_version: u64,
+}
+
+/// Ghost text offered at a caret, such as a streamed inline completion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InlineSuggestion {
+    /// Char index the text would be inserted at.
+    pub char_idx: usize,
+    pub text: String,
+    doc_version: u64,
+}
+
+/// Columns vertical movement aims for, kept while the carets only move vertically.
+#[derive(Debug, Clone)]
+struct GoalColumns {
+    heads: Vec<usize>,
+    cols: Vec<usize>,
+    doc_version: u64,
+}
+
 #[derive(Debug, Clone)]
 pub struct EditorEngine {
     pub buffer: Buffer,
     pub metrics: FontMetrics,
     pub layout: LayoutConfig,
+    pub edit: EditConfig,
     pub viewport: Viewport,
     pub keymap: Keymap,
     line_cache: HashMap<usize, CachedLine>,
+    highlight_cache: HashMap<usize, Vec<CharHighlightSpan>>,
+    highlight_passes: usize,
+    /// When the highlights last went stale, while `LayoutConfig::highlight_debounce`
+    /// holds back recomputing them.
+    highlight_dirty_since: Option<Instant>,
     cached_doc_version: u64,
     cached_line_count: usize,
     shaper: TextShaper,
     highlighter: Option<SyntaxHighlighter>,
     language_registry: LanguageRegistry,
     current_filename: Option<String>,
+    language_name: Option<&'static str>,
+    overwrite: bool,
+    snippet: Option<SnippetSession>,
+    clipboard: Arc<dyn Clipboard>,
+    clipboard_ring: ClipboardRing,
+    paste_cycle: Option<PasteCycle>,
+    /// Text produced by the last copy and the per-selection pieces it was joined from.
+    copied_pieces: Option<(String, Vec<String>)>,
+    goal_columns: Option<GoalColumns>,
+    /// Fixed end of a mouse selection, set by a plain click.
+    anchor: Option<usize>,
+    suggestion: Option<InlineSuggestion>,
+    /// Last `visual_line_count` result and the inputs it was computed from.
+    visual_line_count: Option<(WrapKey, usize)>,
+}
+
+/// What the number of visual rows depends on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct WrapKey {
+    doc_version: u64,
+    width_cols: usize,
+    soft_wrap: bool,
+    wrap_indent: WrapIndent,
 }
 
 impl EditorEngine {
     pub fn new(text: &str) -> Self {
+        Self::from_buffer(Buffer::new(text))
+    }
+
+    /// Build an engine around an existing buffer, keeping its text, version, history,
+    /// selections and saved baseline. Call `set_filename` to enable highlighting.
+    pub fn from_buffer(buffer: Buffer) -> Self {
         let shaper = TextShaper::new(14.0);
         let metrics_from_shaper = shaper.metrics();
         let metrics = FontMetrics {
             char_width_px: metrics_from_shaper.avg_char_width,
             line_height_px: metrics_from_shaper.line_height,
         };
+        let cached_doc_version = buffer.doc.version();
+        let cached_line_count = buffer.doc.len_lines();
         Self {
-            buffer: Buffer::new(text),
+            buffer,
             metrics,
             layout: LayoutConfig::default(),
+            edit: EditConfig::default(),
             viewport: Viewport { first_line: 0, max_lines: 64, width_cols: 120 },
             keymap: Keymap::with_defaults(),
             line_cache: HashMap::new(),
-            cached_doc_version: 0,
-            cached_line_count: 0,
+            highlight_cache: HashMap::new(),
+            highlight_passes: 0,
+            highlight_dirty_since: None,
+            cached_doc_version,
+            cached_line_count,
             shaper,
             highlighter: None,
             language_registry: LanguageRegistry::new(),
             current_filename: None,
+            language_name: None,
+            overwrite: false,
+            snippet: None,
+            clipboard: Arc::new(MemoryClipboard::default()),
+            clipboard_ring: ClipboardRing::default(),
+            paste_cycle: None,
+            copied_pieces: None,
+            goal_columns: None,
+            anchor: None,
+            suggestion: None,
+            visual_line_count: None,
         }
     }
 
     pub fn set_filename(&mut self, filename: &str) {
         self.current_filename = Some(filename.to_string());
-        if let Some(lang_config) = self.language_registry.detect_language(filename) {
+        self.highlight_cache.clear();
+        self.highlight_dirty_since = None;
+        let first_line = self.buffer.doc.line_text(0);
+        let detected = self.language_registry.detect_language_from(filename, Some(&first_line));
+        self.language_name = detected.map(|l| l.name);
+        if let Some(lang_config) = detected.and_then(|l| l.config) {
             let mut highlighter = SyntaxHighlighter::new();
             if highlighter.set_language(lang_config).is_ok() {
                 let _ = highlighter.parse(&self.buffer.doc.to_string());
@@ -69,58 +168,663 @@ impl EditorEngine {
         }
     }
 
-    pub fn apply_key_action(&mut self, action: KeyAction, clipboard_text: &mut String) {
+    /// Derive the viewport from the pixel size of the text area and its vertical scroll offset.
+    pub fn set_viewport_pixels(&mut self, height_px: f32, width_px: f32, scroll_y_px: f32) {
+        let line_height = self.metrics.line_height_px.max(1.0);
+        let char_width = self.metrics.char_width_px.max(1.0);
+        let last_line = self.buffer.doc.len_lines().saturating_sub(1);
+        self.viewport = Viewport {
+            first_line: ((scroll_y_px.max(0.0) / line_height) as usize).min(last_line),
+            max_lines: ((height_px.max(0.0) / line_height) as usize).max(1),
+            width_cols: (width_px.max(0.0) / char_width) as usize,
+        };
+    }
+
+    pub fn apply_key_action(&mut self, action: KeyAction) {
+        if self.suggestion.is_some() {
+            if action == KeyAction::Indent && self.accept_suggestion() {
+                return;
+            }
+            self.suggestion = None;
+        }
+        if self.snippet.is_some() {
+            match action {
+                KeyAction::Indent => {
+                    self.next_tab_stop();
+                    return;
+                }
+                KeyAction::Outdent => {
+                    self.prev_tab_stop();
+                    return;
+                }
+                KeyAction::Undo | KeyAction::Redo | KeyAction::CollapseSelections => self.snippet = None,
+                KeyAction::Move { .. } | KeyAction::Copy | KeyAction::ToggleOverwrite => {}
+                _ => {
+                    self.edit_tracking_snippet(|engine| engine.run_key_action(action));
+                    return;
+                }
+            }
+        }
+        self.run_key_action(action);
+        if matches!(action, KeyAction::Move { .. }) {
+            let head = self.buffer.selections.primary.head;
+            if self.snippet.as_ref().is_some_and(|s| !s.contains(head)) {
+                self.snippet = None;
+            }
+        }
+    }
+
+    fn run_key_action(&mut self, action: KeyAction) {
         match action {
-            KeyAction::Newline => self.buffer.apply_text_to_selections("\n"),
+            KeyAction::Newline => self.newline(),
             KeyAction::Backspace => self.backspace(),
             KeyAction::Delete => self.delete_forward(),
-            KeyAction::DeleteWordBackward => self.delete_word_backward(),
-            KeyAction::DeleteWordForward => self.delete_word_forward(),
+            KeyAction::DeleteWordBackward => self.delete_word_backward(find_word_left),
+            KeyAction::DeleteWordForward => self.delete_word_forward(find_word_right),
+            KeyAction::DeleteSubWordBackward => self.delete_word_backward(find_subword_left),
+            KeyAction::DeleteSubWordForward => self.delete_word_forward(find_subword_right),
             KeyAction::DeleteLine => self.delete_line(),
             KeyAction::Undo => { self.buffer.undo(); }
             KeyAction::Redo => { self.buffer.redo(); }
-            KeyAction::Copy => { *clipboard_text = self.copy(); }
-            KeyAction::Cut => { *clipboard_text = self.cut(); }
+            KeyAction::Copy => {
+                let text = self.copy();
+                self.clipboard.set(&text);
+                self.clipboard_ring.push(&text);
+            }
+            KeyAction::Cut => {
+                let text = self.cut();
+                self.clipboard.set(&text);
+                self.clipboard_ring.push(&text);
+            }
+            KeyAction::PasteCycle => self.paste_cycle(),
             KeyAction::Paste => {
-                let t = clipboard_text.clone();
-                self.buffer.apply_text_to_selections(&t);
+                let text = self.clipboard.get();
+                self.paste_text(&text);
             }
             KeyAction::Indent => self.indent(),
             KeyAction::Outdent => self.outdent(),
             KeyAction::DuplicateLine => self.duplicate_line(),
             KeyAction::ToggleComment => self.toggle_comment(),
-            KeyAction::Move { movement, extend } => self.move_cursors(movement, extend),
+            KeyAction::ToggleOverwrite => self.toggle_overwrite(),
+            KeyAction::TransposeChars => self.transpose_chars(),
+            KeyAction::TransposeWords => self.transpose_words(),
+            KeyAction::CollapseSelections => self.collapse_to_last(),
+            KeyAction::Move { movement, extend } => {
+                self.move_cursors(movement, extend);
+                self.scroll_by_page(movement);
+                if self.layout.follow_cursor {
+                    self.ensure_cursor_visible();
+                }
+            }
+        }
+    }
+
+    /// Insert `text` at `char_idx` without touching the selections beyond shifting
+    /// those after the insertion point.
+    pub fn insert_at(&mut self, char_idx: usize, text: &str) {
+        let char_idx = char_idx.min(self.buffer.doc.len_chars());
+        self.buffer.apply_replace_ranges_preserving_selections(vec![ReplaceRange {
+            start_char: char_idx,
+            end_char: char_idx,
+            inserted: text.to_string(),
+        }]);
+    }
+
+    /// Delete `start..end` without touching the selections beyond shifting those after
+    /// it; selection ends inside the range move to its start.
+    pub fn delete_at(&mut self, start: usize, end: usize) {
+        let len = self.buffer.doc.len_chars();
+        let (start, end) = (start.min(end).min(len), start.max(end).min(len));
+        if start < end {
+            self.buffer.apply_replace_ranges_preserving_selections(vec![ReplaceRange {
+                start_char: start,
+                end_char: end,
+                inserted: String::new(),
+            }]);
+        }
+    }
+
+    /// Start an empty suggestion at the primary caret, replacing any pending one.
+    /// Does nothing with multiple carets or a selection.
+    pub fn begin_suggestion(&mut self) {
+        let primary = self.buffer.selections.primary;
+        self.suggestion = (self.buffer.selections.is_single_caret() && primary.is_caret()).then(|| {
+            InlineSuggestion {
+                char_idx: primary.head,
+                text: String::new(),
+                doc_version: self.buffer.doc.version(),
+            }
+        });
+    }
+
+    /// Append streamed text to the pending suggestion.
+    pub fn push_suggestion_delta(&mut self, delta: &str) {
+        if let Some(suggestion) = self.suggestion.as_mut() {
+            suggestion.text.push_str(delta);
+        }
+    }
+
+    /// The pending suggestion, unless the text or the caret moved since it began.
+    pub fn suggestion(&self) -> Option<&InlineSuggestion> {
+        self.suggestion.as_ref().filter(|s| {
+            s.doc_version == self.buffer.doc.version()
+                && self.buffer.selections.is_single_caret()
+                && self.buffer.selections.primary == Selection { anchor: s.char_idx, head: s.char_idx }
+        })
+    }
+
+    /// Insert the pending suggestion at its caret as one transaction. Returns false if
+    /// there is no valid, non-empty suggestion.
+    pub fn accept_suggestion(&mut self) -> bool {
+        let Some(suggestion) = self.suggestion().filter(|s| !s.text.is_empty()).cloned() else {
+            return false;
+        };
+        self.suggestion = None;
+        let caret = suggestion.char_idx + suggestion.text.chars().count();
+        let mut selections = SelectionSet::default();
+        selections.set_single_caret(caret);
+        self.buffer.apply_replace_ranges(
+            vec![ReplaceRange {
+                start_char: suggestion.char_idx,
+                end_char: suggestion.char_idx,
+                inserted: suggestion.text,
+            }],
+            TransactionKind::Insert,
+            selections,
+        );
+        if self.layout.follow_cursor {
+            self.ensure_cursor_visible();
+        }
+        true
+    }
+
+    pub fn dismiss_suggestion(&mut self) {
+        self.suggestion = None;
+    }
+
+    /// Downsample the document into at most `rows` minimap rows. Short documents get one
+    /// row per line.
+    pub fn minimap(&self, rows: usize) -> Vec<MinimapRow> {
+        let line_count = self.buffer.doc.len_lines();
+        let rows = rows.min(line_count);
+        (0..rows)
+            .map(|row| {
+                let (first_line, end_line) = bucket_lines(row, rows, line_count);
+                let mut visible = 0usize;
+                let mut indent = 0usize;
+                let mut non_blank = 0usize;
+                let mut tokens: Vec<(TokenType, usize)> = Vec::new();
+                for line_idx in first_line..end_line {
+                    let text = self.buffer.doc.line_text(line_idx);
+                    let count = text.chars().filter(|c| !c.is_whitespace()).count();
+                    if count > 0 {
+                        visible += count;
+                        non_blank += 1;
+                        indent += text.chars().take_while(|c| *c == ' ' || *c == '\t').count();
+                    }
+                    for h in self.highlight_cache.get(&line_idx).into_iter().flatten() {
+                        let cols = h.end_col - h.start_col;
+                        match tokens.iter_mut().find(|(t, _)| *t == h.token_type) {
+                            Some((_, total)) => *total += cols,
+                            None => tokens.push((h.token_type, cols)),
+                        }
+                    }
+                }
+                let lines = (end_line - first_line).max(1);
+                MinimapRow {
+                    first_line,
+                    end_line,
+                    density: visible as f32 / lines as f32,
+                    indent: if non_blank == 0 { 0.0 } else { indent as f32 / non_blank as f32 },
+                    dominant_token: tokens.into_iter().max_by_key(|(_, cols)| *cols).map(|(t, _)| t),
+                }
+            })
+            .collect()
+    }
+
+    /// First line of minimap `row` out of `rows`, for scrolling to a clicked row.
+    pub fn minimap_line_at(&self, row: usize, rows: usize) -> usize {
+        let line_count = self.buffer.doc.len_lines();
+        let rows = rows.min(line_count);
+        bucket_lines(row.min(rows.saturating_sub(1)), rows, line_count).0
+    }
+
+    /// Zero-based line and column of the primary caret.
+    pub fn cursor_line_col(&self) -> LineCol {
+        self.buffer.doc.char_to_line_col(self.buffer.selections.primary.head)
+    }
+
+    /// One-based `(line, column)` of the primary caret, as shown in a status bar.
+    pub fn cursor_display_position(&self) -> (usize, usize) {
+        let LineCol { line, col } = self.cursor_line_col();
+        (line + 1, col + 1)
+    }
+
+    /// Position of every caret, in document order.
+    pub fn cursor_positions(&self) -> Vec<LineCol> {
+        self.buffer
+            .selections
+            .ordered()
+            .iter()
+            .map(|s| self.buffer.doc.char_to_line_col(s.head))
+            .collect()
+    }
+
+    /// Drop the secondary selections, keeping the primary one as it is.
+    pub fn collapse_to_primary(&mut self) {
+        self.buffer.selections.secondary.clear();
+        self.ensure_cursor_visible();
+    }
+
+    /// Keep only the selection that comes last in document order and make it the
+    /// primary.
+    pub fn collapse_to_last(&mut self) {
+        if let Some(last) = self.buffer.selections.ordered().pop() {
+            self.buffer.selections.primary = last;
+        }
+        self.collapse_to_primary();
+    }
+
+    /// Move each selection's text to the next selection in document order (the previous
+    /// one when `forward` is false), wrapping around, as one transaction. Selections end
+    /// up covering the text moved into them. Does nothing with fewer than two selections.
+    pub fn rotate_selections(&mut self, forward: bool) {
+        let ordered = self.buffer.selections.ordered();
+        let count = ordered.len();
+        if count < 2 {
+            return;
+        }
+        let primary = self.buffer.selections.primary;
+        let texts: Vec<String> = ordered
+            .iter()
+            .map(|s| {
+                let (start, end) = s.range();
+                self.buffer.doc.slice_to_string(start, end)
+            })
+            .collect();
+        let mut shift = 0isize;
+        let mut ranges = Vec::with_capacity(count);
+        let mut moved = Vec::with_capacity(count);
+        for (i, s) in ordered.iter().enumerate() {
+            let (start, end) = s.range();
+            let source = if forward { (i + count - 1) % count } else { (i + 1) % count };
+            let inserted = texts[source].clone();
+            let inserted_len = inserted.chars().count();
+            let new_start = start.saturating_add_signed(shift);
+            moved.push((Selection { anchor: new_start, head: new_start + inserted_len }, *s == primary));
+            shift += inserted_len as isize - (end - start) as isize;
+            ranges.push(ReplaceRange { start_char: start, end_char: end, inserted });
+        }
+        self.buffer.apply_replace_ranges(ranges, TransactionKind::Replace, selection_set_from(moved));
+    }
+
+    /// Wrap each non-empty selection in `open` and `close` as one transaction, selecting
+    /// the result delimiters included. A caret wraps the word it touches, or gets an
+    /// empty pair with the caret between when it touches none.
+    pub fn surround(&mut self, open: &str, close: &str) {
+        let ordered = self.buffer.selections.ordered();
+        let primary = self.buffer.selections.primary;
+        let open_len = open.chars().count();
+        let close_len = close.chars().count();
+        let mut shift = 0isize;
+        let mut ranges = Vec::with_capacity(ordered.len());
+        let mut wrapped = Vec::with_capacity(ordered.len());
+        for s in ordered.iter() {
+            let (start, end) = if s.is_caret() {
+                self.word_range_at(s.head).unwrap_or((s.head, s.head))
+            } else {
+                s.range()
+            };
+            if ranges.last().is_some_and(|r: &ReplaceRange| start < r.end_char) {
+                continue;
+            }
+            let text = self.buffer.doc.slice_to_string(start, end);
+            let new_start = start.saturating_add_signed(shift);
+            let selection = if start == end {
+                let caret = new_start + open_len;
+                Selection { anchor: caret, head: caret }
+            } else {
+                Selection { anchor: new_start, head: new_start + open_len + (end - start) + close_len }
+            };
+            wrapped.push((selection, *s == primary));
+            shift += (open_len + close_len) as isize;
+            ranges.push(ReplaceRange { start_char: start, end_char: end, inserted: format!("{open}{text}{close}") });
+        }
+        self.buffer.apply_replace_ranges(ranges, TransactionKind::Other, selection_set_from(wrapped));
+    }
+
+    /// Place a single caret at the start of `line` (clamped) and scroll it into view.
+    pub fn goto_line(&mut self, line: usize) {
+        let line = line.min(self.buffer.doc.len_lines().saturating_sub(1));
+        let caret = self.buffer.doc.line_start_char(line);
+        self.buffer.selections.set_single_caret(caret);
+        self.ensure_cursor_visible();
+    }
+
+    /// Replace the text after it changed on disk, keeping carets at the same place in
+    /// the surrounding text where possible. The reload is undoable.
+    pub fn reload(&mut self, new_text: &str) {
+        self.snippet = None;
+        self.buffer.set_text(new_text);
+        if self.layout.follow_cursor {
+            self.ensure_cursor_visible();
+        }
+    }
+
+    /// Place a single caret at `char_idx` and remember it as the anchor for
+    /// `extend_to`, as a plain click does.
+    pub fn set_anchor(&mut self, char_idx: usize) {
+        let char_idx = char_idx.min(self.buffer.doc.len_chars());
+        self.buffer.selections.set_single_caret(char_idx);
+        self.anchor = Some(char_idx);
+    }
+
+    pub fn clear_anchor(&mut self) {
+        self.anchor = None;
+    }
+
+    /// Select from the stored anchor (or the primary selection's anchor if none is
+    /// stored) to `char_idx`, as shift-click and drag-select do.
+    pub fn extend_to(&mut self, char_idx: usize) {
+        let len = self.buffer.doc.len_chars();
+        let anchor = self.anchor.unwrap_or(self.buffer.selections.primary.anchor).min(len);
+        self.buffer.selections.primary = Selection { anchor, head: char_idx.min(len) };
+        self.buffer.selections.secondary.clear();
+    }
+
+    /// Select the word around `char_idx`, as a double click does. On whitespace the
+    /// run of whitespace is selected, on punctuation the run of punctuation. The
+    /// selection start becomes the anchor for a following drag.
+    pub fn select_word_at(&mut self, char_idx: usize) {
+        let chars: Vec<char> = self.buffer.doc.to_string().chars().collect();
+        let mut idx = char_idx.min(chars.len());
+        if idx == chars.len() || (chars[idx] == '\n' && idx > 0 && chars[idx - 1] != '\n') {
+            idx = idx.saturating_sub(1);
+        }
+        let Some(&c) = chars.get(idx) else {
+            return;
+        };
+        let class = char_class(c);
+        let mut start = idx;
+        while start > 0 && char_class(chars[start - 1]) == class && chars[start - 1] != '\n' {
+            start -= 1;
+        }
+        let mut end = idx + 1;
+        while end < chars.len() && char_class(chars[end]) == class && chars[end] != '\n' {
+            end += 1;
+        }
+        self.buffer.selections.primary = Selection { anchor: start, head: end };
+        self.buffer.selections.secondary.clear();
+        self.anchor = Some(start);
+    }
+
+    /// Select the line containing `char_idx`, as a triple click does, optionally
+    /// including its trailing newline.
+    pub fn select_line_at(&mut self, char_idx: usize, include_newline: bool) {
+        let line = self.buffer.doc.char_to_line(char_idx);
+        let start = self.buffer.doc.line_start_char(line);
+        let end = if include_newline {
+            self.buffer.doc.line_end_char(line)
+        } else {
+            self.buffer.doc.line_end_char_no_newline(line)
+        };
+        self.buffer.selections.primary = Selection { anchor: start, head: end };
+        self.buffer.selections.secondary.clear();
+        self.anchor = Some(start);
+    }
+
+    /// Char range of the identifier at or just before `char_idx`.
+    pub fn word_range_at(&self, char_idx: usize) -> Option<(usize, usize)> {
+        let text = self.buffer.doc.to_string();
+        let chars: Vec<char> = text.chars().collect();
+        let mut idx = char_idx.min(chars.len());
+        if !chars.get(idx).is_some_and(|c| is_word_char(*c)) {
+            idx = idx.checked_sub(1).filter(|i| is_word_char(chars[*i]))?;
+        }
+        Some((find_word_left(&text, idx + 1), find_word_right(&text, idx)))
+    }
+
+    /// Text of the definition enclosing `char_idx` when a language is configured,
+    /// otherwise the word at `char_idx`.
+    pub fn enclosing_node_text(&mut self, char_idx: usize) -> Option<String> {
+        let text = self.buffer.doc.to_string();
+        if let Some(highlighter) = self.highlighter.as_mut() {
+            let byte = char_to_byte_idx(&text, char_idx);
+            if let Some(range) = highlighter.enclosing_definition(&text, byte) {
+                return Some(text[range].to_string());
+            }
+        }
+        let (start, end) = self.word_range_at(char_idx)?;
+        Some(self.buffer.doc.slice_to_string(start, end))
+    }
+
+    /// Adjust `viewport.first_line` so the primary caret's visual row lies inside the
+    /// viewport, keeping `layout.scroll_off` rows of context above and below it.
+    pub fn ensure_cursor_visible(&mut self) {
+        let max_rows = self.viewport.max_lines.max(1);
+        let margin = self.layout.scroll_off.min((max_rows - 1) / 2);
+        let lc = self.buffer.doc.char_to_line_col(self.buffer.selections.primary.head);
+        let caret_rows = self.visual_rows_for_line(lc.line);
+        let caret_row = self.visual_row_for_col(lc.line, lc.col).min(caret_rows - 1);
+
+        let mut line = lc.line;
+        let mut rows_above = caret_row;
+        while line > 0 && rows_above < margin {
+            line -= 1;
+            rows_above += self.visual_rows_for_line(line);
+        }
+        if line < self.viewport.first_line {
+            self.viewport.first_line = line;
+            return;
+        }
+
+        let last_line = self.buffer.doc.len_lines().saturating_sub(1);
+        let mut rows_below = caret_rows - caret_row - 1;
+        let mut next = lc.line + 1;
+        while rows_below < margin && next <= last_line {
+            rows_below += self.visual_rows_for_line(next);
+            next += 1;
+        }
+        let needed_below = caret_row + 1 + rows_below.min(margin);
+        let mut first = self.viewport.first_line.max(lc.line.saturating_sub(max_rows));
+        let mut rows_before: usize = (first..lc.line).map(|l| self.visual_rows_for_line(l)).sum();
+        while first < lc.line && rows_before + needed_below > max_rows {
+            rows_before -= self.visual_rows_for_line(first);
+            first += 1;
+        }
+        self.viewport.first_line = first;
+    }
+
+    /// Guide columns for each line in `first..last_exclusive`: one per indent level left
+    /// of its indentation. Blank lines take the smaller indentation of the nearest
+    /// non-blank lines around them, so guides continue through them; those are looked
+    /// for at most `INDENT_GUIDE_SCAN_MARGIN` lines past the range.
+    fn indent_guides(&self, first: usize, last_exclusive: usize) -> Vec<Vec<usize>> {
+        let tab_width = self.layout.tab_width;
+        let unit = self
+            .edit
+            .indent_unit
+            .chars()
+            .fold(0, |cols, c| if c == '\t' { cols + tab_width.max(1) } else { cols + 1 });
+        if unit == 0 {
+            return vec![Vec::new(); last_exclusive - first];
+        }
+        let doc = &self.buffer.doc;
+        let start = first.saturating_sub(INDENT_GUIDE_SCAN_MARGIN);
+        let end = (last_exclusive + INDENT_GUIDE_SCAN_MARGIN).min(doc.len_lines());
+        let indents: Vec<Option<usize>> =
+            (start..end).map(|line| leading_visual_cols(&doc.line_text(line), tab_width)).collect();
+        // Indentation of the nearest non-blank line at or below each line.
+        let mut below = vec![None; indents.len()];
+        let mut next = None;
+        for (i, indent) in indents.iter().enumerate().rev() {
+            next = indent.or(next);
+            below[i] = next;
+        }
+        let mut above = None;
+        let mut guides = Vec::with_capacity(last_exclusive - first);
+        for (i, indent) in indents.iter().enumerate() {
+            above = indent.or(above);
+            if start + i < first || start + i >= last_exclusive {
+                continue;
+            }
+            let indent = indent.unwrap_or_else(|| above.unwrap_or(0).min(below[i].unwrap_or(0)));
+            guides.push((0..indent.div_ceil(unit)).map(|level| level * unit).collect());
+        }
+        guides
+    }
+
+    /// Split a logical line into its visual rows, returning the rows and the hanging
+    /// indent (in columns) applied to continuation rows.
+    fn wrap_line(&self, text: &str) -> (Vec<String>, usize) {
+        let width = self.viewport.width_cols;
+        if !self.layout.soft_wrap || width == 0 {
+            return (vec![text.to_string()], 0);
+        }
+        let indent = match self.layout.wrap_indent {
+            WrapIndent::Fixed(cols) => cols,
+            WrapIndent::Inherit => text.chars().take_while(|c| *c == ' ' || *c == '\t').count(),
+        };
+        let indent = indent.min(width - 1);
+        (split_by_cols(text, width, indent), indent)
+    }
+
+    /// Visual rows the whole document takes, counting every soft-wrapped row. Equals
+    /// `len_lines()` when soft wrap is off. Cached until the text, the viewport width or
+    /// the wrap settings change.
+    pub fn visual_line_count(&mut self) -> usize {
+        let key = WrapKey {
+            doc_version: self.buffer.doc.version(),
+            width_cols: self.viewport.width_cols,
+            soft_wrap: self.layout.soft_wrap,
+            wrap_indent: self.layout.wrap_indent,
+        };
+        if let Some((cached_key, rows)) = self.visual_line_count {
+            if cached_key == key {
+                return rows;
+            }
+        }
+        let line_count = self.buffer.doc.len_lines();
+        let rows = if self.layout.soft_wrap {
+            (0..line_count).map(|line| self.visual_rows_for_line(line)).sum()
+        } else {
+            line_count
+        };
+        self.visual_line_count = Some((key, rows));
+        rows
+    }
+
+    fn visual_rows_for_line(&self, line_idx: usize) -> usize {
+        self.wrap_line(&self.buffer.doc.line_text(line_idx)).0.len()
+    }
+
+    fn visual_row_for_col(&self, line_idx: usize, col: usize) -> usize {
+        let (segments, _) = self.wrap_line(&self.buffer.doc.line_text(line_idx));
+        let mut seg_start = 0usize;
+        for (row, segment) in segments.iter().enumerate() {
+            seg_start += segment.chars().count();
+            if col < seg_start {
+                return row;
+            }
         }
+        segments.len() - 1
     }
 
     pub fn insert_text(&mut self, text: &str) {
+        if self.snippet.is_some() {
+            self.edit_tracking_snippet(|engine| engine.insert_text_at_selections(text));
+        } else {
+            self.insert_text_at_selections(text);
+        }
+    }
+
+    /// Insert pasted text verbatim, like a terminal's bracketed paste: no auto-indent,
+    /// bracket expansion or overwrite. Clipboard lines are spread over multiple carets
+    /// as with `KeyAction::Paste`. Only the `EditConfig` paste options change the text.
+    pub fn paste(&mut self, text: &str) {
+        if self.snippet.is_some() {
+            self.edit_tracking_snippet(|engine| engine.paste_text(text));
+        } else {
+            self.paste_text(text);
+        }
+    }
+
+    fn insert_text_at_selections(&mut self, text: &str) {
+        if self.overwrite && self.buffer.selections.is_single_caret() && !text.contains('\n') {
+            let caret = self.buffer.selections.primary.head;
+            let line = self.buffer.doc.char_to_line(caret);
+            let line_len = self.buffer.doc.line_len_chars(line);
+            let col = caret - self.buffer.doc.line_start_char(line);
+            let replaced = text.chars().count().min(line_len.saturating_sub(col));
+            if replaced > 0 {
+                self.buffer.selections.primary = Selection { anchor: caret, head: caret + replaced };
+            }
+        }
         self.buffer.apply_text_to_selections(text);
     }
 
+    pub fn is_overwrite(&self) -> bool {
+        self.overwrite
+    }
+
+    pub fn toggle_overwrite(&mut self) {
+        self.overwrite = !self.overwrite;
+    }
+
     pub fn view_model(&mut self) -> EditorViewModel {
+        let mut out = EditorViewModel::default();
+        self.view_model_into(&mut out);
+        out
+    }
+
+    /// Fill `out` with the visible rows, reusing its vectors and strings so a model
+    /// kept across frames doesn't allocate once scrolling settles.
+    pub fn view_model_into(&mut self, out: &mut EditorViewModel) {
         let doc_version = self.buffer.doc.version();
         let line_count = self.buffer.doc.len_lines();
         if doc_version != self.cached_doc_version {
+            let debounce = !self.layout.highlight_debounce.is_zero() && !self.highlight_cache.is_empty();
+            let stale_highlights = debounce.then(|| std::mem::take(&mut self.highlight_cache));
             if line_count != self.cached_line_count {
                 self.line_cache.clear();
+                self.highlight_cache.clear();
             } else if let Some(impact) = self.buffer.last_edit_impact {
                 let start = impact.start_line.min(line_count);
                 let end = impact.end_line_inclusive.min(line_count.saturating_sub(1));
                 for line in start..=end {
                     self.line_cache.remove(&line);
+                    self.highlight_cache.remove(&line);
                 }
             } else {
                 self.line_cache.clear();
+                self.highlight_cache.clear();
+            }
+            if let Some(stale) = stale_highlights {
+                let edit_line = self.buffer.last_edit_impact.map_or(0, |impact| impact.start_line);
+                let delta = line_count as isize - self.cached_line_count as isize;
+                self.highlight_cache = shift_line_keys(stale, edit_line, delta);
+                self.highlight_dirty_since = Some(Instant::now());
             }
             self.cached_doc_version = doc_version;
             self.cached_line_count = line_count;
         }
+        if self
+            .highlight_dirty_since
+            .is_some_and(|since| since.elapsed() >= self.layout.highlight_debounce)
+        {
+            self.highlight_cache.clear();
+            self.highlight_dirty_since = None;
+        }
         let first = self.viewport.first_line.min(line_count);
         let last_exclusive = (first + self.viewport.max_lines).min(line_count);
         let gutter_width_cols = line_count.to_string().len().max(3) + 1;
         let selections = self.buffer.selections.all_including_primary();
         let active_line = self.buffer.doc.char_to_line(self.buffer.selections.primary.head);
-        let mut lines = Vec::with_capacity(last_exclusive.saturating_sub(first));
+        let mut row = 0usize;
+        if !self.highlight_pending() {
+            self.refresh_highlights(first, last_exclusive);
+        }
+        let guides = self.indent_guides(first, last_exclusive);
         let mut y_px = 0.0f32;
         for line_idx in first..last_exclusive {
             let (text, shaped) = if let Some(cached) = self.line_cache.get(&line_idx) {
@@ -131,71 +835,236 @@ impl EditorEngine {
                 self.line_cache.insert(line_idx, CachedLine { text: t.clone(), shaped: Some(s.clone()) });
                 (t, Some(s))
             };
-            let segments = if self.layout.soft_wrap && self.viewport.width_cols > 0 {
-                split_by_cols(&text, self.viewport.width_cols)
-            } else {
-                vec![text.clone()]
-            };
+            let line_highlights = self.highlight_cache.get(&line_idx).cloned().unwrap_or_default();
+            let (segments, wrap_indent) = self.wrap_line(&text);
+            let overflow = self
+                .layout
+                .max_line_length
+                .and_then(|max| overflow_char_col(&text, max, self.layout.tab_width));
+            let indent_guides = &guides[line_idx - first];
+            // Measured without the line terminator, so the `\r` of a CRLF line doesn't
+            // hide the whitespace before it.
+            let content_len = self.buffer.doc.line_len_chars(line_idx);
+            let trailing_start = text
+                .chars()
+                .take(content_len)
+                .enumerate()
+                .filter(|(_, c)| *c != ' ' && *c != '\t')
+                .last()
+                .map_or(0, |(i, _)| i + 1);
+            let show_trailing = trailing_start < content_len
+                && !(line_idx == active_line && self.layout.whitespace.hide_trailing_on_current_line);
+            let line_start = self.buffer.doc.line_start_char(line_idx);
+            let line_end = self.buffer.doc.line_end_char(line_idx);
+            let mut wrap_col_offset = 0usize;
             for (segment_idx, segment) in segments.iter().enumerate() {
-                let wrap_col_offset = segment_idx * self.viewport.width_cols;
-                let mut selection_spans = Vec::new();
-                let mut cursors = Vec::new();
+                if row == out.lines.len() {
+                    out.lines.push(VisualLine::default());
+                }
+                let visual = &mut out.lines[row];
+                row += 1;
+                visual.selections.clear();
+                visual.cursors.clear();
+                let seg_len = segment.chars().count();
+                let seg_end = wrap_col_offset + seg_len;
                 for s in selections.iter() {
                     let (start, end) = s.range();
-                    let line_start = self.buffer.doc.line_start_char(line_idx);
-                    let line_end = self.buffer.doc.line_end_char(line_idx);
-                    let sel_start = start.max(line_start).min(line_end);
-                    let sel_end = end.max(line_start).min(line_end);
-                    if sel_start < sel_end {
-                        let start_col = sel_start.saturating_sub(line_start);
-                        let end_col = sel_end.saturating_sub(line_start);
-                        let seg_start = wrap_col_offset;
-                        let seg_end = wrap_col_offset + segment.chars().count();
-                        let inter_start = start_col.max(seg_start).min(seg_end);
-                        let inter_end = end_col.max(seg_start).min(seg_end);
-                        if inter_start < inter_end {
-                            selection_spans.push(SelectionSpan {
-                                start_col: inter_start - seg_start,
-                                end_col: inter_end - seg_start,
-                            });
-                        }
+                    let start_col = start.clamp(line_start, line_end) - line_start;
+                    let end_col = end.clamp(line_start, line_end) - line_start;
+                    if let Some(span) = intersect_selection(wrap_col_offset, seg_len, start_col, end_col) {
+                        visual.selections.push(span);
                     }
                     if s.is_caret() {
                         let caret = s.head;
                         if caret >= line_start && caret <= line_end {
-                            let col = caret.saturating_sub(line_start);
-                            let seg_start = wrap_col_offset;
-                            let seg_end = wrap_col_offset + segment.chars().count();
-                            if col >= seg_start && col <= seg_end {
-                                cursors.push(col - seg_start);
+                            let col = caret - line_start;
+                            // A caret on a soft-wrap boundary starts the next row; only the
+                            // last row of a line owns the column just past its end.
+                            let is_last_segment = segment_idx + 1 == segments.len();
+                            if col >= wrap_col_offset && (col < seg_end || (is_last_segment && col == seg_end)) {
+                                visual.cursors.push(col - wrap_col_offset);
                             }
                         }
                     }
                 }
-                let highlights = if let Some(ref mut highlighter) = self.highlighter {
-                    highlighter.highlight_lines(&self.buffer.doc.to_string(), line_idx..line_idx + 1)
-                        .ok()
-                        .and_then(|mut h| h.pop())
-                        .map(|h| h.spans)
-                        .unwrap_or_default()
-                } else {
-                    Vec::new()
-                };
-                lines.push(VisualLine {
-                    line_idx,
-                    y_px,
-                    wrap_col_offset,
-                    text: segment.clone(),
-                    selections: selection_spans,
-                    cursors,
-                    is_current_line: line_idx == active_line,
-                    shaped: shaped.clone(),
-                    highlights,
-                });
+                merge_selection_spans(&mut visual.selections);
+                visual.highlights.clear();
+                visual.highlights.extend(
+                    line_highlights
+                        .iter()
+                        .filter(|h| h.start_col < seg_end && h.end_col > wrap_col_offset)
+                        .map(|h| CharHighlightSpan {
+                            start_col: h.start_col.max(wrap_col_offset) - wrap_col_offset,
+                            end_col: h.end_col.min(seg_end) - wrap_col_offset,
+                            token_type: h.token_type,
+                        }),
+                );
+                visual.diagnostics.clear();
+                visual.diagnostics.extend(self.buffer.diagnostics().iter().enumerate().filter_map(
+                    |(index, d)| {
+                        let (start, end) = d.range;
+                        let start_col = start.saturating_sub(line_start);
+                        let end_col = end.max(start + 1).checked_sub(line_start)?;
+                        let start_col = start_col.max(wrap_col_offset);
+                        let end_col = end_col.min(seg_end);
+                        (start_col < end_col).then(|| DiagnosticSpan {
+                            start_col: start_col - wrap_col_offset,
+                            end_col: end_col - wrap_col_offset,
+                            severity: d.severity,
+                            index,
+                        })
+                    },
+                ));
+                visual.line_idx = line_idx;
+                visual.y_px = y_px;
+                visual.wrap_col_offset = wrap_col_offset;
+                visual.indent_cols = if segment_idx == 0 { 0 } else { wrap_indent };
+                visual.text.clone_from(segment);
+                visual.is_current_line = line_idx == active_line;
+                visual.gutter_label.clear();
+                if segment_idx == 0 {
+                    self.layout.gutter.write_label(line_idx, active_line, &mut visual.gutter_label);
+                }
+                visual.shaped.clone_from(&shaped);
+                visual.overflow_col = overflow
+                    .filter(|col| *col < seg_end)
+                    .map(|col| col.saturating_sub(wrap_col_offset));
+                let trailing_end = seg_end.min(content_len);
+                visual.trailing_whitespace = (show_trailing && trailing_start.max(wrap_col_offset) < trailing_end)
+                    .then(|| SelectionSpan {
+                        start_col: trailing_start.max(wrap_col_offset) - wrap_col_offset,
+                        end_col: trailing_end - wrap_col_offset,
+                    });
+                visual.indent_guides.clone_from(indent_guides);
                 y_px += self.metrics.line_height_px;
+                wrap_col_offset = seg_end;
+            }
+        }
+        out.lines.truncate(row);
+        out.gutter_width_cols = gutter_width_cols;
+    }
+
+    /// Insert a snippet template (see `Snippet::parse`) in place of the primary selection
+    /// as a single edit and select its first tab stop. Secondary selections are dropped.
+    pub fn insert_snippet(&mut self, template: &str) {
+        let snippet = Snippet::parse(template);
+        self.buffer.selections.secondary.clear();
+        let base = self.buffer.selections.primary.range().0;
+        self.buffer.apply_text_to_selections(&snippet.text);
+        self.snippet = Some(SnippetSession::new(&snippet, base));
+        self.select_snippet_stop();
+    }
+
+    /// Select the next tab stop of the active snippet. Reaching the final stop ends the snippet.
+    pub fn next_tab_stop(&mut self) -> bool {
+        self.step_tab_stop(true)
+    }
+
+    /// Select the previous tab stop of the active snippet.
+    pub fn prev_tab_stop(&mut self) -> bool {
+        self.step_tab_stop(false)
+    }
+
+    pub fn in_snippet(&self) -> bool {
+        self.snippet.is_some()
+    }
+
+    fn step_tab_stop(&mut self, forward: bool) -> bool {
+        let moved = self.snippet.as_mut().is_some_and(|s| s.advance(forward));
+        if moved {
+            self.select_snippet_stop();
+        }
+        moved
+    }
+
+    fn select_snippet_stop(&mut self) {
+        let Some(session) = &self.snippet else {
+            return;
+        };
+        let (start, end) = session.current_range();
+        self.buffer.selections = SelectionSet {
+            primary: Selection { anchor: start, head: end },
+            secondary: Vec::new(),
+        };
+        if session.is_final_stop() {
+            self.snippet = None;
+        }
+    }
+
+    /// Run an edit while a snippet is active, shifting its stops to match. Edits outside
+    /// the current stop, or with several selections, end the snippet.
+    fn edit_tracking_snippet(&mut self, edit: impl FnOnce(&mut Self)) {
+        let len_before = self.buffer.doc.len_chars();
+        let start_before = self.buffer.selections.primary.range().0;
+        let single = self.buffer.selections.secondary.is_empty();
+        edit(self);
+        let delta = self.buffer.doc.len_chars() as isize - len_before as isize;
+        let edit_start = start_before.min(self.buffer.selections.primary.head);
+        let tracked = single && self.snippet.as_mut().is_some_and(|s| s.track_edit(edit_start, delta));
+        if tracked {
+            self.sync_snippet_mirrors();
+        } else {
+            self.snippet = None;
+        }
+    }
+
+    /// Copy the current stop's text into its linked occurrences, folding the change into
+    /// the edit that caused it so both undo together.
+    fn sync_snippet_mirrors(&mut self) {
+        let Some(session) = self.snippet.as_mut() else {
+            return;
+        };
+        let (start, end) = session.current_range();
+        let text = self.buffer.doc.slice_to_string(start, end);
+        let mirrors = session.mirror_ranges();
+        if mirrors.iter().all(|&(s, e)| self.buffer.doc.slice_to_string(s, e) == text) {
+            return;
+        }
+        session.resize_mirrors(text.chars().count());
+        let ranges = mirrors
+            .into_iter()
+            .map(|(start_char, end_char)| ReplaceRange { start_char, end_char, inserted: text.clone() })
+            .collect();
+        let selections = self.buffer.selections.clone();
+        let impact = self.buffer.last_edit_impact;
+        self.buffer.apply_replace_ranges(ranges, TransactionKind::Replace, selections);
+        self.buffer.history.merge_last();
+        if let (Some(a), Some(b)) = (impact, self.buffer.last_edit_impact) {
+            self.buffer.last_edit_impact = Some(EditImpact {
+                start_line: a.start_line.min(b.start_line),
+                end_line_inclusive: a.end_line_inclusive.max(b.end_line_inclusive),
+            });
+        }
+    }
+
+    /// Whether the drawn highlights are stale and waiting for edits to pause. The UI
+    /// should build another view model once `LayoutConfig::highlight_debounce` passes.
+    pub fn highlight_pending(&self) -> bool {
+        self.highlight_dirty_since.is_some()
+    }
+
+    /// Highlight the visible lines that have no cached spans, in a single highlighter pass.
+    fn refresh_highlights(&mut self, first: usize, last_exclusive: usize) {
+        let Some(highlighter) = self.highlighter.as_mut() else {
+            return;
+        };
+        let cache = &mut self.highlight_cache;
+        let missing: Vec<usize> = (first..last_exclusive).filter(|l| !cache.contains_key(l)).collect();
+        let (Some(&lo), Some(&hi)) = (missing.first(), missing.last()) else {
+            return;
+        };
+        self.highlight_passes += 1;
+        let doc = &self.buffer.doc;
+        if let Ok(lines) = highlighter.highlight_lines(&doc.to_string(), lo..hi + 1) {
+            for lh in lines {
+                cache
+                    .entry(lh.line_idx)
+                    .or_insert_with(|| lh.to_char_spans(&doc.line_text(lh.line_idx)));
             }
         }
-        EditorViewModel { lines, gutter_width_cols }
+        for line in missing {
+            cache.entry(line).or_default();
+        }
     }
 
     pub fn find_next(
@@ -251,32 +1120,72 @@ impl EditorEngine {
         );
     }
 
-    pub fn replace_all(&mut self, query: &SearchQuery, replacement: &str) -> usize {
-        if query.needle.is_empty() {
-            return 0;
-        }
-        let mut cursor = 0usize;
-        let mut matches = Vec::new();
-        loop {
-            let Some(m) = self.find_next(query, cursor, SearchDirection::Forward) else { break };
-            matches.push(m);
-            cursor = m.end_char;
-            if cursor >= self.buffer.doc.len_chars() {
-                break;
-            }
-        }
-        if matches.is_empty() {
+    /// All non-overlapping matches of `query`, in document order.
+    pub fn find_all(&self, query: &SearchQuery) -> Vec<SearchMatch> {
+        self.buffer.doc.search_iter(&query.needle, query.case_sensitive).collect()
+    }
+
+    /// Each match `replace_all` would replace, with the text it would put there. The
+    /// buffer is left untouched.
+    pub fn replace_all_preview(
+        &self,
+        query: &SearchQuery,
+        replacement: &str,
+        options: &ReplaceOptions,
+    ) -> Vec<(SearchMatch, String)> {
+        let selections: Vec<(usize, usize)> = match options.scope {
+            ReplaceScope::Document => Vec::new(),
+            ReplaceScope::Selection => self
+                .buffer
+                .selections
+                .ordered()
+                .iter()
+                .filter(|s| !s.is_caret())
+                .map(|s| s.range())
+                .collect(),
+        };
+        self.find_all(query)
+            .into_iter()
+            .filter(|m| {
+                options.scope == ReplaceScope::Document
+                    || selections.iter().any(|(start, end)| *start <= m.start_char && m.end_char <= *end)
+            })
+            .map(|m| {
+                let inserted = if options.preserve_case {
+                    match_case(&self.buffer.doc.slice_to_string(m.start_char, m.end_char), replacement)
+                } else {
+                    replacement.to_string()
+                };
+                (m, inserted)
+            })
+            .collect()
+    }
+
+    /// Replace every match of `query` in the scope as one transaction. Replacing in the
+    /// whole document leaves the caret after the last replacement; replacing in the
+    /// selections keeps them, grown or shrunk to the new text. Returns the number of
+    /// replacements.
+    pub fn replace_all(&mut self, query: &SearchQuery, replacement: &str, options: &ReplaceOptions) -> usize {
+        let edits = self.replace_all_preview(query, replacement, options);
+        let count = edits.len();
+        let mut shift = 0isize;
+        let mut caret = 0usize;
+        let ranges: Vec<ReplaceRange> = edits
+            .into_iter()
+            .map(|(m, inserted)| {
+                let inserted_len = inserted.chars().count();
+                caret = m.start_char.saturating_add_signed(shift) + inserted_len;
+                shift += inserted_len as isize - (m.end_char - m.start_char) as isize;
+                ReplaceRange { start_char: m.start_char, end_char: m.end_char, inserted }
+            })
+            .collect();
+        if ranges.is_empty() {
             return 0;
         }
-        let mut ranges = Vec::with_capacity(matches.len());
-        for m in matches.iter() {
-            ranges.push(ReplaceRange {
-                start_char: m.start_char,
-                end_char: m.end_char,
-                inserted: replacement.to_string(),
-            });
+        if options.scope == ReplaceScope::Selection {
+            self.buffer.apply_replace_ranges_preserving_selections(ranges);
+            return count;
         }
-        let caret = ranges.last().map(|r| r.start_char + replacement.chars().count()).unwrap_or(0);
         self.buffer.apply_replace_ranges(
             ranges,
             TransactionKind::Replace,
@@ -285,36 +1194,155 @@ impl EditorEngine {
                 secondary: Vec::new(),
             },
         );
-        matches.len()
+        count
     }
 
-    fn copy(&self) -> String {
-        let selections = self.buffer.selections.all_including_primary();
+    /// Join the selected texts in document order. Full-line selections already end in
+    /// a newline and are concatenated; others are joined with one. The pieces are kept
+    /// so pasting the same text over as many carets maps them one to one.
+    fn copy(&mut self) -> String {
+        let selections = self.buffer.selections.ordered();
         if selections.iter().all(|s| s.is_caret()) {
             return String::new();
         }
-        let mut out = String::new();
-        for (i, s) in selections.iter().enumerate() {
-            if i > 0 {
-                out.push('\n');
-            }
-            let (start, end) = s.range();
-            out.push_str(&self.buffer.doc.slice_to_string(start, end));
-        }
+        let pieces: Vec<String> = selections
+            .iter()
+            .map(|s| {
+                let (start, end) = s.range();
+                self.buffer.doc.slice_to_string(start, end)
+            })
+            .collect();
+        let full_lines = pieces.iter().all(|p| p.ends_with('\n'))
+            && selections.iter().all(|s| {
+                let start = s.range().0;
+                start == self.buffer.doc.line_start_char(self.buffer.doc.char_to_line(start))
+            });
+        let out = if full_lines { pieces.concat() } else { pieces.join("\n") };
+        self.copied_pieces = Some((out.clone(), pieces));
         out
     }
 
-    fn cut(&mut self) -> String {
-        let text = self.copy();
-        if text.is_empty() {
-            return text;
-        }
-        self.buffer.apply_text_to_selections("");
-        text
+    /// Use `clipboard` for copy, cut and paste, e.g. to reach the system clipboard.
+    pub fn set_clipboard(&mut self, clipboard: Arc<dyn Clipboard>) {
+        self.clipboard = clipboard;
     }
 
-    fn backspace(&mut self) {
-        let selections = self.buffer.selections.all_including_primary();
+    pub fn clipboard(&self) -> &dyn Clipboard {
+        self.clipboard.as_ref()
+    }
+
+    pub fn clipboard_ring(&self) -> &ClipboardRing {
+        &self.clipboard_ring
+    }
+
+    /// With several carets and exactly one clipboard line per caret, paste line `i`
+    /// at caret `i`; otherwise paste the whole text at every caret.
+    fn paste_text(&mut self, text: &str) {
+        let pasted = self.transform_paste(text, self.buffer.selections.primary.head);
+        if !self.buffer.selections.secondary.is_empty() {
+            if let Some((_, pieces)) = self.copied_pieces.clone().filter(|(copied, _)| copied == text) {
+                // Each piece is indented to the selection it replaces
+                let pieces: Vec<String> = pieces
+                    .iter()
+                    .zip(self.buffer.selections.ordered())
+                    .map(|(piece, selection)| self.transform_paste(piece, selection.range().0))
+                    .collect();
+                let pieces: Vec<&str> = pieces.iter().map(String::as_str).collect();
+                if self.buffer.apply_texts_to_selections(&pieces) {
+                    return;
+                }
+            }
+            let text = pasted.as_str();
+            let lines: Vec<&str> = text
+                .strip_suffix('\n')
+                .unwrap_or(text)
+                .split('\n')
+                .map(|l| l.strip_suffix('\r').unwrap_or(l))
+                .collect();
+            if self.buffer.apply_texts_to_selections(&lines) {
+                return;
+            }
+        }
+        self.buffer.apply_text_to_selections(&pasted);
+    }
+
+    /// Apply the `EditConfig` paste options to `text`, indenting to the line of `caret`.
+    fn transform_paste(&self, text: &str, caret: usize) -> String {
+        let mut lines: Vec<String> = text.split('\n').map(str::to_string).collect();
+        if self.edit.trim_trailing_on_paste {
+            for line in lines.iter_mut() {
+                let cr = line.ends_with('\r');
+                let trimmed_len = line.trim_end_matches([' ', '\t', '\r']).len();
+                line.truncate(trimmed_len);
+                if cr {
+                    line.push('\r');
+                }
+            }
+        }
+        if self.edit.reindent_on_paste && lines.len() > 1 {
+            let is_blank = |line: &str| line.trim().is_empty();
+            let leading = |line: &str| line.chars().take_while(|c| *c == ' ' || *c == '\t').count();
+            let common = lines.iter().filter(|l| !is_blank(l)).map(|l| leading(l)).min().unwrap_or(0);
+            let LineCol { line, col } = self.buffer.doc.char_to_line_col(caret);
+            let indent: String = self
+                .buffer
+                .doc
+                .line_text(line)
+                .chars()
+                .take(col)
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect();
+            for (i, line) in lines.iter_mut().enumerate() {
+                if is_blank(line) {
+                    continue;
+                }
+                let dedented: String = line.chars().skip(common.min(leading(line))).collect();
+                *line = if i == 0 { dedented } else { format!("{indent}{dedented}") };
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Paste the newest clipboard ring entry; repeating right after replaces the pasted
+    /// text with the next older entry, wrapping around.
+    fn paste_cycle(&mut self) {
+        let caret = self.buffer.selections.primary.head;
+        let repeat = self.paste_cycle.filter(|c| {
+            c.doc_version == self.buffer.doc.version()
+                && c.end == caret
+                && self.buffer.selections.is_single_caret()
+        });
+        let (start, ring_index) = match repeat {
+            Some(c) => {
+                self.buffer.selections.primary = Selection { anchor: c.start, head: c.end };
+                (c.start, (c.ring_index + 1) % self.clipboard_ring.len().max(1))
+            }
+            None => (self.buffer.selections.primary.range().0, 0),
+        };
+        let Some(text) = self.clipboard_ring.get(ring_index).map(str::to_string) else {
+            return;
+        };
+        let single = self.buffer.selections.secondary.is_empty();
+        self.buffer.apply_text_to_selections(&text);
+        self.paste_cycle = single.then(|| PasteCycle {
+            start,
+            end: start + text.chars().count(),
+            ring_index,
+            doc_version: self.buffer.doc.version(),
+        });
+    }
+
+    fn cut(&mut self) -> String {
+        let text = self.copy();
+        if text.is_empty() {
+            return text;
+        }
+        self.buffer.apply_text_to_selections("");
+        text
+    }
+
+    fn backspace(&mut self) {
+        let selections = self.buffer.selections.all_including_primary();
         if selections.iter().any(|s| !s.is_caret()) {
             self.buffer.apply_text_to_selections("");
             return;
@@ -339,7 +1367,8 @@ impl EditorEngine {
         self.buffer.apply_text_to_selections("");
     }
 
-    fn delete_word_backward(&mut self) {
+    /// Delete from each caret back to the boundary `find_start` reports.
+    fn delete_word_backward(&mut self, find_start: fn(&str, usize) -> usize) {
         let selections = self.buffer.selections.all_including_primary();
         if selections.iter().any(|s| !s.is_caret()) {
             self.buffer.apply_text_to_selections("");
@@ -349,7 +1378,7 @@ impl EditorEngine {
         let mut ranges = Vec::with_capacity(selections.len());
         for s in selections.iter() {
             let caret = s.head;
-            let start = find_word_left(&text, caret);
+            let start = find_start(&text, caret);
             if start < caret {
                 ranges.push(ReplaceRange { start_char: start, end_char: caret, inserted: String::new() });
             }
@@ -362,7 +1391,8 @@ impl EditorEngine {
         );
     }
 
-    fn delete_word_forward(&mut self) {
+    /// Delete from each caret forward to the boundary `find_end` reports.
+    fn delete_word_forward(&mut self, find_end: fn(&str, usize) -> usize) {
         let selections = self.buffer.selections.all_including_primary();
         if selections.iter().any(|s| !s.is_caret()) {
             self.buffer.apply_text_to_selections("");
@@ -372,7 +1402,7 @@ impl EditorEngine {
         let mut ranges = Vec::with_capacity(selections.len());
         for s in selections.iter() {
             let caret = s.head;
-            let end = find_word_right(&text, caret);
+            let end = find_end(&text, caret);
             if caret < end {
                 ranges.push(ReplaceRange { start_char: caret, end_char: end, inserted: String::new() });
             }
@@ -439,12 +1469,38 @@ impl EditorEngine {
         let doc_len = self.buffer.doc.len_chars();
         let selections = self.buffer.selections.all_including_primary();
         let doc_text = self.buffer.doc.to_string();
+        let page_lines = self.page_lines(movement);
+        let vertical = matches!(
+            movement,
+            Movement::Up
+                | Movement::Down
+                | Movement::PageUp
+                | Movement::PageDown
+                | Movement::HalfPageUp
+                | Movement::HalfPageDown
+        );
+        let heads: Vec<usize> = selections.iter().map(|s| s.head).collect();
+        let goal_cols = self
+            .goal_columns
+            .take()
+            .filter(|g| vertical && g.heads == heads && g.doc_version == self.buffer.doc.version())
+            .map(|g| g.cols);
+        let mut cols = Vec::with_capacity(selections.len());
         let mut moved = Vec::with_capacity(selections.len());
-        for s in selections.iter() {
+        for (i, s) in selections.iter().enumerate() {
             let (start, end) = s.range();
             let base = if extend {
                 s.head
-            } else if matches!(movement, Movement::Left | Movement::Up | Movement::WordLeft | Movement::LineStart) {
+            } else if matches!(
+                movement,
+                Movement::Left
+                    | Movement::Up
+                    | Movement::WordLeft
+                    | Movement::SubWordLeft
+                    | Movement::LineStart
+                    | Movement::PageUp
+                    | Movement::HalfPageUp
+            ) {
                 start
             } else {
                 end
@@ -458,17 +1514,36 @@ impl EditorEngine {
                 }
                 Movement::LineEnd => {
                     let line = self.buffer.doc.char_to_line(base);
-                    self.buffer.doc.line_end_char(line)
+                    self.buffer.doc.line_end_char_no_newline(line)
                 }
                 Movement::WordLeft => find_word_left(&doc_text, base),
                 Movement::WordRight => find_word_right(&doc_text, base),
+                Movement::SubWordLeft => find_subword_left(&doc_text, base),
+                Movement::SubWordRight => find_subword_right(&doc_text, base),
                 Movement::Up => {
                     let lc = self.buffer.doc.char_to_line_col(base);
-                    if lc.line == 0 { base } else { self.buffer.doc.line_col_to_char(lc.line - 1, lc.col) }
+                    let col = goal_cols.as_ref().map_or(lc.col, |g| g[i]);
+                    cols.push(col);
+                    if lc.line == 0 { base } else { self.vertical_target(lc.line - 1, col) }
                 }
                 Movement::Down => {
                     let lc = self.buffer.doc.char_to_line_col(base);
-                    if lc.line + 1 >= self.buffer.doc.len_lines() { base } else { self.buffer.doc.line_col_to_char(lc.line + 1, lc.col) }
+                    let col = goal_cols.as_ref().map_or(lc.col, |g| g[i]);
+                    cols.push(col);
+                    if lc.line + 1 >= self.buffer.doc.len_lines() { base } else { self.vertical_target(lc.line + 1, col) }
+                }
+                Movement::PageUp | Movement::HalfPageUp => {
+                    let lc = self.buffer.doc.char_to_line_col(base);
+                    let col = goal_cols.as_ref().map_or(lc.col, |g| g[i]);
+                    cols.push(col);
+                    self.vertical_target(lc.line.saturating_sub(page_lines), col)
+                }
+                Movement::PageDown | Movement::HalfPageDown => {
+                    let lc = self.buffer.doc.char_to_line_col(base);
+                    let col = goal_cols.as_ref().map_or(lc.col, |g| g[i]);
+                    cols.push(col);
+                    let last_line = self.buffer.doc.len_lines().saturating_sub(1);
+                    self.vertical_target((lc.line + page_lines).min(last_line), col)
                 }
             };
             if extend {
@@ -477,6 +1552,13 @@ impl EditorEngine {
                 moved.push(Selection { anchor: new_head, head: new_head });
             }
         }
+        if vertical {
+            self.goal_columns = Some(GoalColumns {
+                heads: moved.iter().map(|s| s.head).collect(),
+                cols,
+                doc_version: self.buffer.doc.version(),
+            });
+        }
         let mut new_set = SelectionSet::default();
         if let Some(p) = moved.first().copied() {
             new_set.primary = p;
@@ -487,12 +1569,96 @@ impl EditorEngine {
         self.buffer.selections = new_set;
     }
 
+    /// Char index for `col` on `line`, clamped to stay before the line terminator.
+    fn vertical_target(&self, line: usize, col: usize) -> usize {
+        self.buffer.doc.line_start_char(line) + col.min(self.buffer.doc.line_len_chars(line))
+    }
+
+    /// Lines moved by a page movement: the viewport height, or half of it.
+    fn page_lines(&self, movement: Movement) -> usize {
+        let rows = self.viewport.max_lines.max(1);
+        match movement {
+            Movement::PageUp | Movement::PageDown => rows,
+            Movement::HalfPageUp | Movement::HalfPageDown => (rows / 2).max(1),
+            _ => 0,
+        }
+    }
+
+    /// Scroll the viewport along with a page movement so the caret keeps its row on screen.
+    fn scroll_by_page(&mut self, movement: Movement) {
+        let lines = self.page_lines(movement);
+        let last_first = self.buffer.doc.len_lines().saturating_sub(self.viewport.max_lines.max(1));
+        match movement {
+            Movement::PageUp | Movement::HalfPageUp => {
+                self.viewport.first_line = self.viewport.first_line.saturating_sub(lines);
+            }
+            Movement::PageDown | Movement::HalfPageDown => {
+                self.viewport.first_line = (self.viewport.first_line + lines).min(last_first);
+            }
+            _ => {}
+        }
+    }
+
+    /// Insert a line break. A single caret directly between a bracket pair gets the
+    /// close bracket pushed to its own line and lands on an indented line between them.
+    fn newline(&mut self) {
+        let caret = self.buffer.selections.primary.head;
+        let between_pair = self.edit.expand_brackets_on_newline
+            && self.buffer.selections.is_single_caret()
+            && caret > 0
+            && matches!(
+                self.buffer.doc.slice_to_string(caret - 1, caret + 1).as_str(),
+                "()" | "[]" | "{}"
+            );
+        if !between_pair {
+            if !self.edit.auto_indent {
+                self.buffer.apply_text_to_selections("\n");
+                return;
+            }
+            self.buffer.selections.normalize();
+            let texts: Vec<String> = self
+                .buffer
+                .selections
+                .ordered()
+                .iter()
+                .map(|s| {
+                    let start = s.range().0;
+                    let line = self.buffer.doc.char_to_line(start);
+                    let col = start - self.buffer.doc.line_start_char(line);
+                    let indent: String = self
+                        .buffer
+                        .doc
+                        .line_text(line)
+                        .chars()
+                        .take(col)
+                        .take_while(|c| *c == ' ' || *c == '\t')
+                        .collect();
+                    format!("\n{indent}")
+                })
+                .collect();
+            let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+            self.buffer.apply_texts_to_selections(&texts);
+            return;
+        }
+        let line_text = self.buffer.doc.line_text(self.buffer.doc.char_to_line(caret));
+        let indent: String = line_text.chars().take_while(|c| *c == ' ' || *c == '\t').collect();
+        let inner = format!("\n{indent}{}", self.edit.indent_unit);
+        let new_caret = caret + inner.chars().count();
+        self.buffer.apply_replace_ranges(
+            vec![ReplaceRange { start_char: caret, end_char: caret, inserted: format!("{inner}\n{indent}") }],
+            TransactionKind::Insert,
+            SelectionSet { primary: Selection { anchor: new_caret, head: new_caret }, secondary: Vec::new() },
+        );
+    }
+
     fn indent(&mut self) {
-        apply_line_prefix_edit(&mut self.buffer, "    ", false);
+        let unit = self.edit.indent_unit.clone();
+        apply_line_prefix_edit(&mut self.buffer, &unit, false);
     }
 
     fn outdent(&mut self) {
-        apply_line_prefix_edit(&mut self.buffer, "    ", true);
+        let unit = self.edit.indent_unit.clone();
+        apply_line_prefix_edit(&mut self.buffer, &unit, true);
     }
 
     fn duplicate_line(&mut self) {
@@ -524,15 +1690,162 @@ impl EditorEngine {
         );
     }
 
+    /// Comment a selection within one line with the language's block comment, or
+    /// otherwise toggle line comments on every selected line. Without a known
+    /// language `//` is used.
     fn toggle_comment(&mut self) {
-        toggle_line_prefix(&mut self.buffer, "//");
+        let config = self.language_name.and_then(|name| self.language_registry.get_language(name));
+        let line_comment = config.and_then(|c| c.line_comment);
+        if let Some((open, close)) = config.and_then(|c| c.block_comment) {
+            if self.toggle_block_comment(open, close) || line_comment.is_none() {
+                return;
+            }
+        }
+        toggle_line_prefix(&mut self.buffer, line_comment.unwrap_or("//"));
+    }
+
+    /// Wrap a single selection that covers part of one line in `open`/`close`, or
+    /// unwrap it if it already is. Returns false for other selections.
+    fn toggle_block_comment(&mut self, open: &str, close: &str) -> bool {
+        let selections = &self.buffer.selections;
+        let (start, end) = selections.primary.range();
+        if !selections.secondary.is_empty() || start == end {
+            return false;
+        }
+        let line = self.buffer.doc.char_to_line(start);
+        let line_start = self.buffer.doc.line_start_char(line);
+        if end > self.buffer.doc.line_end_char_no_newline(line) {
+            return false;
+        }
+        let line_text = self.buffer.doc.line_text(line);
+        let content_start = line_start + line_text.chars().take_while(|c| c.is_whitespace()).count();
+        let content_end = line_start + line_text.trim_end().chars().count();
+        if start <= content_start && end >= content_end {
+            return false;
+        }
+        let text = self.buffer.doc.slice_to_string(start, end);
+        let inner = text
+            .strip_prefix(open)
+            .and_then(|t| t.strip_suffix(close))
+            .map(|t| t.strip_prefix(' ').unwrap_or(t))
+            .map(|t| t.strip_suffix(' ').unwrap_or(t));
+        let (inserted, inner_start, inner_len) = match inner {
+            Some(inner) => (inner.to_string(), start, inner.chars().count()),
+            None => (
+                format!("{open} {text} {close}"),
+                start + open.chars().count() + 1,
+                text.chars().count(),
+            ),
+        };
+        self.buffer.apply_replace_ranges(
+            vec![ReplaceRange { start_char: start, end_char: end, inserted }],
+            TransactionKind::Replace,
+            SelectionSet {
+                primary: Selection { anchor: inner_start, head: inner_start + inner_len },
+                secondary: Vec::new(),
+            },
+        );
+        true
+    }
+
+    /// Swap the chars on either side of the caret and step past them. At the end of a
+    /// line the two chars before the caret are swapped; at the start nothing happens.
+    fn transpose_chars(&mut self) {
+        let caret = self.buffer.selections.primary.head;
+        let line = self.buffer.doc.char_to_line(caret);
+        let line_start = self.buffer.doc.line_start_char(line);
+        let line_len = self.buffer.doc.line_len_chars(line);
+        let col = caret - line_start;
+        if col == 0 || line_len < 2 {
+            return;
+        }
+        let start = if col >= line_len { line_start + line_len - 2 } else { caret - 1 };
+        let pair: String = self.buffer.doc.slice_to_string(start, start + 2).chars().rev().collect();
+        let new_caret = start + 2;
+        self.buffer.apply_replace_ranges(
+            vec![ReplaceRange { start_char: start, end_char: start + 2, inserted: pair }],
+            TransactionKind::Other,
+            SelectionSet { primary: Selection { anchor: new_caret, head: new_caret }, secondary: Vec::new() },
+        );
+    }
+
+    /// Swap the word before the caret with the word after it, leaving the caret after
+    /// both. A caret inside a word counts as being at that word's end.
+    fn transpose_words(&mut self) {
+        let chars: Vec<char> = self.buffer.doc.to_string().chars().collect();
+        let mut caret = self.buffer.selections.primary.head.min(chars.len());
+        while caret > 0 && caret < chars.len() && is_word_char(chars[caret - 1]) && is_word_char(chars[caret]) {
+            caret += 1;
+        }
+        let mut end1 = caret;
+        while end1 > 0 && !is_word_char(chars[end1 - 1]) {
+            end1 -= 1;
+        }
+        let mut start1 = end1;
+        while start1 > 0 && is_word_char(chars[start1 - 1]) {
+            start1 -= 1;
+        }
+        let mut start2 = caret;
+        while start2 < chars.len() && !is_word_char(chars[start2]) {
+            start2 += 1;
+        }
+        let mut end2 = start2;
+        while end2 < chars.len() && is_word_char(chars[end2]) {
+            end2 += 1;
+        }
+        if start1 == end1 || start2 == end2 {
+            return;
+        }
+        let word1: String = chars[start1..end1].iter().collect();
+        let between: String = chars[end1..start2].iter().collect();
+        let word2: String = chars[start2..end2].iter().collect();
+        self.buffer.apply_replace_ranges(
+            vec![ReplaceRange { start_char: start1, end_char: end2, inserted: format!("{word2}{between}{word1}") }],
+            TransactionKind::Other,
+            SelectionSet { primary: Selection { anchor: end2, head: end2 }, secondary: Vec::new() },
+        );
     }
 }
 
+/// Move cached per-line values after `edit_line` by `delta` lines, dropping those that
+/// land on or before it.
+fn shift_line_keys<T>(cache: HashMap<usize, T>, edit_line: usize, delta: isize) -> HashMap<usize, T> {
+    cache
+        .into_iter()
+        .filter_map(|(line, value)| {
+            if line <= edit_line {
+                return Some((line, value));
+            }
+            let shifted = line.checked_add_signed(delta)?;
+            (shifted > edit_line).then_some((shifted, value))
+        })
+        .collect()
+}
+
+/// Build a selection set from selections in document order, flagged when primary. The
+/// first becomes primary if none is flagged.
+fn selection_set_from(selections: Vec<(Selection, bool)>) -> SelectionSet {
+    let primary_idx = selections.iter().position(|(_, p)| *p).unwrap_or(0);
+    let mut secondary: Vec<Selection> = selections.iter().map(|(s, _)| *s).collect();
+    let primary = secondary.remove(primary_idx);
+    SelectionSet { primary, secondary }
+}
+
 fn is_word_char(c: char) -> bool {
     c.is_alphanumeric() || c == '_'
 }
 
+/// Groups chars for word selection: 0 for word chars, 1 for whitespace, 2 otherwise.
+fn char_class(c: char) -> u8 {
+    if is_word_char(c) {
+        0
+    } else if c.is_whitespace() {
+        1
+    } else {
+        2
+    }
+}
+
 fn find_word_left(text: &str, from_char: usize) -> usize {
     let chars: Vec<char> = text.chars().collect();
     let mut i = from_char.min(chars.len());
@@ -568,6 +1881,65 @@ fn find_word_right(text: &str, from_char: usize) -> usize {
     i
 }
 
+/// Whether a sub-word starts at `chars[i]`, where `chars[i - 1]` and `chars[i]` are word
+/// chars: after an underscore, at a lower-to-upper hump, before the last capital of an
+/// acronym followed by lowercase (`HTTPResponse`), or where letters and digits meet.
+fn is_subword_boundary(chars: &[char], i: usize) -> bool {
+    let (prev, cur) = (chars[i - 1], chars[i]);
+    prev == '_'
+        || (prev.is_lowercase() && cur.is_uppercase())
+        || (prev.is_uppercase() && cur.is_uppercase() && chars.get(i + 1).is_some_and(|c| c.is_lowercase()))
+        || prev.is_numeric() != cur.is_numeric()
+}
+
+fn find_subword_left(text: &str, from_char: usize) -> usize {
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = from_char.min(chars.len());
+    while i > 0 && chars[i - 1].is_whitespace() {
+        i -= 1;
+    }
+    if i > 0 && char_class(chars[i - 1]) == 2 {
+        while i > 0 && char_class(chars[i - 1]) == 2 {
+            i -= 1;
+        }
+        return i;
+    }
+    while i > 0 && chars[i - 1] == '_' {
+        i -= 1;
+    }
+    if i > 0 && is_word_char(chars[i - 1]) {
+        i -= 1;
+        while i > 0 && is_word_char(chars[i - 1]) && chars[i - 1] != '_' && !is_subword_boundary(&chars, i) {
+            i -= 1;
+        }
+    }
+    i
+}
+
+fn find_subword_right(text: &str, from_char: usize) -> usize {
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = from_char.min(chars.len());
+    while i < chars.len() && chars[i].is_whitespace() {
+        i += 1;
+    }
+    if i < chars.len() && char_class(chars[i]) == 2 {
+        while i < chars.len() && char_class(chars[i]) == 2 {
+            i += 1;
+        }
+        return i;
+    }
+    while i < chars.len() && chars[i] == '_' {
+        i += 1;
+    }
+    if i < chars.len() && is_word_char(chars[i]) {
+        i += 1;
+        while i < chars.len() && chars[i] != '_' && is_word_char(chars[i]) && !is_subword_boundary(&chars, i) {
+            i += 1;
+        }
+    }
+    i
+}
+
 fn apply_line_prefix_edit(buffer: &mut Buffer, prefix: &str, remove: bool) {
     let selections = buffer.selections.all_including_primary();
     let mut lines = Vec::new();
@@ -630,3 +2002,910 @@ fn toggle_line_prefix(buffer: &mut Buffer, prefix: &str) {
     }
     apply_line_prefix_edit(buffer, prefix, false);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keymap::{KeyChord, KeyCode, KeyModifiers};
+    use crate::document::Document;
+    use crate::layout::GutterConfig;
+
+    fn engine(text: &str) -> EditorEngine {
+        let mut engine = EditorEngine::new(text);
+        engine.metrics = FontMetrics { char_width_px: 8.0, line_height_px: 16.0 };
+        engine
+    }
+
+    #[test]
+    fn test_set_viewport_pixels() {
+        let text = "line\n".repeat(100);
+        let mut engine = engine(&text);
+        engine.set_viewport_pixels(160.0, 800.0, 320.0);
+        assert_eq!(engine.viewport, Viewport { first_line: 20, max_lines: 10, width_cols: 100 });
+
+        engine.set_viewport_pixels(160.0, 800.0, 1_000_000.0);
+        assert_eq!(engine.viewport.first_line, 100);
+    }
+
+    #[test]
+    fn test_ensure_cursor_visible_scrolls_forward() {
+        let text = "line\n".repeat(100);
+        let mut engine = engine(&text);
+        engine.viewport = Viewport { first_line: 0, max_lines: 10, width_cols: 80 };
+        engine.layout.scroll_off = 2;
+        let caret = engine.buffer.doc.line_start_char(20);
+        engine.buffer.selections.set_single_caret(caret);
+        engine.ensure_cursor_visible();
+        assert_eq!(engine.viewport.first_line, 13);
+
+        engine.goto_line(5);
+        assert_eq!(engine.viewport.first_line, 3);
+    }
+
+    #[test]
+    fn test_page_down_moves_caret_and_scrolls() {
+        let text = "line\n".repeat(100);
+        let mut engine = engine(&text);
+        engine.viewport = Viewport { first_line: 0, max_lines: 10, width_cols: 80 };
+        engine.layout.scroll_off = 2;
+        engine.goto_line(3);
+        engine.apply_key_action(KeyAction::Move { movement: Movement::PageDown, extend: false });
+        assert_eq!(engine.buffer.doc.char_to_line(engine.buffer.selections.primary.head), 13);
+        assert_eq!(engine.viewport.first_line, 10);
+
+        engine.apply_key_action(KeyAction::Move { movement: Movement::HalfPageUp, extend: false });
+        assert_eq!(engine.buffer.doc.char_to_line(engine.buffer.selections.primary.head), 8);
+        assert_eq!(engine.viewport.first_line, 5);
+
+        engine.goto_line(98);
+        engine.apply_key_action(KeyAction::Move { movement: Movement::PageDown, extend: false });
+        assert_eq!(engine.buffer.doc.char_to_line(engine.buffer.selections.primary.head), 100);
+        assert_eq!(engine.viewport.first_line, 91);
+    }
+
+    #[test]
+    fn test_vertical_movement_keeps_goal_column() {
+        let mut engine = engine("long line here\nab\nanother long line\n");
+        engine.buffer.selections.set_single_caret(10);
+        let down = KeyAction::Move { movement: Movement::Down, extend: false };
+        let up = KeyAction::Move { movement: Movement::Up, extend: false };
+        engine.apply_key_action(down);
+        assert_eq!(engine.buffer.doc.char_to_line_col(engine.buffer.selections.primary.head).col, 2);
+        engine.apply_key_action(down);
+        assert_eq!(engine.buffer.doc.char_to_line_col(engine.buffer.selections.primary.head).col, 10);
+        engine.apply_key_action(up);
+        engine.apply_key_action(up);
+        assert_eq!(engine.buffer.selections.primary.head, 10);
+
+        engine.apply_key_action(down);
+        engine.apply_key_action(KeyAction::Move { movement: Movement::Left, extend: false });
+        engine.apply_key_action(up);
+        assert_eq!(engine.buffer.selections.primary.head, 1);
+    }
+
+    #[test]
+    fn test_transpose_chars_and_words() {
+        let mut engine = engine("abc\n");
+        engine.buffer.selections.set_single_caret(2);
+        engine.apply_key_action(KeyAction::TransposeChars);
+        assert_eq!(engine.buffer.doc.to_string(), "acb\n");
+        assert_eq!(engine.buffer.selections.primary.head, 3);
+        engine.apply_key_action(KeyAction::TransposeChars);
+        assert_eq!(engine.buffer.doc.to_string(), "abc\n");
+        engine.buffer.selections.set_single_caret(0);
+        engine.apply_key_action(KeyAction::TransposeChars);
+        assert_eq!(engine.buffer.doc.to_string(), "abc\n");
+
+        engine.buffer.set_text("let foo = bar;");
+        engine.buffer.selections.set_single_caret(8);
+        engine.apply_key_action(KeyAction::TransposeWords);
+        assert_eq!(engine.buffer.doc.to_string(), "let bar = foo;");
+        assert_eq!(engine.buffer.selections.primary.head, 13);
+        engine.buffer.undo();
+        assert_eq!(engine.buffer.doc.to_string(), "let foo = bar;");
+    }
+
+    #[test]
+    fn test_newline_between_brackets_expands_block() {
+        let mut engine = engine("  fn f() {}");
+        engine.edit.indent_unit = "\t".to_string();
+        engine.buffer.selections.set_single_caret(10);
+        engine.apply_key_action(KeyAction::Newline);
+        assert_eq!(engine.buffer.doc.to_string(), "  fn f() {\n  \t\n  }");
+        assert_eq!(engine.buffer.selections.primary.head, 14);
+
+        engine.apply_key_action(KeyAction::Newline);
+        assert_eq!(engine.buffer.doc.to_string(), "  fn f() {\n  \t\n\n  }");
+    }
+
+    #[test]
+    fn test_end_stops_before_newline() {
+        let mut engine = engine("abc\ndef\n");
+        engine.buffer.selections.set_single_caret(1);
+        engine.apply_key_action(KeyAction::Move { movement: Movement::LineEnd, extend: false });
+        assert_eq!(engine.buffer.selections.primary.head, 3);
+    }
+
+    #[test]
+    fn test_end_then_type_stays_on_line() {
+        let end = KeyAction::Move { movement: Movement::LineEnd, extend: false };
+        let mut engine = engine("one\ntwo\n\nlast");
+        engine.buffer.selections.set_single_caret(5);
+        engine.apply_key_action(end);
+        engine.insert_text("!");
+        assert_eq!(engine.buffer.doc.line_text(1), "two!");
+
+        engine.buffer.selections.set_single_caret(engine.buffer.doc.line_start_char(3));
+        engine.apply_key_action(end);
+        engine.insert_text("!");
+        assert_eq!(engine.buffer.doc.to_string(), "one\ntwo!\n\nlast!");
+
+        engine.buffer.selections.set_single_caret(9);
+        engine.apply_key_action(end);
+        assert_eq!(engine.buffer.selections.primary.head, 9);
+    }
+
+    #[test]
+    fn test_extend_to_from_anchor() {
+        let mut engine = engine("hello world");
+        engine.set_anchor(6);
+        engine.extend_to(9);
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 6, head: 9 });
+        engine.extend_to(2);
+        assert_eq!(engine.buffer.selections.primary.range(), (2, 6));
+        engine.extend_to(50);
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 6, head: 11 });
+
+        engine.clear_anchor();
+        engine.buffer.selections.set_single_caret(1);
+        engine.extend_to(4);
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 1, head: 4 });
+    }
+
+    #[test]
+    fn test_select_word_and_line_at() {
+        let mut engine = engine("let foo_bar =  1;\nnext");
+        engine.select_word_at(6);
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 4, head: 11 });
+        engine.select_word_at(13);
+        assert_eq!(engine.buffer.selections.primary.range(), (13, 15));
+        engine.select_word_at(17);
+        assert_eq!(engine.buffer.selections.primary.range(), (16, 17));
+        engine.select_word_at(100);
+        assert_eq!(engine.buffer.selections.primary.range(), (18, 22));
+
+        engine.select_line_at(3, true);
+        assert_eq!(engine.buffer.selections.primary.range(), (0, 18));
+        engine.select_line_at(3, false);
+        assert_eq!(engine.buffer.selections.primary.range(), (0, 17));
+        engine.extend_to(20);
+        assert_eq!(engine.buffer.selections.primary.range(), (0, 20));
+    }
+
+    #[test]
+    fn test_reload_keeps_caret_in_place() {
+        let mut engine = engine("fn a() {}\nfn b() {}\n");
+        engine.buffer.selections.set_single_caret(13);
+        engine.reload("// header\nfn a() {}\nfn b() {}\n");
+        assert_eq!(engine.buffer.selections.primary.head, 23);
+        assert_eq!(engine.buffer.doc.char_to_line_col(23).line, 2);
+
+        engine.reload("// header\n");
+        assert_eq!(engine.buffer.selections.primary.head, 10);
+        engine.buffer.undo();
+        assert_eq!(engine.buffer.doc.to_string(), "// header\nfn a() {}\nfn b() {}\n");
+    }
+
+    #[test]
+    fn test_view_model_debug_string() {
+        let mut engine = engine("alpha\nbeta\ngamma");
+        engine.buffer.selections.primary = Selection { anchor: 1, head: 1 };
+        engine.buffer.selections.secondary =
+            vec![Selection { anchor: 8, head: 8 }, Selection { anchor: 14, head: 12 }];
+        assert_eq!(engine.view_model().to_debug_string(), "  1 a|lpha\n  2 be|ta\n  3 g[am]ma");
+    }
+
+    #[test]
+    fn test_relative_gutter_labels() {
+        let mut engine = engine("a\nb\nc\nxxxxxxxxxxxx\ne");
+        engine.layout.gutter = GutterConfig { relative: true, show_absolute_current: true };
+        engine.layout.soft_wrap = true;
+        engine.viewport = Viewport { first_line: 0, max_lines: 10, width_cols: 8 };
+        engine.buffer.selections.set_single_caret(engine.buffer.doc.line_start_char(2));
+        let labels: Vec<String> = engine.view_model().lines.into_iter().map(|l| l.gutter_label).collect();
+        assert_eq!(labels, vec!["2", "1", "3", "1", "", "2"]);
+
+        engine.layout.gutter.show_absolute_current = false;
+        assert_eq!(engine.view_model().lines[2].gutter_label, "0");
+    }
+
+    #[test]
+    fn test_diagnostics_follow_edits() {
+        use crate::diagnostics::{Diagnostic, Severity};
+
+        let mut engine = engine("fn main() {\n    let x = foo;\n}\n");
+        engine.buffer.set_diagnostics(vec![Diagnostic {
+            range: (24, 27),
+            severity: Severity::Error,
+            message: "cannot find value `foo`".to_string(),
+        }]);
+        engine.buffer.selections.set_single_caret(20);
+        engine.insert_text("mut ");
+        assert_eq!(engine.buffer.diagnostics()[0].range, (28, 31));
+        assert_eq!(engine.buffer.doc.slice_to_string(28, 31), "foo");
+
+        let vm = engine.view_model();
+        assert!(vm.lines[0].diagnostics.is_empty());
+        assert_eq!(
+            vm.lines[1].diagnostics,
+            vec![DiagnosticSpan { start_col: 16, end_col: 19, severity: Severity::Error, index: 0 }]
+        );
+
+        engine.buffer.undo();
+        assert_eq!(engine.buffer.diagnostics()[0].range, (24, 27));
+    }
+
+    #[test]
+    fn test_symbol_at_cursor() {
+        let text = "// entry\nfn answer() -> u32 {\n    42\n}\n";
+        let mut engine = engine(text);
+        assert_eq!(engine.word_range_at(14), Some((12, 18)));
+        assert_eq!(engine.word_range_at(18), Some((12, 18)));
+        assert_eq!(engine.word_range_at(19), None);
+        assert_eq!(engine.enclosing_node_text(14).as_deref(), Some("answer"));
+
+        engine.set_filename("main.rs");
+        assert_eq!(
+            engine.enclosing_node_text(14).as_deref(),
+            Some("fn answer() -> u32 {\n    42\n}")
+        );
+    }
+
+    #[test]
+    fn test_accept_inline_suggestion() {
+        let mut engine = engine("fn main() {\n    \n}\n");
+        engine.buffer.selections.set_single_caret(16);
+        engine.begin_suggestion();
+        for delta in ["println!(", "\"hi\");"] {
+            engine.push_suggestion_delta(delta);
+        }
+        assert_eq!(engine.suggestion().map(|s| (s.char_idx, s.text.as_str())), Some((16, "println!(\"hi\");")));
+
+        engine.apply_key_action(KeyAction::Indent);
+        assert_eq!(engine.buffer.doc.to_string(), "fn main() {\n    println!(\"hi\");\n}\n");
+        assert_eq!(engine.buffer.selections.primary.head, 31);
+        assert!(engine.suggestion().is_none());
+        assert_eq!(engine.buffer.history.undo.len(), 1);
+
+        engine.begin_suggestion();
+        engine.push_suggestion_delta(" // done");
+        engine.insert_text(" ");
+        assert!(engine.suggestion().is_none());
+        assert!(!engine.accept_suggestion());
+    }
+
+    #[test]
+    fn test_minimap_buckets_lines() {
+        let text = "fn a() {\n    1\n}\n\n\n\nfn b() {\n    22\n}";
+        let engine = engine(text);
+        let rows = engine.minimap(3);
+        let ranges: Vec<(usize, usize)> = rows.iter().map(|r| (r.first_line, r.end_line)).collect();
+        assert_eq!(ranges, vec![(0, 3), (3, 6), (6, 9)]);
+        assert_eq!(rows[0].density, 8.0 / 3.0);
+        assert_eq!(rows[1].density, 0.0);
+        assert_eq!(rows[2].indent, 4.0 / 3.0);
+        assert_eq!(rows[0].dominant_token, None);
+
+        assert_eq!(engine.minimap_line_at(2, 3), 6);
+        assert_eq!(engine.minimap_line_at(7, 3), 6);
+        assert_eq!(engine.minimap(20).len(), 9);
+        assert_eq!(engine.minimap_line_at(4, 20), 4);
+    }
+
+    #[test]
+    fn test_overflow_past_max_line_length() {
+        let long = "x".repeat(90);
+        let mut engine = engine(&format!("short\n{long}\n\t{}\n", "y".repeat(78)));
+        engine.layout.rulers = vec![80];
+        engine.layout.max_line_length = Some(80);
+        let vm = engine.view_model();
+        assert_eq!(vm.lines[0].overflow_col, None);
+        assert_eq!(vm.lines[1].overflow_col, Some(80));
+        // The tab takes four columns, so the 78th `y` already ends at column 81.
+        assert_eq!(vm.lines[2].overflow_col, Some(77));
+
+        engine.layout.soft_wrap = true;
+        engine.viewport.width_cols = 50;
+        let vm = engine.view_model();
+        assert_eq!(vm.lines[1].overflow_col, None);
+        assert_eq!(vm.lines[2].overflow_col, Some(30));
+    }
+
+    #[test]
+    fn test_trailing_whitespace_span() {
+        let mut engine = engine("let a = 1;  \nlet b = 2;\n\t \n");
+        engine.buffer.selections.set_single_caret(0);
+        let vm = engine.view_model();
+        assert_eq!(vm.lines[0].trailing_whitespace, Some(SelectionSpan { start_col: 10, end_col: 12 }));
+        assert_eq!(vm.lines[1].trailing_whitespace, None);
+        assert_eq!(vm.lines[2].trailing_whitespace, Some(SelectionSpan { start_col: 0, end_col: 2 }));
+
+        engine.layout.whitespace.hide_trailing_on_current_line = true;
+        let vm = engine.view_model();
+        assert_eq!(vm.lines[0].trailing_whitespace, None);
+        assert!(vm.lines[2].trailing_whitespace.is_some());
+
+        engine = self::engine("let a = 1;  \r\nlet b = 2;\r\n");
+        engine.buffer.selections.set_single_caret(0);
+        let vm = engine.view_model();
+        assert_eq!(vm.lines[0].trailing_whitespace, Some(SelectionSpan { start_col: 10, end_col: 12 }));
+        assert_eq!(vm.lines[1].trailing_whitespace, None);
+    }
+
+    #[test]
+    fn test_indent_guides_continue_through_blank_lines() {
+        let text = "fn a() {\n    if x {\n        foo();\n\n\tbar();\n    }\n}\n";
+        let mut engine = engine(text);
+        let vm = engine.view_model();
+        let guides: Vec<Vec<usize>> = vm.lines.iter().map(|l| l.indent_guides.clone()).collect();
+        assert_eq!(
+            guides,
+            vec![vec![], vec![0], vec![0, 4], vec![0], vec![0], vec![0], vec![], vec![]]
+        );
+
+        engine.layout.tab_width = 8;
+        let vm = engine.view_model();
+        assert_eq!(vm.lines[3].indent_guides, vec![0, 4]);
+        assert_eq!(vm.lines[4].indent_guides, vec![0, 4]);
+
+        engine.viewport = Viewport { first_line: 3, max_lines: 1, width_cols: 120 };
+        let vm = engine.view_model();
+        assert_eq!(vm.lines[0].indent_guides, vec![0, 4]);
+    }
+
+    #[test]
+    fn test_insert_at_keeps_unrelated_carets() {
+        let mut engine = engine("alpha\nbeta\ngamma\n");
+        engine.buffer.selections = SelectionSet {
+            primary: Selection { anchor: 6, head: 10 },
+            secondary: vec![Selection { anchor: 2, head: 2 }, Selection { anchor: 13, head: 13 }],
+        };
+        engine.insert_at(6, "// ");
+        assert_eq!(engine.buffer.doc.to_string(), "alpha\n// beta\ngamma\n");
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 9, head: 13 });
+        assert_eq!(
+            engine.buffer.selections.secondary,
+            vec![Selection { anchor: 2, head: 2 }, Selection { anchor: 16, head: 16 }]
+        );
+
+        engine.delete_at(0, 3);
+        assert_eq!(engine.buffer.doc.to_string(), "ha\n// beta\ngamma\n");
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 6, head: 10 });
+        assert_eq!(engine.buffer.selections.secondary[0], Selection { anchor: 0, head: 0 });
+        assert_eq!(engine.buffer.selections.secondary.len(), 2);
+    }
+
+    #[test]
+    fn test_copy_paste_through_plugged_clipboard() {
+        #[derive(Debug, Default)]
+        struct RecordingClipboard {
+            writes: std::sync::Mutex<Vec<String>>,
+        }
+        impl Clipboard for RecordingClipboard {
+            fn get(&self) -> String {
+                self.writes.lock().unwrap().last().cloned().unwrap_or_default()
+            }
+            fn set(&self, text: &str) {
+                self.writes.lock().unwrap().push(text.to_string());
+            }
+        }
+
+        let clipboard = Arc::new(RecordingClipboard::default());
+        let mut engine = engine("hello world");
+        engine.set_clipboard(clipboard.clone());
+        engine.buffer.selections.primary = Selection { anchor: 0, head: 5 };
+        engine.apply_key_action(KeyAction::Copy);
+        engine.buffer.selections.set_single_caret(11);
+        engine.apply_key_action(KeyAction::Paste);
+        assert_eq!(engine.buffer.doc.to_string(), "hello worldhello");
+        assert_eq!(*clipboard.writes.lock().unwrap(), vec!["hello".to_string()]);
+    }
+
+    #[test]
+    fn test_paste_skips_auto_indent() {
+        let mut engine = engine("fn main() {\n    \n}\n");
+        engine.edit.auto_indent = true;
+        engine.buffer.selections.set_single_caret(16);
+        engine.apply_key_action(KeyAction::Newline);
+        assert_eq!(engine.buffer.doc.to_string(), "fn main() {\n    \n    \n}\n");
+
+        let snippet = "if ok {\n        run();\n    }";
+        engine.toggle_overwrite();
+        engine.paste(snippet);
+        assert_eq!(engine.buffer.doc.to_string(), format!("fn main() {{\n    \n    {snippet}\n}}\n"));
+    }
+
+    #[test]
+    fn test_toggle_comment_wraps_partial_selection() {
+        let mut engine = engine("let total = a + b * c;\nlet x = 1;\n");
+        engine.set_filename("main.rs");
+        engine.buffer.selections.primary = Selection { anchor: 16, head: 21 };
+        engine.apply_key_action(KeyAction::ToggleComment);
+        assert_eq!(engine.buffer.doc.line_text(0), "let total = a + /* b * c */;");
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 19, head: 24 });
+
+        engine.buffer.selections.primary = Selection { anchor: 16, head: 27 };
+        engine.apply_key_action(KeyAction::ToggleComment);
+        assert_eq!(engine.buffer.doc.line_text(0), "let total = a + b * c;");
+
+        engine.buffer.selections.primary = Selection { anchor: 0, head: 22 };
+        engine.apply_key_action(KeyAction::ToggleComment);
+        assert_eq!(engine.buffer.doc.line_text(0), "//let total = a + b * c;");
+    }
+
+    #[test]
+    fn test_replace_all_preview_matches_applied_edits() {
+        let text = "Color color; colorful COLOR\n";
+        let mut engine = engine(text);
+        let query = SearchQuery { needle: "color".to_string(), case_sensitive: false };
+        let preview = engine.replace_all_preview(&query, "hue", &ReplaceOptions::default());
+        assert_eq!(engine.buffer.doc.to_string(), text);
+        let starts: Vec<usize> = preview.iter().map(|(m, _)| m.start_char).collect();
+        assert_eq!(starts, vec![0, 6, 13, 22]);
+        assert!(preview.iter().all(|(_, r)| r == "hue"));
+
+        assert_eq!(engine.replace_all(&query, "hue", &ReplaceOptions::default()), preview.len());
+        assert_eq!(engine.buffer.doc.to_string(), "hue hue; hueful hue\n");
+        assert_eq!(engine.buffer.selections.primary.head, 19);
+    }
+
+    #[test]
+    fn test_replace_all_in_selection_scope() {
+        let mut engine = engine("let a = x;\nlet b = x;\nlet c = x;\nlet d = x;\n");
+        let query = SearchQuery { needle: "x".to_string(), case_sensitive: true };
+        let options = ReplaceOptions { scope: ReplaceScope::Selection, ..Default::default() };
+        engine.buffer.selections.set_single_caret(0);
+        assert_eq!(engine.replace_all(&query, "value", &options), 0);
+
+        engine.buffer.selections.primary = Selection { anchor: 0, head: 22 };
+        assert_eq!(engine.replace_all(&query, "value", &options), 2);
+        assert_eq!(
+            engine.buffer.doc.to_string(),
+            "let a = value;\nlet b = value;\nlet c = x;\nlet d = x;\n"
+        );
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 0, head: 30 });
+    }
+
+    #[test]
+    fn test_replace_all_preserving_case() {
+        let mut engine = engine("color Color COLOR cOLOR\n");
+        let query = SearchQuery { needle: "color".to_string(), case_sensitive: false };
+        let options = ReplaceOptions { preserve_case: true, ..Default::default() };
+        assert_eq!(engine.replace_all(&query, "colour", &options), 4);
+        assert_eq!(engine.buffer.doc.to_string(), "colour Colour COLOUR colour\n");
+    }
+
+    #[test]
+    fn test_view_model_into_matches_fresh_model() {
+        let mut engine = engine("fn main() {\n    let x = 1;   \n}\n");
+        engine.buffer.selections.primary = Selection { anchor: 4, head: 20 };
+        let mut reused = EditorViewModel::default();
+        engine.view_model_into(&mut reused);
+
+        engine.buffer.selections.set_single_caret(0);
+        engine.viewport.first_line = 1;
+        engine.view_model_into(&mut reused);
+        let fresh = engine.view_model();
+        assert_eq!(format!("{reused:?}"), format!("{fresh:?}"));
+        assert_eq!(reused.lines.len(), fresh.lines.len());
+    }
+
+    #[test]
+    fn test_overlapping_selections_merge_into_one_span() {
+        let mut engine = engine("abcdefghij\n");
+        engine.buffer.selections = SelectionSet {
+            primary: Selection { anchor: 1, head: 5 },
+            secondary: vec![Selection { anchor: 3, head: 8 }],
+        };
+        let vm = engine.view_model();
+        assert_eq!(vm.lines[0].selections, vec![SelectionSpan { start_col: 1, end_col: 8 }]);
+    }
+
+    #[test]
+    fn test_cursor_display_position_is_one_based() {
+        let mut engine = engine("alpha\nbeta\ngamma\n");
+        engine.buffer.selections.set_single_caret(2);
+        let down = KeyAction::Move { movement: Movement::Down, extend: false };
+        engine.apply_key_action(down);
+        engine.apply_key_action(down);
+        assert_eq!(engine.cursor_line_col(), LineCol { line: 2, col: 2 });
+        assert_eq!(engine.cursor_display_position(), (3, 3));
+    }
+
+    #[test]
+    fn test_from_buffer_keeps_content_and_version() {
+        let mut buffer = Buffer::new("hello\n");
+        buffer.selections.set_single_caret(5);
+        buffer.apply_text_to_selections(" world");
+        let version = buffer.doc.version();
+
+        let mut engine = EditorEngine::from_buffer(buffer);
+        assert_eq!(engine.buffer.doc.to_string(), "hello world\n");
+        assert_eq!(engine.buffer.doc.version(), version);
+        assert_eq!(engine.cursor_line_col(), LineCol { line: 0, col: 11 });
+        assert_eq!(engine.view_model().lines[0].text, "hello world");
+    }
+
+    #[test]
+    fn test_engine_from_edited_document_is_unmodified() {
+        let mut doc = Document::new("hello\n");
+        doc.replace_range(5, 5, " world");
+        let mut engine = EditorEngine::from_buffer(Buffer::from_document(doc));
+        assert!(!engine.buffer.is_modified());
+        engine.insert_text("!");
+        assert!(engine.buffer.is_modified());
+    }
+
+    #[test]
+    fn test_visual_line_count_with_soft_wrap() {
+        let text = format!("{}\n{}\n\n{}\n", "a".repeat(25), "b".repeat(10), "c".repeat(11));
+        let mut engine = engine(&text);
+        assert_eq!(engine.visual_line_count(), 5);
+
+        engine.layout.soft_wrap = true;
+        engine.viewport.width_cols = 10;
+        assert_eq!(engine.visual_line_count(), 3 + 1 + 1 + 2 + 1);
+
+        engine.buffer.selections.set_single_caret(0);
+        engine.insert_text(&"x".repeat(6));
+        assert_eq!(engine.visual_line_count(), 4 + 1 + 1 + 2 + 1);
+    }
+
+    #[test]
+    fn test_subword_movement_stops_inside_identifiers() {
+        let mut engine = engine("getHTTPResponseCode_v2\n");
+        engine.buffer.selections.set_single_caret(0);
+        let alt_right = KeyChord { code: KeyCode::Right, mods: KeyModifiers { alt: true, ..KeyModifiers::default() } };
+        let right = engine.keymap.resolve(alt_right).unwrap();
+        let mut stops = Vec::new();
+        for _ in 0..6 {
+            engine.apply_key_action(right);
+            stops.push(engine.buffer.selections.primary.head);
+        }
+        assert_eq!(stops, vec![3, 7, 15, 19, 21, 22]);
+
+        let left = KeyAction::Move { movement: Movement::SubWordLeft, extend: false };
+        let mut stops = Vec::new();
+        for _ in 0..6 {
+            engine.apply_key_action(left);
+            stops.push(engine.buffer.selections.primary.head);
+        }
+        assert_eq!(stops, vec![21, 20, 15, 7, 3, 0]);
+
+        engine.buffer.selections.set_single_caret(15);
+        engine.apply_key_action(KeyAction::DeleteSubWordBackward);
+        assert_eq!(engine.buffer.doc.to_string(), "getHTTPCode_v2\n");
+    }
+
+    #[test]
+    fn test_collapse_multiple_cursors() {
+        let mut engine = engine("one\ntwo\nthree\n");
+        let three_cursors = SelectionSet {
+            primary: Selection { anchor: 5, head: 5 },
+            secondary: vec![Selection { anchor: 1, head: 1 }, Selection { anchor: 10, head: 10 }],
+        };
+        engine.buffer.selections = three_cursors.clone();
+        assert_eq!(
+            engine.cursor_positions(),
+            vec![LineCol { line: 0, col: 1 }, LineCol { line: 1, col: 1 }, LineCol { line: 2, col: 2 }]
+        );
+
+        engine.collapse_to_primary();
+        assert_eq!(engine.cursor_positions(), vec![LineCol { line: 1, col: 1 }]);
+
+        engine.buffer.selections = three_cursors;
+        let escape = KeyChord { code: KeyCode::Escape, mods: KeyModifiers::default() };
+        engine.apply_key_action(engine.keymap.resolve(escape).unwrap());
+        assert_eq!(engine.buffer.selections, SelectionSet {
+            primary: Selection { anchor: 10, head: 10 },
+            secondary: Vec::new(),
+        });
+
+        engine.buffer.selections = SelectionSet {
+            primary: Selection { anchor: 12, head: 14 },
+            secondary: vec![Selection { anchor: 6, head: 6 }, Selection { anchor: 1, head: 1 }],
+        };
+        engine.collapse_to_last();
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 12, head: 14 });
+        assert!(engine.buffer.selections.secondary.is_empty());
+    }
+
+    #[test]
+    fn test_rotate_selections_moves_text_along() {
+        let mut engine = engine("red, green, blue\n");
+        engine.buffer.selections = SelectionSet {
+            primary: Selection { anchor: 0, head: 3 },
+            secondary: vec![Selection { anchor: 5, head: 10 }, Selection { anchor: 12, head: 16 }],
+        };
+        engine.rotate_selections(true);
+        assert_eq!(engine.buffer.doc.to_string(), "blue, red, green\n");
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 0, head: 4 });
+        assert_eq!(
+            engine.buffer.selections.secondary,
+            vec![Selection { anchor: 6, head: 9 }, Selection { anchor: 11, head: 16 }]
+        );
+
+        engine.rotate_selections(false);
+        assert_eq!(engine.buffer.doc.to_string(), "red, green, blue\n");
+        engine.buffer.undo();
+        assert_eq!(engine.buffer.doc.to_string(), "blue, red, green\n");
+    }
+
+    #[test]
+    fn test_surround_wraps_selections_and_words() {
+        let mut engine = engine("make this bold and that\n");
+        engine.buffer.selections = SelectionSet {
+            primary: Selection { anchor: 5, head: 9 },
+            secondary: vec![Selection { anchor: 21, head: 21 }],
+        };
+        engine.surround("**", "**");
+        assert_eq!(engine.buffer.doc.to_string(), "make **this** bold and **that**\n");
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 5, head: 13 });
+        assert_eq!(engine.buffer.selections.secondary, vec![Selection { anchor: 23, head: 31 }]);
+        engine.buffer.undo();
+        assert_eq!(engine.buffer.doc.to_string(), "make this bold and that\n");
+    }
+
+    #[test]
+    fn test_paste_reindents_and_trims_when_enabled() {
+        let mut engine = engine("fn f() {\n    \n}\n");
+        engine.edit.trim_trailing_on_paste = true;
+        engine.edit.reindent_on_paste = true;
+        engine.buffer.selections.set_single_caret(13);
+        engine.paste("        a();   \n            b();\n\n        c();\n");
+        assert_eq!(engine.buffer.doc.to_string(), "fn f() {\n    a();\n        b();\n\n    c();\n\n}\n");
+        engine.buffer.undo();
+        assert_eq!(engine.buffer.doc.to_string(), "fn f() {\n    \n}\n");
+
+        let mut engine = self::engine("x\n    y\n");
+        engine.edit.trim_trailing_on_paste = true;
+        engine.edit.reindent_on_paste = true;
+        engine.buffer.selections.set_single_caret(0);
+        engine.buffer.selections.secondary = vec![Selection { anchor: 6, head: 6 }];
+        let piece = "p();  \n  q();".to_string();
+        engine.copied_pieces = Some((format!("{piece}\n{piece}"), vec![piece.clone(), piece.clone()]));
+        engine.paste(&format!("{piece}\n{piece}"));
+        assert_eq!(engine.buffer.doc.to_string(), "p();\n  q();x\n    p();\n      q();y\n");
+    }
+
+    #[test]
+    fn test_ensure_cursor_visible_counts_wrapped_rows() {
+        let text = format!("{}\nshort\nshort\nshort\n", "x".repeat(40));
+        let mut engine = engine(&text);
+        engine.layout.soft_wrap = true;
+        engine.layout.scroll_off = 0;
+        engine.viewport = Viewport { first_line: 0, max_lines: 4, width_cols: 10 };
+        let caret = engine.buffer.doc.line_start_char(1);
+        engine.buffer.selections.set_single_caret(caret);
+        engine.ensure_cursor_visible();
+        assert_eq!(engine.viewport.first_line, 1);
+    }
+
+    #[test]
+    fn test_wrap_indent_offsets_continuation_rows() {
+        let mut engine = engine("    abcdefghijklmnop\n");
+        engine.layout.soft_wrap = true;
+        engine.layout.wrap_indent = WrapIndent::Inherit;
+        engine.viewport = Viewport { first_line: 0, max_lines: 10, width_cols: 10 };
+        engine.buffer.selections.primary = Selection { anchor: 8, head: 14 };
+        let vm = engine.view_model();
+        let rows: Vec<_> = vm.lines.iter().filter(|l| l.line_idx == 0).collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].text, "    abcdef");
+        assert_eq!((rows[0].indent_cols, rows[0].wrap_col_offset), (0, 0));
+        assert_eq!(rows[1].text, "ghijkl");
+        assert_eq!((rows[1].indent_cols, rows[1].wrap_col_offset), (4, 10));
+        assert_eq!(rows[2].wrap_col_offset, 16);
+        assert_eq!(rows[0].selections, vec![SelectionSpan { start_col: 8, end_col: 10 }]);
+        assert_eq!(rows[1].selections, vec![SelectionSpan { start_col: 0, end_col: 4 }]);
+        assert!(rows[2].selections.is_empty());
+    }
+
+    #[test]
+    fn test_caret_at_wrap_boundary_starts_next_row() {
+        let mut engine = engine("abcdefghijklmno\nxy");
+        engine.layout.soft_wrap = true;
+        engine.viewport = Viewport { first_line: 0, max_lines: 10, width_cols: 10 };
+        engine.buffer.selections.set_single_caret(10);
+        let vm = engine.view_model();
+        let rows_with_caret: Vec<_> = vm.lines.iter().filter(|l| !l.cursors.is_empty()).collect();
+        assert_eq!(rows_with_caret.len(), 1);
+        assert_eq!(rows_with_caret[0].wrap_col_offset, 10);
+        assert_eq!(rows_with_caret[0].cursors, vec![0]);
+        assert_eq!(engine.visual_row_for_col(0, 10), 1);
+
+        engine.buffer.selections.set_single_caret(15);
+        let vm = engine.view_model();
+        let rows_with_caret: Vec<_> = vm.lines.iter().filter(|l| !l.cursors.is_empty()).collect();
+        assert_eq!(rows_with_caret.len(), 1);
+        assert_eq!((rows_with_caret[0].line_idx, rows_with_caret[0].cursors.clone()), (0, vec![5]));
+    }
+
+    #[test]
+    fn test_snippet_tab_stops_follow_edits() {
+        let mut engine = engine("x");
+        engine.buffer.selections.set_single_caret(1);
+        engine.insert_snippet("for (${1:i} in ${2:list}) {\n    $0\n}");
+        assert_eq!(engine.buffer.doc.to_string(), "xfor (i in list) {\n    \n}");
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 6, head: 7 });
+
+        engine.insert_text("idx");
+        assert!(engine.next_tab_stop());
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 13, head: 17 });
+        assert!(engine.prev_tab_stop());
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 6, head: 9 });
+
+        engine.apply_key_action(KeyAction::Indent);
+        engine.apply_key_action(KeyAction::Indent);
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 25, head: 25 });
+        assert!(!engine.in_snippet());
+        assert!(engine.buffer.undo());
+        assert_eq!(engine.buffer.doc.to_string(), "xfor (i in list) {\n    \n}");
+    }
+
+    #[test]
+    fn test_linked_snippet_placeholders_mirror_typing() {
+        let mut engine = engine("");
+        engine.insert_snippet("let ${1:name} = ${2:f}($1, $1);$0");
+        assert_eq!(engine.buffer.doc.to_string(), "let name = f(name, name);");
+
+        engine.insert_text("x");
+        engine.insert_text("yz");
+        assert_eq!(engine.buffer.doc.to_string(), "let xyz = f(xyz, xyz);");
+        engine.apply_key_action(KeyAction::Backspace);
+        assert_eq!(engine.buffer.doc.to_string(), "let xy = f(xy, xy);");
+        assert!(engine.next_tab_stop());
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 9, head: 10 });
+
+        assert!(engine.buffer.undo());
+        assert_eq!(engine.buffer.doc.to_string(), "let xyz = f(xyz, xyz);");
+        assert!(engine.buffer.undo());
+        assert!(engine.buffer.undo());
+        assert_eq!(engine.buffer.doc.to_string(), "let name = f(name, name);");
+    }
+
+    #[test]
+    fn test_paste_key_mirrors_linked_snippet_placeholders() {
+        let mut engine = engine("");
+        engine.clipboard.set("xyz");
+        engine.insert_snippet("let ${1:name} = ${2:f}($1, $1);$0");
+        engine.apply_key_action(KeyAction::Paste);
+        assert_eq!(engine.buffer.doc.to_string(), "let xyz = f(xyz, xyz);");
+    }
+
+    #[test]
+    fn test_paste_cycle_steps_through_ring() {
+        let mut engine = engine("one two three\n");
+        for (start, end) in [(0, 3), (4, 7), (8, 13)] {
+            engine.buffer.selections.primary = Selection { anchor: start, head: end };
+            engine.apply_key_action(KeyAction::Copy);
+        }
+        engine.buffer.selections.set_single_caret(14);
+
+        engine.apply_key_action(KeyAction::PasteCycle);
+        assert_eq!(engine.buffer.doc.line_text(1), "three");
+        engine.apply_key_action(KeyAction::PasteCycle);
+        assert_eq!(engine.buffer.doc.line_text(1), "two");
+        engine.apply_key_action(KeyAction::PasteCycle);
+        assert_eq!(engine.buffer.doc.line_text(1), "one");
+        engine.apply_key_action(KeyAction::PasteCycle);
+        assert_eq!(engine.buffer.doc.line_text(1), "three");
+        assert_eq!(engine.buffer.selections.primary.head, 19);
+
+        engine.insert_text(" ");
+        engine.apply_key_action(KeyAction::PasteCycle);
+        assert_eq!(engine.buffer.doc.line_text(1), "three three");
+    }
+
+    #[test]
+    fn test_copy_joins_selections_in_document_order() {
+        let mut engine = engine("one two\nthree four\n");
+        engine.buffer.selections.primary = Selection { anchor: 8, head: 13 };
+        engine.buffer.selections.secondary = vec![Selection { anchor: 0, head: 3 }];
+        engine.apply_key_action(KeyAction::Copy);
+        assert_eq!(engine.clipboard().get(), "one\nthree");
+
+        engine.buffer.selections.primary = Selection { anchor: 8, head: 19 };
+        engine.buffer.selections.secondary = vec![Selection { anchor: 0, head: 8 }];
+        engine.apply_key_action(KeyAction::Copy);
+        assert_eq!(engine.clipboard().get(), "one two\nthree four\n");
+
+        engine.buffer.selections.primary = Selection { anchor: 7, head: 7 };
+        engine.buffer.selections.secondary = vec![Selection { anchor: 18, head: 18 }];
+        engine.apply_key_action(KeyAction::Paste);
+        assert_eq!(engine.buffer.doc.to_string(), "one twoone two\n\nthree fourthree four\n\n");
+    }
+
+    #[test]
+    fn test_paste_distributes_lines_across_carets() {
+        let mut engine = engine("a\nb\nc\n");
+        engine.buffer.selections.primary = Selection { anchor: 1, head: 1 };
+        engine.buffer.selections.secondary =
+            vec![Selection { anchor: 5, head: 5 }, Selection { anchor: 3, head: 3 }];
+        engine.clipboard().set("1\n2\n3\n");
+        engine.apply_key_action(KeyAction::Paste);
+        assert_eq!(engine.buffer.doc.to_string(), "a1\nb2\nc3\n");
+        assert_eq!(engine.buffer.selections.primary.head, 2);
+        assert_eq!(engine.buffer.selections.secondary, vec![
+            Selection { anchor: 5, head: 5 },
+            Selection { anchor: 8, head: 8 },
+        ]);
+
+        engine.clipboard().set("x\ny");
+        engine.apply_key_action(KeyAction::Paste);
+        assert_eq!(engine.buffer.doc.to_string(), "a1x\ny\nb2x\ny\nc3x\ny\n");
+    }
+
+    #[test]
+    fn test_highlight_cache_invalidates_only_edited_lines() {
+        let mut engine = engine("fn a() {}\nlet x = 1;\n// done\nlet y = 2;");
+        engine.set_filename("main.rs");
+        engine.view_model();
+        assert_eq!(engine.highlight_passes, 1);
+        let first_line = engine.highlight_cache.get(&0).cloned().unwrap();
+        assert!(!first_line.is_empty());
+
+        engine.view_model();
+        assert_eq!(engine.highlight_passes, 1);
+
+        let caret = engine.buffer.doc.line_start_char(1);
+        engine.buffer.selections.set_single_caret(caret);
+        engine.insert_text("mut ");
+        engine.view_model();
+        assert_eq!(engine.highlight_passes, 2);
+        assert_eq!(engine.highlight_cache.get(&0), Some(&first_line));
+        assert!(engine.highlight_cache.contains_key(&3));
+    }
+
+    #[test]
+    fn test_debounced_highlighting_recomputes_once_after_pause() {
+        let mut engine = engine("fn a() {}\nlet x = 1;\n// done\nlet y = 2;");
+        engine.set_filename("main.rs");
+        engine.layout.highlight_debounce = std::time::Duration::from_millis(40);
+        engine.view_model();
+        assert_eq!(engine.highlight_passes, 1);
+        let comment = engine.highlight_cache.get(&2).cloned().unwrap();
+
+        engine.buffer.selections.set_single_caret(0);
+        for text in ["// one\n", "// two\n", "x"] {
+            engine.insert_text(text);
+            engine.view_model();
+        }
+        assert_eq!(engine.highlight_passes, 1);
+        assert!(engine.highlight_pending());
+        assert_eq!(engine.highlight_cache.get(&4), Some(&comment));
+
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        engine.view_model();
+        engine.view_model();
+        assert_eq!(engine.highlight_passes, 2);
+        assert!(!engine.highlight_pending());
+    }
+
+    #[test]
+    fn test_overwrite_mode_replaces_chars() {
+        let mut engine = engine("hello\nworld");
+        engine.apply_key_action(KeyAction::ToggleOverwrite);
+        assert!(engine.is_overwrite());
+        engine.buffer.selections.set_single_caret(3);
+        engine.insert_text("p");
+        engine.insert_text("!");
+        engine.insert_text("?");
+        assert_eq!(engine.buffer.doc.to_string(), "help!?\nworld");
+        engine.buffer.undo();
+        assert_eq!(engine.buffer.doc.to_string(), "help!\nworld");
+        engine.insert_text("\n");
+        assert_eq!(engine.buffer.doc.line_text(0), "help!");
+        assert_eq!(engine.buffer.doc.len_lines(), 3);
+    }
+}
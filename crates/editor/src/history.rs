@@ -23,6 +23,8 @@ pub enum TransactionKind {
     Other,
 }
 
+/// A group of edits undone and redone together. Edits are applied in list order,
+/// each in the coordinates left by the ones before it.
 #[derive(Debug, Clone)]
 pub struct Transaction {
     pub kind: TransactionKind,
@@ -49,23 +51,19 @@ impl History {
     }
 
     pub fn push(&mut self, tx: Transaction, allow_coalesce_insert: bool) {
-        if allow_coalesce_insert {
-            if tx.kind == TransactionKind::Insert {
-                if let Some(prev) = self.undo.last_mut() {
-                    if prev.kind == TransactionKind::Insert {
-                        if prev.edits.len() == 1 && tx.edits.len() == 1 {
-                            let prev_edit = &mut prev.edits[0];
-                            let new_edit = &tx.edits[0];
-                            if prev_edit.deleted.is_empty()
-                                && new_edit.deleted.is_empty()
-                                && prev_edit.start_char + prev_edit.inserted_len_chars()
-                                    == new_edit.start_char
-                            {
-                                prev_edit.inserted.push_str(&new_edit.inserted);
-                                self.redo.clear();
-                                return;
-                            }
-                        }
+        if allow_coalesce_insert && tx.kind == TransactionKind::Insert {
+            if let Some(prev) = self.undo.last_mut() {
+                if prev.kind == TransactionKind::Insert && prev.edits.len() == 1 && tx.edits.len() == 1 {
+                    let prev_edit = &mut prev.edits[0];
+                    let new_edit = &tx.edits[0];
+                    if prev_edit.deleted.is_empty()
+                        && new_edit.deleted.is_empty()
+                        && prev_edit.start_char + prev_edit.inserted_len_chars()
+                            == new_edit.start_char
+                    {
+                        prev_edit.inserted.push_str(&new_edit.inserted);
+                        self.redo.clear();
+                        return;
                     }
                 }
             }
@@ -73,4 +71,16 @@ impl History {
         self.undo.push(tx);
         self.redo.clear();
     }
+
+    /// Fold the most recent transaction into the one before it so both undo as one step.
+    pub fn merge_last(&mut self) {
+        if self.undo.len() < 2 {
+            return;
+        }
+        if let Some(last) = self.undo.pop() {
+            if let Some(prev) = self.undo.last_mut() {
+                prev.edits.extend(last.edits);
+            }
+        }
+    }
 }
@@ -12,7 +12,11 @@ pub enum KeyCode {
     Down,
     Home,
     End,
+    PageUp,
+    PageDown,
     Tab,
+    Insert,
+    Escape,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
@@ -37,8 +41,16 @@ pub enum Movement {
     Down,
     WordLeft,
     WordRight,
+    /// Like `WordLeft`, but also stopping at camelCase humps, underscores and
+    /// letter/digit changes.
+    SubWordLeft,
+    SubWordRight,
     LineStart,
     LineEnd,
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -48,16 +60,24 @@ pub enum KeyAction {
     Delete,
     DeleteWordBackward,
     DeleteWordForward,
+    DeleteSubWordBackward,
+    DeleteSubWordForward,
     DeleteLine,
     Undo,
     Redo,
     Copy,
     Cut,
     Paste,
+    PasteCycle,
     Indent,
     Outdent,
     DuplicateLine,
     ToggleComment,
+    ToggleOverwrite,
+    TransposeChars,
+    TransposeWords,
+    /// Drop every selection but the last one in document order.
+    CollapseSelections,
     Move { movement: Movement, extend: bool },
 }
 
@@ -105,6 +125,14 @@ impl Keymap {
             KeyChord { code: KeyCode::Right, mods: KeyModifiers { ctrl: true, ..KeyModifiers::default() } },
             KeyAction::Move { movement: Movement::WordRight, extend: false },
         );
+        bindings.insert(
+            KeyChord { code: KeyCode::Left, mods: KeyModifiers { alt: true, ..KeyModifiers::default() } },
+            KeyAction::Move { movement: Movement::SubWordLeft, extend: false },
+        );
+        bindings.insert(
+            KeyChord { code: KeyCode::Right, mods: KeyModifiers { alt: true, ..KeyModifiers::default() } },
+            KeyAction::Move { movement: Movement::SubWordRight, extend: false },
+        );
         bindings.insert(
             KeyChord { code: KeyCode::Home, mods: KeyModifiers::default() },
             KeyAction::Move { movement: Movement::LineStart, extend: false },
@@ -113,6 +141,14 @@ impl Keymap {
             KeyChord { code: KeyCode::End, mods: KeyModifiers::default() },
             KeyAction::Move { movement: Movement::LineEnd, extend: false },
         );
+        bindings.insert(
+            KeyChord { code: KeyCode::PageUp, mods: KeyModifiers::default() },
+            KeyAction::Move { movement: Movement::PageUp, extend: false },
+        );
+        bindings.insert(
+            KeyChord { code: KeyCode::PageDown, mods: KeyModifiers::default() },
+            KeyAction::Move { movement: Movement::PageDown, extend: false },
+        );
         bindings.insert(
             KeyChord { code: KeyCode::Char('z'), mods: KeyModifiers { ctrl: true, ..KeyModifiers::default() } },
             KeyAction::Undo,
@@ -133,6 +169,21 @@ impl Keymap {
             KeyChord { code: KeyCode::Char('v'), mods: KeyModifiers { ctrl: true, ..KeyModifiers::default() } },
             KeyAction::Paste,
         );
+        bindings.insert(
+            KeyChord {
+                code: KeyCode::Char('v'),
+                mods: KeyModifiers { ctrl: true, shift: true, ..KeyModifiers::default() },
+            },
+            KeyAction::PasteCycle,
+        );
+        bindings.insert(
+            KeyChord { code: KeyCode::Char('t'), mods: KeyModifiers { ctrl: true, ..KeyModifiers::default() } },
+            KeyAction::TransposeChars,
+        );
+        bindings.insert(
+            KeyChord { code: KeyCode::Char('t'), mods: KeyModifiers { alt: true, ..KeyModifiers::default() } },
+            KeyAction::TransposeWords,
+        );
         bindings.insert(
             KeyChord { code: KeyCode::Tab, mods: KeyModifiers::default() },
             KeyAction::Indent,
@@ -141,6 +192,14 @@ impl Keymap {
             KeyChord { code: KeyCode::Tab, mods: KeyModifiers { shift: true, ..KeyModifiers::default() } },
             KeyAction::Outdent,
         );
+        bindings.insert(
+            KeyChord { code: KeyCode::Insert, mods: KeyModifiers::default() },
+            KeyAction::ToggleOverwrite,
+        );
+        bindings.insert(
+            KeyChord { code: KeyCode::Escape, mods: KeyModifiers::default() },
+            KeyAction::CollapseSelections,
+        );
         Self { bindings }
     }
 
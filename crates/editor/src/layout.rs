@@ -1,3 +1,6 @@
+use std::time::Duration;
+use unicode_width::UnicodeWidthChar;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct FontMetrics {
     pub char_width_px: f32,
@@ -16,32 +19,156 @@ impl Default for FontMetrics {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LayoutConfig {
     pub soft_wrap: bool,
+    /// Visual indent applied to continuation rows of soft-wrapped lines.
+    pub wrap_indent: WrapIndent,
     pub whitespace: WhitespaceConfig,
+    pub gutter: GutterConfig,
+    /// Minimum number of visual rows kept between the caret and the viewport edges.
+    pub scroll_off: usize,
+    /// Scroll the viewport to keep the primary caret visible after cursor movement.
+    pub follow_cursor: bool,
+    /// Columns a tab advances to the next multiple of.
+    pub tab_width: usize,
+    /// Visual columns to draw vertical guides at.
+    pub rulers: Vec<usize>,
+    /// Visual columns a line may use before it is reported as too long.
+    pub max_line_length: Option<usize>,
+    /// How long edits must pause before highlights are recomputed. Until then the old
+    /// spans are drawn, shifted to follow inserted and deleted lines. Zero recomputes
+    /// on every frame after an edit.
+    pub highlight_debounce: Duration,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            soft_wrap: false,
+            wrap_indent: WrapIndent::default(),
+            whitespace: WhitespaceConfig::default(),
+            gutter: GutterConfig::default(),
+            scroll_off: 2,
+            follow_cursor: true,
+            tab_width: 4,
+            rulers: Vec::new(),
+            max_line_length: None,
+            highlight_debounce: Duration::ZERO,
+        }
+    }
+}
+
+/// Char column of the first char in `text` that ends past `max_cols` visual columns,
+/// with tabs advancing to the next multiple of `tab_width`.
+pub(crate) fn overflow_char_col(text: &str, max_cols: usize, tab_width: usize) -> Option<usize> {
+    let mut visual = 0usize;
+    for (i, c) in text.chars().enumerate() {
+        visual = advance_col(visual, c, tab_width);
+        if visual > max_cols {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// Visual columns taken by the leading spaces and tabs of `text`, or `None` if the
+/// line is blank.
+pub(crate) fn leading_visual_cols(text: &str, tab_width: usize) -> Option<usize> {
+    let mut visual = 0usize;
+    for c in text.chars() {
+        match c {
+            ' ' | '\t' => visual = advance_col(visual, c, tab_width),
+            c if c.is_whitespace() => {}
+            _ => return Some(visual),
+        }
+    }
+    None
+}
+
+fn advance_col(visual: usize, c: char, tab_width: usize) -> usize {
+    if c == '\t' {
+        let tab_width = tab_width.max(1);
+        (visual / tab_width + 1) * tab_width
+    } else {
+        visual + char_cols(c)
+    }
+}
+
+/// Approximate x of char column `col` in `text` for when no shaped line is available:
+/// each visual column is `char_width_px` wide, tabs expand to the next multiple of
+/// `tab_width` and full-width chars take two columns.
+pub fn column_to_x(text: &str, col: usize, char_width_px: f32, tab_width: usize) -> f32 {
+    let visual = text.chars().take(col).fold(0, |visual, c| advance_col(visual, c, tab_width));
+    visual as f32 * char_width_px
+}
+
+/// The part of the char columns `start_col..end_col` that falls on the row covering
+/// `seg_start..seg_start + seg_len`, relative to the row; `None` when they don't overlap.
+pub fn intersect_selection(
+    seg_start: usize,
+    seg_len: usize,
+    start_col: usize,
+    end_col: usize,
+) -> Option<SelectionSpan> {
+    let seg_end = seg_start + seg_len;
+    let start = start_col.clamp(seg_start, seg_end);
+    let end = end_col.clamp(seg_start, seg_end);
+    (start < end).then(|| SelectionSpan { start_col: start - seg_start, end_col: end - seg_start })
+}
+
+/// Sort `spans` and coalesce those that overlap or touch, so no column is covered twice.
+pub(crate) fn merge_selection_spans(spans: &mut Vec<SelectionSpan>) {
+    spans.sort_by_key(|span| span.start_col);
+    spans.dedup_by(|next, prev| {
+        if next.start_col > prev.end_col {
+            return false;
+        }
+        prev.end_col = prev.end_col.max(next.end_col);
+        true
+    });
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WrapIndent {
+    /// Indent continuation rows by a fixed number of columns.
+    Fixed(usize),
+    /// Indent continuation rows to the line's leading whitespace.
+    Inherit,
+}
+
+impl Default for WrapIndent {
+    fn default() -> Self {
+        WrapIndent::Fixed(0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct WhitespaceConfig {
     pub show_spaces: bool,
     pub show_tabs: bool,
     pub show_newlines: bool,
+    /// Leave trailing whitespace unreported on the primary caret's line, so it does not
+    /// flicker while typing.
+    pub hide_trailing_on_current_line: bool,
 }
 
-impl Default for WhitespaceConfig {
-    fn default() -> Self {
-        Self {
-            show_spaces: false,
-            show_tabs: false,
-            show_newlines: false,
-        }
-    }
+/// Line numbering in the gutter.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct GutterConfig {
+    /// Number lines by their distance from the current line.
+    pub relative: bool,
+    /// With relative numbering, show the current line's absolute number instead of `0`.
+    pub show_absolute_current: bool,
 }
 
-impl Default for LayoutConfig {
-    fn default() -> Self {
-        Self {
-            soft_wrap: false,
-            whitespace: WhitespaceConfig::default(),
-        }
+impl GutterConfig {
+    /// Append the label for `line_idx`, given the line holding the primary caret, to `out`.
+    pub fn write_label(&self, line_idx: usize, current_line: usize, out: &mut String) {
+        use std::fmt::Write;
+        let number = if !self.relative || (line_idx == current_line && self.show_absolute_current) {
+            line_idx + 1
+        } else {
+            line_idx.abs_diff(current_line)
+        };
+        let _ = write!(out, "{number}");
     }
 }
 
@@ -58,39 +185,168 @@ pub struct SelectionSpan {
     pub end_col: usize,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct VisualLine {
     pub line_idx: usize,
     pub y_px: f32,
     pub wrap_col_offset: usize,
+    /// Columns of hanging indent to draw before `text` (non-zero only on continuation rows).
+    pub indent_cols: usize,
     pub text: String,
     pub selections: Vec<SelectionSpan>,
     pub cursors: Vec<usize>,
     pub is_current_line: bool,
+    /// Line number shown in the gutter; empty on soft-wrap continuation rows.
+    pub gutter_label: String,
     pub shaped: Option<crate::text_shaping::ShapedLine>,
-    pub highlights: Vec<syntax::HighlightSpan>,
+    /// Highlight spans in char columns relative to `text`.
+    pub highlights: Vec<syntax::CharHighlightSpan>,
+    /// Diagnostics intersecting the row; empty ranges cover one column.
+    pub diagnostics: Vec<crate::diagnostics::DiagnosticSpan>,
+    /// Column from which the row's text is past `LayoutConfig::max_line_length`.
+    pub overflow_col: Option<usize>,
+    /// Columns of the row covered by the line's trailing spaces and tabs.
+    pub trailing_whitespace: Option<SelectionSpan>,
+    /// Visual columns of the indent guides to draw through the row.
+    pub indent_guides: Vec<usize>,
+}
+
+impl VisualLine {
+    /// X of char column `col` relative to the start of the row's text. Uses the shaped
+    /// line when there is one, otherwise falls back to `column_to_x`.
+    pub fn column_to_x(&self, col: usize, metrics: &FontMetrics, tab_width: usize) -> f32 {
+        match &self.shaped {
+            Some(shaped) => {
+                shaped.x_for_char(self.wrap_col_offset + col) - shaped.x_for_char(self.wrap_col_offset)
+            }
+            None => column_to_x(&self.text, col, metrics.char_width_px, tab_width),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct EditorViewModel {
     pub lines: Vec<VisualLine>,
     pub gutter_width_cols: usize,
 }
 
-pub fn split_by_cols(text: &str, max_cols: usize) -> Vec<String> {
-    if max_cols == 0 {
-        return vec![text.to_string()];
+impl EditorViewModel {
+    /// Render the visible rows as plain text for tests: the right-aligned gutter label
+    /// (blank on continuation rows), then the row with `|` at each
+    /// cursor and `[`/`]` around each selection span.
+    pub fn to_debug_string(&self) -> String {
+        let number_width = self.gutter_width_cols.saturating_sub(1);
+        let mut out = String::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            out.push_str(&format!("{:>number_width$} ", line.gutter_label));
+            out.push_str(&" ".repeat(line.indent_cols));
+            let chars: Vec<char> = line.text.chars().collect();
+            for col in 0..=chars.len() {
+                for span in &line.selections {
+                    if span.end_col == col {
+                        out.push(']');
+                    }
+                }
+                if line.cursors.contains(&col) {
+                    out.push('|');
+                }
+                for span in &line.selections {
+                    if span.start_col == col {
+                        out.push('[');
+                    }
+                }
+                if let Some(c) = chars.get(col) {
+                    out.push(*c);
+                }
+            }
+        }
+        out
     }
-    let chars: Vec<char> = text.chars().collect();
-    if chars.len() <= max_cols {
+}
+
+/// Columns `c` takes on screen: 2 for full-width chars such as CJK ideographs, 0 for
+/// combining marks and 1 otherwise.
+pub(crate) fn char_cols(c: char) -> usize {
+    UnicodeWidthChar::width(c).unwrap_or(1)
+}
+
+/// Split `text` into rows of at most `max_cols` columns, where every row after the first
+/// loses `indent` columns to the hanging indent. The indent is clamped so each row keeps
+/// at least one column of text. Full-width chars count as two columns; a row always
+/// takes at least one char, so joining the rows gives back `text`.
+pub fn split_by_cols(text: &str, max_cols: usize, indent: usize) -> Vec<String> {
+    if max_cols == 0 || text.chars().map(char_cols).sum::<usize>() <= max_cols {
         return vec![text.to_string()];
     }
+    let rest_cols = max_cols - indent.min(max_cols - 1);
     let mut out = Vec::new();
-    let mut i = 0usize;
-    while i < chars.len() {
-        let end = (i + max_cols).min(chars.len());
-        out.push(chars[i..end].iter().collect());
-        i = end;
+    let mut row = String::new();
+    let mut row_width = 0usize;
+    for c in text.chars() {
+        let cols = if out.is_empty() { max_cols } else { rest_cols };
+        let width = char_cols(c);
+        if !row.is_empty() && row_width + width > cols {
+            out.push(std::mem::take(&mut row));
+            row_width = 0;
+        }
+        row.push(c);
+        row_width += width;
+    }
+    if !row.is_empty() {
+        out.push(row);
     }
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_by_cols_counts_full_width_chars_twice() {
+        let text = "漢字仮名交じり文です";
+        let rows = split_by_cols(text, 10, 0);
+        assert_eq!(rows, vec!["漢字仮名交", "じり文です"]);
+        assert_eq!(rows.concat(), text);
+
+        let mixed = split_by_cols("ab漢字cd", 3, 0);
+        assert_eq!(mixed, vec!["ab", "漢", "字c", "d"]);
+    }
+
+    #[test]
+    fn test_column_to_x_without_shaping() {
+        let metrics = FontMetrics { char_width_px: 8.0, line_height_px: 16.0 };
+        let line = VisualLine { text: "\tab漢c".to_string(), ..Default::default() };
+        assert_eq!(line.column_to_x(0, &metrics, 4), 0.0);
+        assert_eq!(line.column_to_x(1, &metrics, 4), 32.0);
+        assert_eq!(line.column_to_x(3, &metrics, 4), 48.0);
+        assert_eq!(line.column_to_x(4, &metrics, 4), 64.0);
+    }
+
+    #[test]
+    fn test_intersect_selection() {
+        // Row covers columns 10..20.
+        assert_eq!(intersect_selection(10, 10, 2, 8), None);
+        assert_eq!(intersect_selection(10, 10, 20, 25), None);
+        assert_eq!(intersect_selection(10, 10, 15, 15), None);
+        assert_eq!(
+            intersect_selection(10, 10, 5, 14),
+            Some(SelectionSpan { start_col: 0, end_col: 4 })
+        );
+        assert_eq!(
+            intersect_selection(10, 10, 18, 30),
+            Some(SelectionSpan { start_col: 8, end_col: 10 })
+        );
+        assert_eq!(
+            intersect_selection(10, 10, 0, 40),
+            Some(SelectionSpan { start_col: 0, end_col: 10 })
+        );
+        assert_eq!(
+            intersect_selection(10, 10, 12, 17),
+            Some(SelectionSpan { start_col: 2, end_col: 7 })
+        );
+    }
+}
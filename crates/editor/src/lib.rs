@@ -1,24 +1,57 @@
+//! Text editing engine for the AI code editor.
+//!
+//! [`EditorEngine`] owns a [`Buffer`] (document, selections and undo history) and turns
+//! it into an [`EditorViewModel`] of visible rows for the UI to draw. Key input goes
+//! through a [`Keymap`]; search, snippets and the clipboard ring hang off the engine.
+//!
+//! ```
+//! use editor::{EditorEngine, LineCol, SearchQuery};
+//!
+//! let mut engine = EditorEngine::new("fn main() {}\n");
+//! engine.insert_text("// entry\n");
+//! assert_eq!(engine.buffer.doc.to_string(), "// entry\nfn main() {}\n");
+//! assert_eq!(engine.cursor_line_col(), LineCol { line: 1, col: 0 });
+//!
+//! let query = SearchQuery { needle: "main".to_string(), case_sensitive: true };
+//! assert_eq!(engine.find_all(&query).len(), 1);
+//!
+//! let model = engine.view_model();
+//! assert_eq!(model.lines[1].text, "fn main() {}");
+//! ```
+
 mod buffer;
+mod clipboard;
+mod diagnostics;
 mod document;
+mod edit_config;
 mod engine;
 mod history;
 mod keymap;
 mod layout;
+mod minimap;
 mod search;
+mod search_session;
 mod selection;
+mod snippet;
 mod text_shaping;
 
-pub use buffer::{Buffer, EditImpact, ReplaceRange};
+pub use buffer::{ApplyError, Buffer, EditImpact, ReplaceRange};
+pub use clipboard::{Clipboard, ClipboardRing, MemoryClipboard, DEFAULT_CLIPBOARD_RING_CAPACITY};
+pub use diagnostics::{Diagnostic, DiagnosticSpan, Severity};
 pub use document::{Document, DocumentSnapshot};
-pub use engine::EditorEngine;
+pub use edit_config::EditConfig;
+pub use engine::{EditorEngine, InlineSuggestion};
 pub use history::{Edit, History, Transaction, TransactionKind};
 pub use keymap::{KeyAction, KeyChord, KeyCode, KeyModifiers, Keymap, Movement};
 pub use layout::{
-    EditorViewModel, FontMetrics, LayoutConfig, SelectionSpan, VisualLine, Viewport,
-    WhitespaceConfig,
+    column_to_x, intersect_selection, split_by_cols, EditorViewModel, FontMetrics, GutterConfig,
+    LayoutConfig, SelectionSpan, VisualLine, Viewport, WhitespaceConfig, WrapIndent,
 };
-pub use search::{SearchDirection, SearchMatch, SearchQuery};
+pub use minimap::MinimapRow;
+pub use search::{ReplaceOptions, ReplaceScope, SearchDirection, SearchMatch, SearchQuery};
+pub use search_session::SearchSession;
 pub use selection::{Cursor, LineCol, Selection, SelectionSet};
+pub use snippet::{Snippet, TabStop};
 pub use text_shaping::{ShapedGlyph, ShapedLine, TextShaper};
 
-pub use syntax::{HighlightSpan, LanguageRegistry, SyntaxHighlighter, TokenType};
+pub use syntax::{CharHighlightSpan, HighlightSpan, LanguageRegistry, SyntaxHighlighter, TokenType};
@@ -0,0 +1,29 @@
+use syntax::TokenType;
+
+/// One row of a minimap, summarizing a run of document lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinimapRow {
+    /// First line the row covers.
+    pub first_line: usize,
+    /// Line after the last one the row covers.
+    pub end_line: usize,
+    /// Average count of non-whitespace chars per line.
+    pub density: f32,
+    /// Average count of leading whitespace chars per non-blank line.
+    pub indent: f32,
+    /// Token type covering the most highlighted columns, when highlights are cached.
+    pub dominant_token: Option<TokenType>,
+}
+
+/// Lines `first..end` covered by `row` when `line_count` lines are spread over `rows`
+/// rows. With fewer lines than rows each row holds one line and the rest are empty.
+pub(crate) fn bucket_lines(row: usize, rows: usize, line_count: usize) -> (usize, usize) {
+    if rows == 0 {
+        return (0, 0);
+    }
+    if line_count <= rows {
+        let line = row.min(line_count);
+        return (line, (row + 1).min(line_count));
+    }
+    (row * line_count / rows, (row + 1) * line_count / rows)
+}
@@ -1,3 +1,5 @@
+use std::collections::VecDeque;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum SearchDirection {
     Forward,
@@ -10,23 +12,114 @@ pub struct SearchQuery {
     pub case_sensitive: bool,
 }
 
+/// Which part of the document a replace may touch.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ReplaceScope {
+    #[default]
+    Document,
+    /// Only matches lying entirely inside one of the current selections.
+    Selection,
+}
+
+/// How `EditorEngine::replace_all` applies a replacement.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ReplaceOptions {
+    pub scope: ReplaceScope,
+    /// Give a single-word replacement the case pattern of the word it replaces.
+    pub preserve_case: bool,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct SearchMatch {
     pub start_char: usize,
     pub end_char: usize,
 }
 
+/// `replacement` with the case pattern of `matched` (all-lower, all-upper or title case)
+/// when both are single words. Anything else is returned unchanged.
+pub(crate) fn match_case(matched: &str, replacement: &str) -> String {
+    let is_word = |s: &str| !s.is_empty() && !s.chars().any(char::is_whitespace);
+    if !is_word(matched) || !is_word(replacement) || !matched.chars().any(char::is_alphabetic) {
+        return replacement.to_string();
+    }
+    if matched == matched.to_lowercase() {
+        return replacement.to_lowercase();
+    }
+    if matched == matched.to_uppercase() {
+        return replacement.to_uppercase();
+    }
+    let mut chars = matched.chars();
+    let first = chars.next().unwrap_or_default();
+    let rest = chars.as_str();
+    if first.is_uppercase() && rest == rest.to_lowercase() {
+        let mut chars = replacement.chars();
+        let first = chars.next().map(|c| c.to_uppercase().to_string()).unwrap_or_default();
+        return first + &chars.as_str().to_lowercase();
+    }
+    replacement.to_string()
+}
+
 pub fn byte_to_char_idx(s: &str, byte_idx: usize) -> usize {
     s[..byte_idx.min(s.len())].chars().count()
 }
 
 pub fn char_to_byte_idx(s: &str, char_idx: usize) -> usize {
-    let mut cur = 0usize;
-    for (b, _) in s.char_indices() {
-        if cur == char_idx {
-            return b;
+    s.char_indices().nth(char_idx).map(|(b, _)| b).unwrap_or(s.len())
+}
+
+/// Lazy, non-overlapping search over a stream of chars.
+///
+/// Only a window of `needle.len()` chars is buffered, so matches that straddle
+/// rope chunk boundaries are found without copying the document.
+pub struct SearchIter<I> {
+    chars: I,
+    needle: Vec<char>,
+    case_sensitive: bool,
+    window: VecDeque<char>,
+    pos: usize,
+}
+
+impl<I: Iterator<Item = char>> SearchIter<I> {
+    pub fn new(chars: I, needle: &str, case_sensitive: bool) -> Self {
+        let needle: Vec<char> = needle.chars().collect();
+        Self {
+            chars,
+            window: VecDeque::with_capacity(needle.len()),
+            needle,
+            case_sensitive,
+            pos: 0,
+        }
+    }
+
+    fn window_matches(&self) -> bool {
+        self.window.iter().zip(self.needle.iter()).all(|(a, b)| {
+            a == b || (!self.case_sensitive && a.to_lowercase().eq(b.to_lowercase()))
+        })
+    }
+}
+
+impl<I: Iterator<Item = char>> Iterator for SearchIter<I> {
+    type Item = SearchMatch;
+
+    fn next(&mut self) -> Option<SearchMatch> {
+        if self.needle.is_empty() {
+            return None;
+        }
+        loop {
+            while self.window.len() < self.needle.len() {
+                self.window.push_back(self.chars.next()?);
+            }
+            if self.window_matches() {
+                let start_char = self.pos;
+                self.pos += self.needle.len();
+                self.window.clear();
+                return Some(SearchMatch {
+                    start_char,
+                    end_char: self.pos,
+                });
+            }
+            self.window.pop_front();
+            self.pos += 1;
         }
-        cur += 1;
     }
-    s.len()
 }
@@ -0,0 +1,134 @@
+use crate::engine::EditorEngine;
+use crate::search::{SearchMatch, SearchQuery};
+use crate::selection::{Selection, SelectionSet};
+
+/// Find-bar state: a query, its matches in the document and which one is current.
+///
+/// Matches are recomputed whenever the query changes or the document version differs
+/// from the one they were found in.
+#[derive(Debug, Clone)]
+pub struct SearchSession {
+    query: SearchQuery,
+    matches: Vec<SearchMatch>,
+    current: Option<usize>,
+    /// Document version `matches` were computed against; `None` before the first search.
+    version: Option<u64>,
+}
+
+impl SearchSession {
+    pub fn new(query: SearchQuery) -> Self {
+        Self { query, matches: Vec::new(), current: None, version: None }
+    }
+
+    pub fn query(&self) -> &SearchQuery {
+        &self.query
+    }
+
+    /// Replace the query. Matches are recomputed on the next `refresh`, `next` or `prev`.
+    pub fn set_query(&mut self, query: SearchQuery) {
+        if query != self.query {
+            self.query = query;
+            self.version = None;
+        }
+    }
+
+    /// Recompute the matches if the query or the document changed since the last search.
+    /// The current index is kept when it still names a match starting at the same char.
+    pub fn refresh(&mut self, engine: &EditorEngine) {
+        let version = engine.buffer.doc.version();
+        if self.version == Some(version) {
+            return;
+        }
+        let previous = self.current.and_then(|i| self.matches.get(i)).map(|m| m.start_char);
+        self.matches = engine.find_all(&self.query);
+        self.current = previous.and_then(|start| self.matches.iter().position(|m| m.start_char == start));
+        self.version = Some(version);
+    }
+
+    pub fn matches(&self) -> &[SearchMatch] {
+        &self.matches
+    }
+
+    pub fn match_count(&self) -> usize {
+        self.matches.len()
+    }
+
+    /// Index of the current match, if one has been selected.
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Select the match after the current one, or the first at or after the cursor when
+    /// none is current, wrapping past the end. Returns the new index.
+    pub fn next(&mut self, engine: &mut EditorEngine) -> Option<usize> {
+        self.refresh(engine);
+        if self.matches.is_empty() {
+            self.current = None;
+            return None;
+        }
+        let index = match self.current {
+            Some(i) => (i + 1) % self.matches.len(),
+            None => {
+                let cursor = engine.buffer.selections.primary.range().0;
+                self.matches.iter().position(|m| m.start_char >= cursor).unwrap_or(0)
+            }
+        };
+        self.select(engine, index);
+        Some(index)
+    }
+
+    /// Select the match before the current one, or the last before the cursor when none
+    /// is current, wrapping past the start. Returns the new index.
+    pub fn prev(&mut self, engine: &mut EditorEngine) -> Option<usize> {
+        self.refresh(engine);
+        if self.matches.is_empty() {
+            self.current = None;
+            return None;
+        }
+        let last = self.matches.len() - 1;
+        let index = match self.current {
+            Some(i) => i.checked_sub(1).unwrap_or(last),
+            None => {
+                let cursor = engine.buffer.selections.primary.range().0;
+                self.matches.iter().rposition(|m| m.start_char < cursor).unwrap_or(last)
+            }
+        };
+        self.select(engine, index);
+        Some(index)
+    }
+
+    fn select(&mut self, engine: &mut EditorEngine, index: usize) {
+        let m = self.matches[index];
+        engine.buffer.selections = SelectionSet {
+            primary: Selection { anchor: m.start_char, head: m.end_char },
+            secondary: Vec::new(),
+        };
+        engine.ensure_cursor_visible();
+        self.current = Some(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_steps_through_matches_and_wraps() {
+        let mut engine = EditorEngine::new("foo bar foo baz foo\n");
+        let mut session = SearchSession::new(SearchQuery { needle: "foo".to_string(), case_sensitive: true });
+
+        assert_eq!(session.next(&mut engine), Some(0));
+        assert_eq!(session.match_count(), 3);
+        assert_eq!(session.next(&mut engine), Some(1));
+        assert_eq!(engine.buffer.selections.primary, Selection { anchor: 8, head: 11 });
+        assert_eq!(session.next(&mut engine), Some(2));
+        assert_eq!(session.next(&mut engine), Some(0));
+        assert_eq!(session.prev(&mut engine), Some(2));
+
+        engine.buffer.selections.set_single_caret(0);
+        engine.insert_text("foo ");
+        session.refresh(&engine);
+        assert_eq!(session.match_count(), 4);
+        assert_eq!(session.current(), None);
+    }
+}
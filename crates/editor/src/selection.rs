@@ -56,6 +56,31 @@ impl SelectionSet {
         out
     }
 
+    pub fn primary_range(&self) -> (usize, usize) {
+        self.primary.range()
+    }
+
+    /// Selections sorted by position.
+    pub fn ordered(&self) -> Vec<Selection> {
+        let mut all = self.all_including_primary();
+        all.sort_by_key(|s| s.range());
+        all
+    }
+
+    /// Whether `char_idx` lies within any selection, boundaries included.
+    pub fn contains(&self, char_idx: usize) -> bool {
+        self.selection_at(char_idx).is_some()
+    }
+
+    /// Index into `ordered()` of the first selection whose range includes `char_idx`,
+    /// boundaries included.
+    pub fn selection_at(&self, char_idx: usize) -> Option<usize> {
+        self.ordered().iter().position(|s| {
+            let (start, end) = s.range();
+            start <= char_idx && char_idx <= end
+        })
+    }
+
     pub fn set_single_caret(&mut self, char_idx: usize) {
         self.primary = Selection {
             anchor: char_idx,
@@ -63,4 +88,69 @@ impl SelectionSet {
         };
         self.secondary.clear();
     }
+
+    /// Sort selections and merge any that overlap or touch, dropping carets that
+    /// fall inside (or on the edge of) another selection. The merged selection that
+    /// contains the old primary becomes the new primary.
+    pub fn normalize(&mut self) {
+        if self.secondary.is_empty() {
+            return;
+        }
+        let mut all: Vec<(Selection, bool)> = Vec::with_capacity(1 + self.secondary.len());
+        all.push((self.primary, true));
+        all.extend(self.secondary.iter().map(|s| (*s, false)));
+        all.sort_by_key(|(s, _)| s.range());
+
+        let mut merged: Vec<(Selection, bool)> = Vec::with_capacity(all.len());
+        for (sel, is_primary) in all {
+            let Some((last, last_primary)) = merged.last_mut() else {
+                merged.push((sel, is_primary));
+                continue;
+            };
+            let (last_start, last_end) = last.range();
+            let (start, end) = sel.range();
+            if start > last_end {
+                merged.push((sel, is_primary));
+                continue;
+            }
+            let end = end.max(last_end);
+            let backward = if is_primary { sel.head < sel.anchor } else { last.head < last.anchor };
+            *last = if backward {
+                Selection { anchor: end, head: last_start }
+            } else {
+                Selection { anchor: last_start, head: end }
+            };
+            *last_primary |= is_primary;
+        }
+
+        let primary_idx = merged.iter().position(|(_, p)| *p).unwrap_or(0);
+        self.primary = merged[primary_idx].0;
+        self.secondary = merged
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| *i != primary_idx)
+            .map(|(_, (s, _))| s)
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection_queries() {
+        let set = SelectionSet {
+            primary: Selection { anchor: 12, head: 8 },
+            secondary: vec![Selection { anchor: 3, head: 3 }],
+        };
+        assert_eq!(set.primary_range(), (8, 12));
+        assert!(set.contains(3));
+        assert_eq!(set.selection_at(3), Some(0));
+        assert!(!set.contains(4));
+        assert_eq!(set.selection_at(10), Some(1));
+        assert_eq!(set.selection_at(8), Some(1));
+        assert_eq!(set.selection_at(12), Some(1));
+        assert_eq!(set.selection_at(13), None);
+    }
 }
@@ -0,0 +1,252 @@
+/// A tab stop inside an expanded snippet, as char offsets into `Snippet::text`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TabStop {
+    pub index: usize,
+    pub start_char: usize,
+    pub end_char: usize,
+}
+
+/// A parsed snippet template: the literal text to insert plus its tab stops in
+/// document order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub text: String,
+    pub stops: Vec<TabStop>,
+}
+
+impl Snippet {
+    /// Parse `$n`, `${n}` and `${n:default}` placeholders. `\$`, `\}` and `\\` escape
+    /// literals; anything else that does not form a placeholder is kept as text.
+    /// A final `$0` stop is added at the end if the template has none.
+    pub fn parse(template: &str) -> Self {
+        let chars: Vec<char> = template.chars().collect();
+        let mut text = String::new();
+        let mut len = 0usize;
+        let mut stops: Vec<TabStop> = Vec::new();
+        let mut i = 0usize;
+        while i < chars.len() {
+            let c = chars[i];
+            if c == '\\' && matches!(chars.get(i + 1), Some('$' | '}' | '\\')) {
+                text.push(chars[i + 1]);
+                len += 1;
+                i += 2;
+                continue;
+            }
+            if c == '$' {
+                if let Some((index, mut default, next)) = parse_placeholder(&chars, i + 1) {
+                    // Later occurrences without a default mirror the first one's text.
+                    if default.is_empty() {
+                        if let Some(first) = stops.iter().find(|s| s.index == index) {
+                            let first_len = first.end_char - first.start_char;
+                            default = text.chars().skip(first.start_char).take(first_len).collect();
+                        }
+                    }
+                    stops.push(TabStop {
+                        index,
+                        start_char: len,
+                        end_char: len + default.chars().count(),
+                    });
+                    len += default.chars().count();
+                    text.push_str(&default);
+                    i = next;
+                    continue;
+                }
+            }
+            text.push(c);
+            len += 1;
+            i += 1;
+        }
+        if !stops.iter().any(|s| s.index == 0) {
+            stops.push(TabStop { index: 0, start_char: len, end_char: len });
+        }
+        Self { text, stops }
+    }
+
+    /// Distinct stop indices in the order they are visited: ascending, with `0` last.
+    pub fn visit_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = self.stops.iter().map(|s| s.index).filter(|i| *i != 0).collect();
+        order.sort_unstable();
+        order.dedup();
+        order.push(0);
+        order
+    }
+}
+
+/// Parse the part of a placeholder after `$`, returning its index, default text and
+/// the position just past it.
+fn parse_placeholder(chars: &[char], start: usize) -> Option<(usize, String, usize)> {
+    let braced = chars.get(start) == Some(&'{');
+    let mut i = if braced { start + 1 } else { start };
+    let digits_start = i;
+    while chars.get(i).is_some_and(|c| c.is_ascii_digit()) {
+        i += 1;
+    }
+    if i == digits_start {
+        return None;
+    }
+    let index: usize = chars[digits_start..i].iter().collect::<String>().parse().ok()?;
+    if !braced {
+        return Some((index, String::new(), i));
+    }
+    let mut default = String::new();
+    if chars.get(i) == Some(&':') {
+        i += 1;
+        loop {
+            match chars.get(i)? {
+                '\\' if matches!(chars.get(i + 1), Some('$' | '}' | '\\')) => {
+                    default.push(chars[i + 1]);
+                    i += 2;
+                }
+                '}' => break,
+                c => {
+                    default.push(*c);
+                    i += 1;
+                }
+            }
+        }
+    }
+    (chars.get(i) == Some(&'}')).then_some((index, default, i + 1))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct StopRange {
+    group: usize,
+    start: usize,
+    end: usize,
+}
+
+/// Tab stops of an inserted snippet, tracked in absolute char offsets while the
+/// user moves through them.
+#[derive(Debug, Clone)]
+pub(crate) struct SnippetSession {
+    /// Stop ranges in document order; `group` indexes the visit order.
+    ranges: Vec<StopRange>,
+    group_count: usize,
+    current: usize,
+}
+
+impl SnippetSession {
+    pub(crate) fn new(snippet: &Snippet, base: usize) -> Self {
+        let order = snippet.visit_order();
+        let ranges = snippet
+            .stops
+            .iter()
+            .map(|s| StopRange {
+                group: order.iter().position(|i| *i == s.index).unwrap_or(0),
+                start: base + s.start_char,
+                end: base + s.end_char,
+            })
+            .collect();
+        Self { ranges, group_count: order.len(), current: 0 }
+    }
+
+    pub(crate) fn is_final_stop(&self) -> bool {
+        self.current + 1 >= self.group_count
+    }
+
+    /// Range of the first occurrence of the current stop.
+    pub(crate) fn current_range(&self) -> (usize, usize) {
+        self.active_idx()
+            .map(|i| (self.ranges[i].start, self.ranges[i].end))
+            .unwrap_or((0, 0))
+    }
+
+    pub(crate) fn advance(&mut self, forward: bool) -> bool {
+        if forward && self.current + 1 < self.group_count {
+            self.current += 1;
+            true
+        } else if !forward && self.current > 0 {
+            self.current -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub(crate) fn contains(&self, char_idx: usize) -> bool {
+        self.ranges.iter().any(|r| r.start <= char_idx && char_idx <= r.end)
+    }
+
+    /// Account for an edit starting at `edit_start` that changed the document length by
+    /// `delta`. Returns false if the edit was not inside the current stop.
+    pub(crate) fn track_edit(&mut self, edit_start: usize, delta: isize) -> bool {
+        let Some(active) = self.active_idx() else {
+            return false;
+        };
+        let range = self.ranges[active];
+        if edit_start < range.start || edit_start > range.end {
+            return false;
+        }
+        let Some(end) = range.end.checked_add_signed(delta).filter(|e| *e >= range.start) else {
+            return false;
+        };
+        self.ranges[active].end = end;
+        for r in self.ranges[active + 1..].iter_mut() {
+            r.start = r.start.saturating_add_signed(delta);
+            r.end = r.end.saturating_add_signed(delta);
+        }
+        true
+    }
+
+    /// Ranges of the other occurrences of the current stop.
+    pub(crate) fn mirror_ranges(&self) -> Vec<(usize, usize)> {
+        let Some(active) = self.active_idx() else {
+            return Vec::new();
+        };
+        self.ranges[active + 1..]
+            .iter()
+            .filter(|r| r.group == self.current)
+            .map(|r| (r.start, r.end))
+            .collect()
+    }
+
+    /// Resize the mirrors of the current stop to `len` chars once they are replaced
+    /// with its text, shifting the stops that follow them.
+    pub(crate) fn resize_mirrors(&mut self, len: usize) {
+        let Some(active) = self.active_idx() else {
+            return;
+        };
+        let mut shift = 0isize;
+        for r in self.ranges[active + 1..].iter_mut() {
+            r.start = r.start.saturating_add_signed(shift);
+            r.end = r.end.saturating_add_signed(shift);
+            if r.group == self.current {
+                shift += len as isize - (r.end - r.start) as isize;
+                r.end = r.start + len;
+            }
+        }
+    }
+
+    fn active_idx(&self) -> Option<usize> {
+        self.ranges.iter().position(|r| r.group == self.current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tab_stops() {
+        let snippet = Snippet::parse("for (${1:i} in ${2:list}) {\n    $0\n}");
+        assert_eq!(snippet.text, "for (i in list) {\n    \n}");
+        assert_eq!(
+            snippet.stops,
+            vec![
+                TabStop { index: 1, start_char: 5, end_char: 6 },
+                TabStop { index: 2, start_char: 10, end_char: 14 },
+                TabStop { index: 0, start_char: 22, end_char: 22 },
+            ]
+        );
+        assert_eq!(snippet.visit_order(), vec![1, 2, 0]);
+
+        let snippet = Snippet::parse("$2 \\$1 ${1} ${x}");
+        assert_eq!(snippet.text, " $1  ${x}");
+        assert_eq!(snippet.visit_order(), vec![1, 2, 0]);
+        assert_eq!(snippet.stops.last(), Some(&TabStop { index: 0, start_char: 9, end_char: 9 }));
+
+        let snippet = Snippet::parse("let ${1:name} = $1;");
+        assert_eq!(snippet.text, "let name = name;");
+        assert_eq!(snippet.stops[1], TabStop { index: 1, start_char: 11, end_char: 15 });
+    }
+}
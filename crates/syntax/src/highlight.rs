@@ -9,17 +9,56 @@ pub struct HighlightSpan {
     pub token_type: TokenType,
 }
 
+/// A highlight span measured in char columns relative to the start of its line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharHighlightSpan {
+    pub start_col: usize,
+    pub end_col: usize,
+    pub token_type: TokenType,
+}
+
 #[derive(Debug, Clone)]
 pub struct LineHighlights {
     pub line_idx: usize,
     pub spans: Vec<HighlightSpan>,
 }
 
+impl LineHighlights {
+    /// Convert the line-relative byte spans into char columns of `line_text`.
+    pub fn to_char_spans(&self, line_text: &str) -> Vec<CharHighlightSpan> {
+        self.spans
+            .iter()
+            .map(|span| CharHighlightSpan {
+                start_col: byte_to_char_col(line_text, span.start_byte),
+                end_col: byte_to_char_col(line_text, span.end_byte),
+                token_type: span.token_type,
+            })
+            .filter(|span| span.start_col < span.end_col)
+            .collect()
+    }
+}
+
+fn byte_to_char_col(line_text: &str, byte_idx: usize) -> usize {
+    let mut byte_idx = byte_idx.min(line_text.len());
+    while !line_text.is_char_boundary(byte_idx) {
+        byte_idx -= 1;
+    }
+    line_text[..byte_idx].chars().count()
+}
+
+/// Documents larger than this are not highlighted by default.
+pub const DEFAULT_MAX_HIGHLIGHT_BYTES: usize = 1024 * 1024;
+/// Lines longer than this get no highlight spans by default.
+pub const DEFAULT_MAX_LINE_HIGHLIGHT_BYTES: usize = 4096;
+
 pub struct SyntaxHighlighter {
     parser: IncrementalParser,
     highlighter: Highlighter,
     current_config: Option<HighlightConfiguration>,
     highlight_names: Vec<String>,
+    max_text_bytes: usize,
+    max_line_bytes: usize,
+    definition_kinds: &'static [&'static str],
 }
 
 impl Clone for SyntaxHighlighter {
@@ -29,6 +68,9 @@ impl Clone for SyntaxHighlighter {
             highlighter: Highlighter::new(),
             current_config: None,
             highlight_names: self.highlight_names.clone(),
+            max_text_bytes: self.max_text_bytes,
+            max_line_bytes: self.max_line_bytes,
+            definition_kinds: &[],
         }
     }
 }
@@ -61,6 +103,9 @@ impl SyntaxHighlighter {
             highlighter: Highlighter::new(),
             current_config: None,
             highlight_names,
+            max_text_bytes: DEFAULT_MAX_HIGHLIGHT_BYTES,
+            max_line_bytes: DEFAULT_MAX_LINE_HIGHLIGHT_BYTES,
+            definition_kinds: &[],
         }
     }
 
@@ -76,15 +121,32 @@ impl SyntaxHighlighter {
         .map_err(|e| format!("Failed to create highlight config: {}", e))?;
         highlight_config.configure(&self.highlight_names);
         self.current_config = Some(highlight_config);
+        self.definition_kinds = config.definition_kinds;
         Ok(())
     }
 
+    /// Set the document size (in bytes) above which parsing and highlighting are skipped.
+    pub fn set_max_text_bytes(&mut self, max_bytes: usize) {
+        self.max_text_bytes = max_bytes;
+    }
+
+    /// Set the line length (in bytes) above which a line gets no highlight spans.
+    pub fn set_max_line_bytes(&mut self, max_bytes: usize) {
+        self.max_line_bytes = max_bytes;
+    }
+
     pub fn parse(&mut self, text: &str) -> Option<()> {
+        if text.len() > self.max_text_bytes {
+            return None;
+        }
         self.parser.parse(text)?;
         Some(())
     }
 
     pub fn highlight_text(&mut self, text: &str) -> Result<Vec<HighlightSpan>, String> {
+        if text.len() > self.max_text_bytes {
+            return Ok(Vec::new());
+        }
         let config = self
             .current_config
             .as_ref()
@@ -134,7 +196,13 @@ impl SyntaxHighlighter {
             if line_idx >= line_range.start && line_idx < line_range.end {
                 let line_start = byte_offset;
                 let line_end = byte_offset + line_text.len();
-                let line_spans: Vec<HighlightSpan> = all_spans
+                let candidates = if line_text.len() > self.max_line_bytes {
+                    &all_spans[..0]
+                } else {
+                    let first_after = all_spans.partition_point(|span| span.start_byte < line_end);
+                    &all_spans[..first_after]
+                };
+                let line_spans: Vec<HighlightSpan> = candidates
                     .iter()
                     .filter(|span| span.start_byte < line_end && span.end_byte > line_start)
                     .map(|span| HighlightSpan {
@@ -153,22 +221,27 @@ impl SyntaxHighlighter {
         Ok(result)
     }
 
+    /// Byte range of the innermost definition (a function, type, impl, class, ...)
+    /// containing `byte_offset`, parsing `text` from scratch. Definitions are the
+    /// language's `LanguageConfig::definition_kinds`.
+    pub fn enclosing_definition(&mut self, text: &str, byte_offset: usize) -> Option<std::ops::Range<usize>> {
+        if text.len() > self.max_text_bytes {
+            return None;
+        }
+        self.parser.reset();
+        let tree = self.parser.parse(text)?;
+        let mut node = tree.root_node().descendant_for_byte_range(byte_offset, byte_offset)?;
+        loop {
+            if self.definition_kinds.contains(&node.kind()) {
+                return Some(node.byte_range());
+            }
+            node = node.parent()?;
+        }
+    }
+
     fn map_index_to_token_type(highlight_names: &[String], idx: usize) -> Option<TokenType> {
         let name = highlight_names.get(idx)?;
-        match name.as_str() {
-            "keyword" => Some(TokenType::Keyword),
-            "function" => Some(TokenType::Function),
-            "type" => Some(TokenType::Type),
-            "string" => Some(TokenType::String),
-            "comment" => Some(TokenType::Comment),
-            "number" => Some(TokenType::Number),
-            "operator" => Some(TokenType::Operator),
-            "variable" => Some(TokenType::Variable),
-            "punctuation" => Some(TokenType::Punctuation),
-            "property" => Some(TokenType::Property),
-            "constant" => Some(TokenType::Constant),
-            _ => Some(TokenType::None),
-        }
+        Some(TokenType::from_name(name).unwrap_or(TokenType::None))
     }
 }
 
@@ -177,3 +250,103 @@ impl Default for SyntaxHighlighter {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language::LanguageRegistry;
+
+    #[test]
+    fn test_builtin_queries_highlight_keywords_and_constants() {
+        let registry = LanguageRegistry::new();
+        let token_at = |name: &str, text: &str, word: &str| {
+            let mut highlighter = SyntaxHighlighter::new();
+            highlighter.set_language(registry.get_language(name).unwrap()).unwrap();
+            let start = text.find(word).unwrap();
+            highlighter
+                .highlight_text(text)
+                .unwrap()
+                .into_iter()
+                .find(|s| s.start_byte <= start && start + word.len() <= s.end_byte)
+                .map(|s| s.token_type)
+        };
+        assert_eq!(token_at("rust", "fn f(&mut self) {}", "mut"), Some(TokenType::Keyword));
+        assert_eq!(token_at("rust", "fn f(&mut self) {}", "self"), Some(TokenType::Keyword));
+        assert_eq!(token_at("javascript", "let a = null;", "null"), Some(TokenType::Constant));
+        assert_eq!(token_at("javascript", "a?.b", "?."), Some(TokenType::Operator));
+    }
+
+    #[test]
+    fn test_char_spans_after_multibyte_text() {
+        let registry = LanguageRegistry::new();
+        let mut highlighter = SyntaxHighlighter::new();
+        highlighter
+            .set_language(registry.get_language("rust").unwrap())
+            .unwrap();
+        let line = "let s = \"café\"; // note";
+        let lines = highlighter.highlight_lines(line, 0..1).unwrap();
+        let spans = lines[0].to_char_spans(line);
+        let comment = spans
+            .iter()
+            .find(|s| s.token_type == TokenType::Comment)
+            .unwrap();
+        assert_eq!((comment.start_col, comment.end_col), (16, 23));
+        let string = spans
+            .iter()
+            .find(|s| s.token_type == TokenType::String)
+            .unwrap();
+        assert_eq!((string.start_col, string.end_col), (8, 14));
+    }
+
+    #[test]
+    fn test_enclosing_definition_skips_statements() {
+        let registry = LanguageRegistry::new();
+        let mut highlighter = SyntaxHighlighter::new();
+        highlighter
+            .set_language(registry.get_language("rust").unwrap())
+            .unwrap();
+        let text = "use std::fmt;\n\nfn main() {\n    let x = 1;\n}\n";
+        let caret = text.find("x =").unwrap();
+        let range = highlighter.enclosing_definition(text, caret).unwrap();
+        assert_eq!(&text[range], "fn main() {\n    let x = 1;\n}");
+        assert_eq!(highlighter.enclosing_definition(text, 5), None);
+
+        highlighter
+            .set_language(registry.get_language("javascript").unwrap())
+            .unwrap();
+        let text = "class A {\n  run() {\n    const y = 2;\n  }\n}\n";
+        let caret = text.find("y =").unwrap();
+        let range = highlighter.enclosing_definition(text, caret).unwrap();
+        assert_eq!(&text[range], "run() {\n    const y = 2;\n  }");
+    }
+
+    #[test]
+    fn test_oversized_text_is_not_highlighted() {
+        let registry = LanguageRegistry::new();
+        let mut highlighter = SyntaxHighlighter::new();
+        highlighter
+            .set_language(registry.get_language("javascript").unwrap())
+            .unwrap();
+        highlighter.set_max_text_bytes(64 * 1024);
+        let minified = "var a=1;".repeat(256 * 1024);
+        let start = std::time::Instant::now();
+        assert!(highlighter.highlight_text(&minified).unwrap().is_empty());
+        let lines = highlighter.highlight_lines(&minified, 0..1).unwrap();
+        assert!(lines[0].spans.is_empty());
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+
+        highlighter.set_max_line_bytes(8);
+        let lines = highlighter.highlight_lines("var a = 1;\nvar b;", 0..2).unwrap();
+        assert!(lines[0].spans.is_empty());
+        assert!(!lines[1].spans.is_empty());
+    }
+
+    #[test]
+    fn test_builtin_queries_compile() {
+        let registry = LanguageRegistry::new();
+        for name in ["rust", "javascript"] {
+            let mut highlighter = SyntaxHighlighter::new();
+            highlighter.set_language(registry.get_language(name).unwrap()).unwrap();
+        }
+    }
+}
@@ -16,18 +16,87 @@ pub enum TokenType {
     None,
 }
 
+impl TokenType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "keyword" => Some(TokenType::Keyword),
+            "function" => Some(TokenType::Function),
+            "type" => Some(TokenType::Type),
+            "string" => Some(TokenType::String),
+            "comment" => Some(TokenType::Comment),
+            "number" => Some(TokenType::Number),
+            "operator" => Some(TokenType::Operator),
+            "variable" => Some(TokenType::Variable),
+            "punctuation" => Some(TokenType::Punctuation),
+            "property" => Some(TokenType::Property),
+            "constant" => Some(TokenType::Constant),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct LanguageConfig {
     pub name: &'static str,
     pub language: tree_sitter::Language,
     pub highlight_query: &'static str,
     pub extensions: &'static [&'static str],
+    /// Prefix that comments out the rest of a line, if the language has one.
+    pub line_comment: Option<&'static str>,
+    /// Delimiters of a block comment, if the language has them.
+    pub block_comment: Option<(&'static str, &'static str)>,
+    /// Node kinds that count as definitions (functions, types, impls, classes, ...)
+    /// for `SyntaxHighlighter::enclosing_definition`.
+    pub definition_kinds: &'static [&'static str],
+}
+
+/// A language found by `LanguageRegistry::detect_language_from`.
+#[derive(Clone, Copy)]
+pub struct DetectedLanguage<'a> {
+    pub name: &'static str,
+    /// Grammar and queries, if one is registered for the language (there is none for
+    /// e.g. `dockerfile` or `shell`).
+    pub config: Option<&'a LanguageConfig>,
 }
 
+/// Well-known file names that identify a language without an extension.
+const FILENAME_LANGUAGES: &[(&str, &str)] = &[
+    ("Dockerfile", "dockerfile"),
+    ("Containerfile", "dockerfile"),
+    ("Makefile", "make"),
+    ("makefile", "make"),
+    ("GNUmakefile", "make"),
+    ("CMakeLists.txt", "cmake"),
+    ("Rakefile", "ruby"),
+    ("Gemfile", "ruby"),
+    (".bashrc", "shell"),
+    (".bash_profile", "shell"),
+    (".profile", "shell"),
+    (".zshrc", "shell"),
+];
+
+/// Shebang interpreters and the language they imply.
+const SHEBANG_LANGUAGES: &[(&str, &str)] = &[
+    ("sh", "shell"),
+    ("bash", "shell"),
+    ("zsh", "shell"),
+    ("dash", "shell"),
+    ("python", "python"),
+    ("python2", "python"),
+    ("python3", "python"),
+    ("node", "javascript"),
+    ("nodejs", "javascript"),
+    ("deno", "javascript"),
+    ("ruby", "ruby"),
+    ("perl", "perl"),
+];
+
 #[derive(Clone)]
 pub struct LanguageRegistry {
     languages: HashMap<&'static str, LanguageConfig>,
     extension_map: HashMap<&'static str, &'static str>,
+    filename_map: HashMap<&'static str, &'static str>,
+    shebang_map: HashMap<&'static str, &'static str>,
 }
 
 impl std::fmt::Debug for LanguageRegistry {
@@ -43,6 +112,8 @@ impl LanguageRegistry {
         let mut registry = Self {
             languages: HashMap::new(),
             extension_map: HashMap::new(),
+            filename_map: FILENAME_LANGUAGES.iter().copied().collect(),
+            shebang_map: SHEBANG_LANGUAGES.iter().copied().collect(),
         };
         registry.register_builtin_languages();
         registry
@@ -54,12 +125,37 @@ impl LanguageRegistry {
             language: tree_sitter_rust::language(),
             highlight_query: include_str!("queries/rust.scm"),
             extensions: &["rs"],
+            line_comment: Some("//"),
+            block_comment: Some(("/*", "*/")),
+            definition_kinds: &[
+                "function_item",
+                "function_signature_item",
+                "struct_item",
+                "enum_item",
+                "union_item",
+                "trait_item",
+                "impl_item",
+                "mod_item",
+                "macro_definition",
+            ],
         });
         self.register(LanguageConfig {
             name: "javascript",
             language: tree_sitter_javascript::language(),
             highlight_query: include_str!("queries/javascript.scm"),
             extensions: &["js", "jsx", "mjs"],
+            line_comment: Some("//"),
+            block_comment: Some(("/*", "*/")),
+            definition_kinds: &[
+                "function_declaration",
+                "generator_function_declaration",
+                "function_expression",
+                "function",
+                "generator_function",
+                "class_declaration",
+                "class",
+                "method_definition",
+            ],
         });
     }
 
@@ -71,9 +167,50 @@ impl LanguageRegistry {
     }
 
     pub fn detect_language(&self, filename: &str) -> Option<&LanguageConfig> {
-        let extension = filename.rsplit('.').next()?;
-        let lang_name = self.extension_map.get(extension)?;
-        self.languages.get(lang_name)
+        self.detect_language_from(filename, None)?.config
+    }
+
+    /// Detect a language from the file name, the shebang in `first_line`, or the
+    /// extension, in that order. Languages without a registered grammar are still
+    /// named, with no config.
+    pub fn detect_language_from(&self, filename: &str, first_line: Option<&str>) -> Option<DetectedLanguage<'_>> {
+        let name = self.detect_language_name(filename, first_line)?;
+        Some(DetectedLanguage { name, config: self.languages.get(name) })
+    }
+
+    fn detect_language_name(&self, filename: &str, first_line: Option<&str>) -> Option<&'static str> {
+        let basename = filename.rsplit(['/', '\\']).next().unwrap_or(filename);
+        if let Some(name) = self.filename_map.get(basename) {
+            return Some(name);
+        }
+        if let Some(name) = first_line.and_then(|line| self.language_from_shebang(line)) {
+            return Some(name);
+        }
+        let (_, extension) = basename.rsplit_once('.')?;
+        self.extension_map.get(extension).copied()
+    }
+
+    fn language_from_shebang(&self, line: &str) -> Option<&'static str> {
+        let mut parts = line.strip_prefix("#!")?.split_whitespace();
+        let mut interpreter = parts.next()?.rsplit('/').next()?;
+        if interpreter == "env" {
+            interpreter = parts.find(|p| !p.starts_with('-') && !p.contains('='))?;
+        }
+        if let Some(name) = self.shebang_map.get(interpreter) {
+            return Some(name);
+        }
+        let unversioned = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        self.shebang_map.get(unversioned).copied()
+    }
+
+    /// Map an exact file name (e.g. `Justfile`) to a language name.
+    pub fn register_filename(&mut self, filename: &'static str, language: &'static str) {
+        self.filename_map.insert(filename, language);
+    }
+
+    /// Map a shebang interpreter (e.g. `lua`) to a language name.
+    pub fn register_interpreter(&mut self, interpreter: &'static str, language: &'static str) {
+        self.shebang_map.insert(interpreter, language);
     }
 
     pub fn get_language(&self, name: &str) -> Option<&LanguageConfig> {
@@ -86,3 +223,22 @@ impl Default for LanguageRegistry {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_by_filename_and_shebang() {
+        let registry = LanguageRegistry::new();
+        let name = |filename, first_line| registry.detect_language_from(filename, first_line).map(|l| l.name);
+        assert_eq!(name("docker/Dockerfile", None), Some("dockerfile"));
+        assert_eq!(name("build", Some("#!/bin/sh")), Some("shell"));
+        assert_eq!(name("tool", Some("#!/usr/bin/env python3.11")), Some("python"));
+        assert!(registry.detect_language_from("build", Some("#!/bin/sh")).unwrap().config.is_none());
+        let js = registry.detect_language_from("serve", Some("#!/usr/bin/env node")).unwrap();
+        assert_eq!(js.config.unwrap().name, "javascript");
+        assert_eq!(registry.detect_language("src/main.rs").unwrap().name, "rust");
+        assert!(registry.detect_language("README").is_none());
+    }
+}
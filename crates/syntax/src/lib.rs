@@ -1,7 +1,12 @@
 mod highlight;
 mod language;
 mod parser;
+mod theme;
 
-pub use highlight::{HighlightSpan, LineHighlights, SyntaxHighlighter};
-pub use language::{LanguageConfig, LanguageRegistry, TokenType};
+pub use highlight::{
+    CharHighlightSpan, HighlightSpan, LineHighlights, SyntaxHighlighter, DEFAULT_MAX_HIGHLIGHT_BYTES,
+    DEFAULT_MAX_LINE_HIGHLIGHT_BYTES,
+};
+pub use language::{DetectedLanguage, LanguageConfig, LanguageRegistry, TokenType};
 pub use parser::{create_input_edit, IncrementalParser};
+pub use theme::{Rgba, Theme};
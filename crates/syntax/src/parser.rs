@@ -25,6 +25,11 @@ impl IncrementalParser {
         self.tree.as_ref()
     }
 
+    /// Drop the previous tree so the next parse starts from scratch.
+    pub fn reset(&mut self) {
+        self.tree = None;
+    }
+
     pub fn edit(&mut self, edit: &InputEdit) {
         if let Some(tree) = &mut self.tree {
             tree.edit(edit);
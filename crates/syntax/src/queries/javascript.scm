@@ -57,9 +57,6 @@
   function: (member_expression
     property: (property_identifier) @function))
 
-; Types (TypeScript-like)
-(type_identifier) @type
-
 ; Strings
 (string) @string
 (template_string) @string
@@ -72,10 +69,10 @@
 
 ; Constants
 [
-  "true"
-  "false"
-  "null"
-  "undefined"
+  (true)
+  (false)
+  (null)
+  (undefined)
 ] @constant
 
 ; Properties
@@ -121,7 +118,7 @@
   "=>"
   "..."
   "??"
-  "?."
+  (optional_chain)
 ] @operator
 
 ; Punctuation
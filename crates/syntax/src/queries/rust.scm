@@ -31,12 +31,13 @@
   "use"
   "where"
   "while"
-  "mut"
-  "crate"
-  "self"
-  "super"
 ] @keyword
 
+(mutable_specifier) @keyword
+(crate) @keyword
+(self) @keyword
+(super) @keyword
+
 ; Function definitions
 (function_item
   name: (identifier) @function)
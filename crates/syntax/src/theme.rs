@@ -0,0 +1,168 @@
+use crate::language::TokenType;
+use editor_core::ThemeState;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Rgba {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 0xff }
+    }
+
+    /// Parse `#rrggbb` or `#rrggbbaa` (the leading `#` is optional).
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        if !hex.is_ascii() || (hex.len() != 6 && hex.len() != 8) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        Some(Self {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+            a: if hex.len() == 8 { channel(6)? } else { 0xff },
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub name: String,
+    pub foreground: Rgba,
+    pub background: Rgba,
+    colors: HashMap<TokenType, Rgba>,
+}
+
+#[derive(Deserialize)]
+struct ThemeFile {
+    name: String,
+    foreground: String,
+    background: String,
+    #[serde(default)]
+    colors: HashMap<String, String>,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self::from_table(
+            "dark",
+            Rgba::rgb(0xab, 0xb2, 0xbf),
+            Rgba::rgb(0x28, 0x2c, 0x34),
+            &[
+                (TokenType::Keyword, Rgba::rgb(0xc6, 0x78, 0xdd)),
+                (TokenType::Function, Rgba::rgb(0x61, 0xaf, 0xef)),
+                (TokenType::Type, Rgba::rgb(0xe5, 0xc0, 0x7b)),
+                (TokenType::String, Rgba::rgb(0x98, 0xc3, 0x79)),
+                (TokenType::Comment, Rgba::rgb(0x5c, 0x63, 0x70)),
+                (TokenType::Number, Rgba::rgb(0xd1, 0x9a, 0x66)),
+                (TokenType::Operator, Rgba::rgb(0x56, 0xb6, 0xc2)),
+                (TokenType::Variable, Rgba::rgb(0xe0, 0x6c, 0x75)),
+                (TokenType::Punctuation, Rgba::rgb(0xab, 0xb2, 0xbf)),
+                (TokenType::Property, Rgba::rgb(0xe0, 0x6c, 0x75)),
+                (TokenType::Constant, Rgba::rgb(0xd1, 0x9a, 0x66)),
+            ],
+        )
+    }
+
+    pub fn light() -> Self {
+        Self::from_table(
+            "light",
+            Rgba::rgb(0x38, 0x3a, 0x42),
+            Rgba::rgb(0xfa, 0xfa, 0xfa),
+            &[
+                (TokenType::Keyword, Rgba::rgb(0xa6, 0x26, 0xa4)),
+                (TokenType::Function, Rgba::rgb(0x40, 0x78, 0xf2)),
+                (TokenType::Type, Rgba::rgb(0xc1, 0x84, 0x01)),
+                (TokenType::String, Rgba::rgb(0x50, 0xa1, 0x4f)),
+                (TokenType::Comment, Rgba::rgb(0xa0, 0xa1, 0xa7)),
+                (TokenType::Number, Rgba::rgb(0x98, 0x68, 0x01)),
+                (TokenType::Operator, Rgba::rgb(0x01, 0x84, 0xbc)),
+                (TokenType::Variable, Rgba::rgb(0xe4, 0x56, 0x49)),
+                (TokenType::Punctuation, Rgba::rgb(0x38, 0x3a, 0x42)),
+                (TokenType::Property, Rgba::rgb(0xe4, 0x56, 0x49)),
+                (TokenType::Constant, Rgba::rgb(0x98, 0x68, 0x01)),
+            ],
+        )
+    }
+
+    fn from_table(name: &str, foreground: Rgba, background: Rgba, colors: &[(TokenType, Rgba)]) -> Self {
+        Self {
+            name: name.to_string(),
+            foreground,
+            background,
+            colors: colors.iter().copied().collect(),
+        }
+    }
+
+    /// Look up a built-in theme by name.
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            _ => None,
+        }
+    }
+
+    /// Resolve the theme selected in the app state, falling back to the dark theme.
+    pub fn from_state(state: &ThemeState) -> Self {
+        Self::builtin(&state.theme_name).unwrap_or_else(Self::dark)
+    }
+
+    /// Load a theme from JSON of the form
+    /// `{"name": "..", "foreground": "#rrggbb", "background": "#rrggbb", "colors": {"keyword": "#rrggbb"}}`.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let file: ThemeFile =
+            serde_json::from_str(json).map_err(|e| format!("Invalid theme: {}", e))?;
+        let parse = |hex: &str| Rgba::from_hex(hex).ok_or_else(|| format!("Invalid color: {}", hex));
+        let mut colors = HashMap::new();
+        for (token, hex) in file.colors.iter() {
+            let token_type = TokenType::from_name(token)
+                .ok_or_else(|| format!("Unknown token type: {}", token))?;
+            colors.insert(token_type, parse(hex)?);
+        }
+        Ok(Self {
+            name: file.name,
+            foreground: parse(&file.foreground)?,
+            background: parse(&file.background)?,
+            colors,
+        })
+    }
+
+    /// Color for a token type; token types without an entry use the foreground color.
+    pub fn color(&self, token_type: TokenType) -> Rgba {
+        self.colors.get(&token_type).copied().unwrap_or(self.foreground)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_theme_and_resolve_keyword() {
+        let theme = Theme::from_json(
+            r##"{"name": "custom", "foreground": "#ffffff", "background": "#000000",
+                "colors": {"keyword": "#ff8000", "comment": "#80808080"}}"##,
+        )
+        .unwrap();
+        assert_eq!(theme.color(TokenType::Keyword), Rgba::rgb(0xff, 0x80, 0x00));
+        assert_eq!(theme.color(TokenType::Comment).a, 0x80);
+        assert_eq!(theme.color(TokenType::String), theme.foreground);
+
+        let state = ThemeState { theme_name: "light".to_string() };
+        assert_eq!(Theme::from_state(&state).name, "light");
+    }
+}
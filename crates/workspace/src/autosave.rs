@@ -0,0 +1,152 @@
+//! Debounced autosave for open documents.
+
+use crate::FileOps;
+use editor_core::{DocumentId, Event, EventSender};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// Messages accepted by the autosave task.
+#[derive(Debug, Clone)]
+pub enum AutosaveRequest {
+    /// A document was edited; `text` is its full content after the edit.
+    Edited {
+        document_id: DocumentId,
+        path: Option<PathBuf>,
+        text: String,
+    },
+    /// Drop any pending save (e.g. the document was saved manually or closed).
+    Cancel { document_id: DocumentId },
+}
+
+/// A save waiting for its debounce interval to elapse.
+#[derive(Debug)]
+struct PendingSave {
+    path: PathBuf,
+    text: String,
+    last_edit: Instant,
+}
+
+/// Save edited documents once no edits have arrived for `delay`.
+///
+/// Each save writes through `FileOps::write_file` and emits `Event::DocumentSaved`,
+/// or `Event::Error` if the write fails. Documents without a path are never saved.
+/// When the request channel closes, pending saves are flushed immediately.
+pub async fn run_autosave(
+    mut requests: mpsc::Receiver<AutosaveRequest>,
+    events: EventSender,
+    delay: Duration,
+) {
+    let mut pending: HashMap<DocumentId, PendingSave> = HashMap::new();
+    loop {
+        let deadline = pending.values().map(|p| p.last_edit + delay).min();
+        let request = match deadline {
+            Some(deadline) => match tokio::time::timeout_at(deadline, requests.recv()).await {
+                Ok(request) => request,
+                Err(_) => {
+                    flush(&mut pending, &events, Some(Instant::now() - delay)).await;
+                    continue;
+                }
+            },
+            None => requests.recv().await,
+        };
+        match request {
+            Some(AutosaveRequest::Edited {
+                document_id,
+                path: Some(path),
+                text,
+            }) => {
+                pending.insert(
+                    document_id,
+                    PendingSave {
+                        path,
+                        text,
+                        last_edit: Instant::now(),
+                    },
+                );
+            }
+            Some(AutosaveRequest::Edited { path: None, .. }) => {}
+            Some(AutosaveRequest::Cancel { document_id }) => {
+                pending.remove(&document_id);
+            }
+            None => {
+                flush(&mut pending, &events, None).await;
+                return;
+            }
+        }
+    }
+}
+
+/// Write every pending save whose last edit is at or before `edited_before`
+/// (all of them if `None`).
+async fn flush(
+    pending: &mut HashMap<DocumentId, PendingSave>,
+    events: &EventSender,
+    edited_before: Option<Instant>,
+) {
+    let due: Vec<DocumentId> = pending
+        .iter()
+        .filter(|(_, p)| edited_before.is_none_or(|t| p.last_edit <= t))
+        .map(|(id, _)| *id)
+        .collect();
+    for document_id in due {
+        let Some(save) = pending.remove(&document_id) else {
+            continue;
+        };
+        let event = match FileOps::write_file(&save.path, &save.text) {
+            Ok(()) => Event::DocumentSaved { document_id },
+            Err(e) => Event::Error {
+                message: format!("autosave failed for {}: {e}", save.path.display()),
+            },
+        };
+        let _ = events.send(event).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_debounced_edits_save_once() {
+        let temp_dir = std::env::temp_dir().join("workspace_test_autosave");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("notes.txt");
+
+        let (request_tx, request_rx) = mpsc::channel(16);
+        let (event_tx, mut event_rx) = mpsc::channel(16);
+        tokio::spawn(run_autosave(request_rx, event_tx, Duration::from_millis(30)));
+
+        for text in ["a", "ab", "abc"] {
+            request_tx
+                .send(AutosaveRequest::Edited {
+                    document_id: 7,
+                    path: Some(path.clone()),
+                    text: text.to_string(),
+                })
+                .await
+                .unwrap();
+        }
+        request_tx
+            .send(AutosaveRequest::Edited {
+                document_id: 8,
+                path: None,
+                text: "untitled".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let event = event_rx.recv().await.unwrap();
+        assert!(matches!(event, Event::DocumentSaved { document_id: 7 }));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "abc");
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert!(event_rx.try_recv().is_err());
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+}
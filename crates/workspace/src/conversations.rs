@@ -0,0 +1,144 @@
+//! Per-workspace chat history persistence.
+
+use crate::settings::WorkspaceSettings;
+use directories::ProjectDirs;
+use editor_core::{ChatState, Conversation, ConversationId};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Most conversations kept per workspace; the least recently updated are dropped.
+pub const MAX_SAVED_CONVERSATIONS: usize = 50;
+
+/// The conversations of a workspace as saved to disk.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConversationHistory {
+    /// Root path of the workspace
+    pub root: PathBuf,
+    /// Conversations in the order they were shown
+    pub conversations: Vec<Conversation>,
+    /// Conversation that was active when saved
+    pub active_conversation: Option<ConversationId>,
+}
+
+impl ConversationHistory {
+    /// Capture the conversations of `state`, pruned to `MAX_SAVED_CONVERSATIONS`.
+    pub fn from_state(root: PathBuf, state: &ChatState) -> Self {
+        let mut history = Self {
+            root,
+            conversations: state.conversations.clone(),
+            active_conversation: state.active_conversation,
+        };
+        history.prune(MAX_SAVED_CONVERSATIONS);
+        history
+    }
+
+    /// Turn the history back into chat state.
+    pub fn into_state(self) -> ChatState {
+        ChatState {
+            active_conversation: self.active_conversation,
+            conversations: self.conversations,
+        }
+    }
+
+    /// Keep the `max` most recently updated conversations, in their current order.
+    pub fn prune(&mut self, max: usize) {
+        if self.conversations.len() <= max {
+            return;
+        }
+        // Oldest first; among equal times the earlier conversation goes first.
+        let mut order: Vec<usize> = (0..self.conversations.len()).collect();
+        order.sort_by_key(|&i| (self.conversations[i].updated_at, i));
+        let mut dropped = vec![false; self.conversations.len()];
+        for &i in &order[..self.conversations.len() - max] {
+            dropped[i] = true;
+        }
+        let mut idx = 0;
+        self.conversations.retain(|_| {
+            idx += 1;
+            !dropped[idx - 1]
+        });
+        if self
+            .active_conversation
+            .is_some_and(|id| !self.conversations.iter().any(|c| c.id == id))
+        {
+            self.active_conversation = None;
+        }
+    }
+
+    /// Get the history file path for a workspace.
+    fn history_path(root: &Path) -> Option<PathBuf> {
+        let dirs = ProjectDirs::from("dev", "text_editor", "ai_code_editor")?;
+        let hash = WorkspaceSettings::hash_path(root);
+        Some(dirs.data_dir().join("conversations").join(format!("{hash}.json")))
+    }
+
+    /// Load the history of the workspace at `root`.
+    pub fn load(root: &Path) -> Option<Self> {
+        Self::load_from(&Self::history_path(root)?)
+    }
+
+    /// Load a history from `path`.
+    pub fn load_from(path: &Path) -> Option<Self> {
+        let data = std::fs::read_to_string(path).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    /// Save the history to disk.
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::history_path(&self.root).ok_or("no history path")?;
+        self.save_to(&path)
+    }
+
+    /// Save the history to `path`.
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor_core::ChatRole;
+
+    #[test]
+    fn test_history_round_trip_and_prune() {
+        let dir = std::env::temp_dir().join("workspace_test_conversations");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("history.json");
+
+        let mut state = ChatState::default();
+        let first = state.conversation_mut(7);
+        first.title = "Parser bug".to_string();
+        first.push_message(ChatRole::User, "Why does this panic?");
+        first.push_message(ChatRole::Assistant, "The index is off by one.");
+        state.conversation_mut(3).push_message(ChatRole::User, "Rename this");
+        state.active_conversation = Some(3);
+
+        let history = ConversationHistory::from_state(PathBuf::from("/ws"), &state);
+        history.save_to(&path).unwrap();
+        let restored = ConversationHistory::load_from(&path).unwrap().into_state();
+        assert_eq!(restored.active_conversation, Some(3));
+        let ids: Vec<ConversationId> = restored.conversations.iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![7, 3]);
+        let first = &restored.conversations[0];
+        assert_eq!(first.title, "Parser bug");
+        assert!(first.created_at > 0 && first.updated_at >= first.created_at);
+        let messages: Vec<(ChatRole, &str)> =
+            first.messages.iter().map(|m| (m.role, m.content.as_str())).collect();
+        assert_eq!(
+            messages,
+            vec![(ChatRole::User, "Why does this panic?"), (ChatRole::Assistant, "The index is off by one.")]
+        );
+
+        let mut history = ConversationHistory::from_state(PathBuf::from("/ws"), &restored);
+        history.conversations[0].updated_at = 1;
+        history.prune(1);
+        assert_eq!(history.conversations.len(), 1);
+        assert_eq!(history.conversations[0].id, 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,334 @@
+//! Open documents backed by files on disk.
+
+use crate::{FileMetadata, FileOpError, FileOpResult, FileOps};
+use editor::Buffer;
+use editor_core::{Command, DocumentId, Event};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Result of saving a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveOutcome {
+    /// The content was written to disk.
+    Written,
+    /// The content matched the last save, so nothing was written.
+    Unchanged,
+}
+
+/// A document loaded from a file, remembering the file state of its last write.
+#[derive(Debug, Clone)]
+pub struct FileDocument {
+    /// Path the document is saved to
+    pub path: PathBuf,
+    /// Editable buffer
+    pub buffer: Buffer,
+    /// File state as of the last load or save
+    disk: Option<DiskStamp>,
+}
+
+/// Modification time and size used to notice changes made outside the editor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskStamp {
+    modified: Option<SystemTime>,
+    size: u64,
+}
+
+impl From<FileMetadata> for DiskStamp {
+    fn from(meta: FileMetadata) -> Self {
+        Self {
+            modified: meta.modified,
+            size: meta.size,
+        }
+    }
+}
+
+pub(crate) fn stat(path: &Path) -> Option<DiskStamp> {
+    FileOps::metadata(path).ok().map(DiskStamp::from)
+}
+
+impl FileDocument {
+    /// Load a document from disk.
+    pub fn open(path: &Path) -> FileOpResult<Self> {
+        let disk = stat(path);
+        let text = FileOps::read_file(path)?;
+        let mut buffer = Buffer::new(&text);
+        buffer.mark_saved();
+        Ok(Self {
+            path: path.to_path_buf(),
+            disk,
+            buffer,
+        })
+    }
+
+    /// Check if the buffer differs from the last saved content.
+    pub fn is_dirty(&self) -> bool {
+        self.buffer.is_modified()
+    }
+
+    /// Check if the file was changed on disk since it was last loaded or saved.
+    ///
+    /// A file that has since been deleted does not count as modified.
+    pub fn is_modified_on_disk(&self) -> bool {
+        match stat(&self.path) {
+            Some(current) => self.disk != Some(current),
+            None => false,
+        }
+    }
+
+    /// Write the buffer to disk, skipping the write if nothing changed since the last save.
+    ///
+    /// Fails with `FileOpError::Conflict` if the file was modified on disk in the
+    /// meantime; use `force_save` to overwrite it or `reload_from_disk` to discard edits.
+    pub fn save(&mut self) -> FileOpResult<SaveOutcome> {
+        if !self.is_dirty() {
+            return Ok(SaveOutcome::Unchanged);
+        }
+        if self.is_modified_on_disk() {
+            return Err(FileOpError::Conflict(self.path.clone()));
+        }
+        self.write()
+    }
+
+    /// Write the buffer to disk even if the file was modified externally.
+    pub fn force_save(&mut self) -> FileOpResult<SaveOutcome> {
+        self.write()
+    }
+
+    /// Replace the buffer with the file's current content as one undoable edit.
+    pub fn reload_from_disk(&mut self) -> FileOpResult<()> {
+        let disk = stat(&self.path);
+        let text = FileOps::read_file(&self.path)?;
+        self.buffer.set_text(&text);
+        self.buffer.mark_saved();
+        self.disk = disk;
+        Ok(())
+    }
+
+    /// Accept a write made on this document's behalf (e.g. by autosave): the disk
+    /// stamp is refreshed, and the buffer is marked saved if it still matches the file.
+    pub fn sync_written(&mut self) -> FileOpResult<()> {
+        let disk = stat(&self.path);
+        let text = FileOps::read_file(&self.path)?;
+        if self.buffer.doc.to_string() == text {
+            self.buffer.mark_saved();
+        }
+        self.disk = disk;
+        Ok(())
+    }
+
+    fn write(&mut self) -> FileOpResult<SaveOutcome> {
+        FileOps::write_file(&self.path, &self.buffer.doc.to_string())?;
+        self.buffer.mark_saved();
+        self.disk = stat(&self.path);
+        Ok(SaveOutcome::Written)
+    }
+}
+
+/// Open file-backed documents keyed by id.
+#[derive(Debug, Default)]
+pub struct DocumentStore {
+    documents: HashMap<DocumentId, FileDocument>,
+    next_id: DocumentId,
+}
+
+impl DocumentStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a file, returning the existing id if it is already open.
+    pub fn open(&mut self, path: &Path) -> FileOpResult<DocumentId> {
+        if let Some(id) = self.find_by_path(path) {
+            return Ok(id);
+        }
+        let document = FileDocument::open(path)?;
+        self.next_id += 1;
+        self.documents.insert(self.next_id, document);
+        Ok(self.next_id)
+    }
+
+    /// Open a file and put a single caret at a zero-based line and column. The column
+    /// is clamped to the line's length, the line to the last line.
+    pub fn open_at(&mut self, path: &Path, line: usize, col: usize) -> FileOpResult<DocumentId> {
+        let id = self.open(path)?;
+        if let Some(document) = self.documents.get_mut(&id) {
+            let idx = document.buffer.doc.clamped_line_col_to_char(line, col);
+            document.buffer.selections.set_single_caret(idx);
+        }
+        Ok(id)
+    }
+
+    /// Handle the document commands: open, save and close. Returns the event to emit,
+    /// or `None` if the command is not a document command or names an unknown id.
+    pub fn dispatch(&mut self, command: &Command) -> Option<Event> {
+        let opened = |store: &Self, id: DocumentId| {
+            let document = store.get(id)?;
+            Some(Event::DocumentOpened {
+                document_id: id,
+                path: document.path.clone(),
+                text: document.buffer.doc.to_string(),
+                cursor_char_idx: document.buffer.selections.primary.head,
+            })
+        };
+        let error = |e: FileOpError| Some(Event::Error { message: e.to_string() });
+        match command {
+            Command::OpenFile { path } => match self.open(path) {
+                Ok(id) => opened(self, id),
+                Err(e) => error(e),
+            },
+            Command::OpenFileAt { path, line, col } => match self.open_at(path, *line, *col) {
+                Ok(id) => opened(self, id),
+                Err(e) => error(e),
+            },
+            Command::SaveFile { document_id } => match self.get_mut(*document_id)?.save() {
+                Ok(_) => Some(Event::DocumentSaved { document_id: *document_id }),
+                Err(e) => error(e),
+            },
+            Command::CloseFile { document_id } => self
+                .close(*document_id)
+                .map(|_| Event::DocumentClosed { document_id: *document_id }),
+            _ => None,
+        }
+    }
+
+    /// Find the id of an open document by path.
+    pub fn find_by_path(&self, path: &Path) -> Option<DocumentId> {
+        self.documents
+            .iter()
+            .find(|(_, d)| d.path == path)
+            .map(|(id, _)| *id)
+    }
+
+    /// Get an open document.
+    pub fn get(&self, id: DocumentId) -> Option<&FileDocument> {
+        self.documents.get(&id)
+    }
+
+    /// Get an open document mutably.
+    pub fn get_mut(&mut self, id: DocumentId) -> Option<&mut FileDocument> {
+        self.documents.get_mut(&id)
+    }
+
+    /// Reload an open document from disk. Returns false if the id is not open.
+    pub fn reload_from_disk(&mut self, id: DocumentId) -> FileOpResult<bool> {
+        match self.documents.get_mut(&id) {
+            Some(document) => document.reload_from_disk().map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    /// Close a document, returning it if it was open.
+    pub fn close(&mut self, id: DocumentId) -> Option<FileDocument> {
+        self.documents.remove(&id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_save_skips_unchanged_content() {
+        let temp_dir = std::env::temp_dir().join("workspace_test_documents_save");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("a.txt");
+        fs::write(&path, "hello").unwrap();
+
+        let mut store = DocumentStore::new();
+        let id = store.open(&path).unwrap();
+        let doc = store.get_mut(id).unwrap();
+        assert_eq!(doc.save().unwrap(), SaveOutcome::Unchanged);
+
+        doc.buffer.apply_text_to_selections("> ");
+        assert!(doc.is_dirty());
+        assert_eq!(doc.save().unwrap(), SaveOutcome::Written);
+        assert!(!doc.is_dirty());
+        assert_eq!(doc.save().unwrap(), SaveOutcome::Unchanged);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "> hello");
+
+        doc.buffer.undo();
+        doc.buffer.redo();
+        assert_eq!(doc.save().unwrap(), SaveOutcome::Unchanged);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_save_detects_external_modification() {
+        let temp_dir = std::env::temp_dir().join("workspace_test_documents_conflict");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("a.txt");
+        fs::write(&path, "hello").unwrap();
+
+        let mut store = DocumentStore::new();
+        let id = store.open(&path).unwrap();
+        store.get_mut(id).unwrap().buffer.apply_text_to_selections("> ");
+        fs::write(&path, "changed elsewhere").unwrap();
+
+        let doc = store.get_mut(id).unwrap();
+        assert!(matches!(doc.save(), Err(FileOpError::Conflict(_))));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "changed elsewhere");
+
+        assert!(store.reload_from_disk(id).unwrap());
+        let doc = store.get_mut(id).unwrap();
+        assert_eq!(doc.buffer.doc.to_string(), "changed elsewhere");
+        assert!(!doc.is_dirty());
+        assert!(doc.buffer.undo());
+        assert_eq!(doc.buffer.doc.to_string(), "> hello");
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_sync_written_accepts_write_on_behalf() {
+        let temp_dir = std::env::temp_dir().join("workspace_test_documents_sync");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("a.txt");
+        fs::write(&path, "hello").unwrap();
+
+        let mut doc = FileDocument::open(&path).unwrap();
+        doc.buffer.apply_text_to_selections("> ");
+        fs::write(&path, "> hello").unwrap();
+        doc.sync_written().unwrap();
+        assert!(!doc.is_dirty());
+        assert!(!doc.is_modified_on_disk());
+
+        doc.buffer.apply_text_to_selections("> ");
+        fs::write(&path, "> hello").unwrap();
+        doc.sync_written().unwrap();
+        assert!(doc.is_dirty());
+        assert_eq!(doc.save().unwrap(), SaveOutcome::Written);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "> > hello");
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_open_file_at_sets_caret() {
+        let temp_dir = std::env::temp_dir().join("workspace_test_documents_open_at");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("a.txt");
+        fs::write(&path, "l0\nl1\nl2\nl3\nl4\nline five\n").unwrap();
+
+        let mut store = DocumentStore::new();
+        let event = store.dispatch(&Command::OpenFileAt { path: path.clone(), line: 5, col: 2 });
+        let Some(Event::DocumentOpened { document_id, cursor_char_idx, .. }) = event else {
+            panic!("expected DocumentOpened, got {event:?}");
+        };
+        assert_eq!(cursor_char_idx, 17);
+        let doc = store.get(document_id).unwrap();
+        assert_eq!(doc.buffer.selections.primary.head, 17);
+
+        store.dispatch(&Command::OpenFileAt { path: path.clone(), line: 1, col: 40 });
+        assert_eq!(store.get(document_id).unwrap().buffer.selections.primary.head, 5);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+}
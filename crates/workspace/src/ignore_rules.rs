@@ -0,0 +1,78 @@
+//! Path ignore rules shared by tree building and file watching.
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Which paths the workspace leaves out of the tree and the watcher.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IgnoreConfig {
+    /// Respect `.gitignore` files and `.git/info/exclude`
+    pub git_ignore: bool,
+    /// Extra gitignore-style patterns, relative to the workspace root
+    pub exclude: Vec<String>,
+}
+
+impl Default for IgnoreConfig {
+    fn default() -> Self {
+        Self {
+            git_ignore: true,
+            exclude: vec![".git/".to_string()],
+        }
+    }
+}
+
+impl IgnoreConfig {
+    /// Build a matcher for paths under `root`.
+    ///
+    /// Only the root `.gitignore` is read; nested ignore files are honoured by the tree
+    /// walk but not by the matcher.
+    pub fn matcher(&self, root: &Path) -> IgnoreMatcher {
+        let mut excludes = GitignoreBuilder::new(root);
+        for pattern in &self.exclude {
+            let _ = excludes.add_line(None, pattern);
+        }
+        let gitignore = self.git_ignore.then(|| {
+            let mut builder = GitignoreBuilder::new(root);
+            builder.add(root.join(".gitignore"));
+            builder.add(root.join(".git").join("info").join("exclude"));
+            builder.build().unwrap_or_else(|_| Gitignore::empty())
+        });
+        IgnoreMatcher {
+            excludes: excludes.build().unwrap_or_else(|_| Gitignore::empty()),
+            gitignore,
+        }
+    }
+}
+
+/// Compiled form of an `IgnoreConfig`.
+#[derive(Debug, Clone)]
+pub struct IgnoreMatcher {
+    excludes: Gitignore,
+    gitignore: Option<Gitignore>,
+}
+
+impl IgnoreMatcher {
+    /// Check if the path or one of its parents matches an `exclude` pattern.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        matches_path_or_parents(&self.excludes, path)
+    }
+
+    /// Check if the path is excluded or gitignored.
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        self.is_excluded(path)
+            || self
+                .gitignore
+                .as_ref()
+                .is_some_and(|g| matches_path_or_parents(g, path))
+    }
+}
+
+fn matches_path_or_parents(matcher: &Gitignore, path: &Path) -> bool {
+    if !path.starts_with(matcher.path()) {
+        return false;
+    }
+    matcher
+        .matched_path_or_any_parents(path, path.is_dir())
+        .is_ignore()
+}
@@ -3,17 +3,28 @@
 //! Provides file tree building, file operations, file watching,
 //! and workspace settings persistence.
 
+pub mod autosave;
+pub mod conversations;
+pub mod documents;
+pub mod ignore_rules;
 pub mod ops;
+pub mod refresh;
 pub mod settings;
 pub mod tree;
 pub mod watcher;
 
+pub use autosave::{run_autosave, AutosaveRequest};
+pub use conversations::{ConversationHistory, MAX_SAVED_CONVERSATIONS};
+pub use documents::{DiskStamp, DocumentStore, FileDocument, SaveOutcome};
+pub use ignore_rules::{IgnoreConfig, IgnoreMatcher};
 pub use ops::{FileMetadata, FileOpError, FileOpResult, FileOps};
-pub use settings::{GlobalSettings, WorkspaceSettings};
-pub use tree::{FlatTreeItem, NodeKind, TreeNode};
-pub use watcher::{FileWatcher, WatchEvent};
+pub use refresh::run_tree_refresh;
+pub use settings::{GlobalSettings, WorkspaceSettings, DEFAULT_MODEL_ID};
+pub use tree::{FlatTreeItem, NodeKind, TreeIter, TreeNode};
+pub use watcher::{FileWatcher, WatchEvent, WatchMode};
 
 use ignore::WalkBuilder;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 /// Main workspace service that coordinates file tree, operations, and watching.
@@ -27,6 +38,8 @@ pub struct WorkspaceService {
     watcher: Option<FileWatcher>,
     /// Workspace settings
     settings: WorkspaceSettings,
+    /// File state last seen by `read_document`/`write_document`, per path
+    disk_stamps: HashMap<PathBuf, DiskStamp>,
 }
 
 impl WorkspaceService {
@@ -52,6 +65,7 @@ impl WorkspaceService {
             tree: None,
             watcher: None,
             settings,
+            disk_stamps: HashMap::new(),
         })
     }
 
@@ -74,11 +88,14 @@ impl WorkspaceService {
         root_node.expanded = true;
 
         // Use ignore crate to respect .gitignore
+        let ignore = &self.settings.ignore;
+        let matcher = ignore.matcher(&self.root);
         let walker = WalkBuilder::new(&self.root)
             .hidden(false)
-            .git_ignore(true)
-            .git_global(true)
-            .git_exclude(true)
+            .git_ignore(ignore.git_ignore)
+            .git_global(ignore.git_ignore)
+            .git_exclude(ignore.git_ignore)
+            .filter_entry(move |e| !matcher.is_excluded(e.path()))
             .build();
 
         let mut paths: Vec<PathBuf> = walker
@@ -149,6 +166,55 @@ impl WorkspaceService {
         }
     }
 
+    /// Apply a single watcher event to the cached tree without rebuilding it.
+    ///
+    /// Returns `true` if the tree changed.
+    pub fn apply_watch_event(&mut self, event: &WatchEvent) -> bool {
+        match event {
+            WatchEvent::Created(path) => self.insert_watched_path(path),
+            WatchEvent::Modified(path) => path.exists() && self.insert_watched_path(path),
+            WatchEvent::Deleted(path) => self.remove_watched_path(path),
+            WatchEvent::Renamed { from, to } => {
+                let removed = self.remove_watched_path(from);
+                let inserted = self.insert_watched_path(to);
+                removed || inserted
+            }
+            WatchEvent::Error(_) => false,
+        }
+    }
+
+    /// Insert a path reported by the watcher if it is not already in the tree.
+    fn insert_watched_path(&mut self, path: &Path) -> bool {
+        if path == self.root || !path.starts_with(&self.root) {
+            return false;
+        }
+        let Some(mut tree) = self.tree.take() else {
+            return false;
+        };
+        let changed = tree.find_by_path(path).is_none();
+        if changed {
+            self.insert_path(&mut tree, path);
+            if let Some(parent) = path.parent().and_then(|p| tree.find_by_path_mut(p)) {
+                parent.sort_children();
+            }
+        }
+        self.tree = Some(tree);
+        changed
+    }
+
+    /// Remove a path reported by the watcher from the tree.
+    fn remove_watched_path(&mut self, path: &Path) -> bool {
+        let Some(parent) = path
+            .parent()
+            .and_then(|p| self.tree.as_mut()?.find_by_path_mut(p))
+        else {
+            return false;
+        };
+        let before = parent.children.len();
+        parent.children.retain(|c| c.path != path);
+        parent.children.len() != before
+    }
+
     /// Get the cached file tree.
     pub fn tree(&self) -> Option<&TreeNode> {
         self.tree.as_ref()
@@ -174,6 +240,32 @@ impl WorkspaceService {
         }
     }
 
+    /// Expand every directory between the root and `path` so it is visible in the
+    /// explorer. Returns whether `path` is in the tree.
+    pub fn reveal(&mut self, path: &Path) -> bool {
+        let Some(tree) = &mut self.tree else {
+            return false;
+        };
+        if tree.find_by_path(path).is_none() {
+            return false;
+        }
+        let ancestors: Vec<PathBuf> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(&self.root))
+            .map(Path::to_path_buf)
+            .collect();
+        for dir in ancestors {
+            if let Some(node) = tree.find_by_path_mut(&dir) {
+                if node.is_directory() {
+                    node.expanded = true;
+                }
+            }
+        }
+        self.update_expanded_dirs();
+        true
+    }
+
     /// Update expanded dirs in settings.
     fn update_expanded_dirs(&mut self) {
         if let Some(tree) = &self.tree {
@@ -196,7 +288,7 @@ impl WorkspaceService {
 
     /// Start file watching.
     pub fn start_watching(&mut self) -> Result<(), String> {
-        let watcher = FileWatcher::new(&self.root)?;
+        let watcher = FileWatcher::new(&self.root, &self.settings.ignore, self.settings.watch_mode)?;
         self.watcher = Some(watcher);
         Ok(())
     }
@@ -242,6 +334,60 @@ impl WorkspaceService {
         Ok(())
     }
 
+    /// Rename a directory and return the new path of every file beneath it, so open
+    /// documents can be remapped. Fails if `to` already exists.
+    pub fn rename_directory(&mut self, from: &Path, to: &Path) -> FileOpResult<HashMap<PathBuf, PathBuf>> {
+        if !from.exists() {
+            return Err(FileOpError::NotFound(from.to_path_buf()));
+        }
+        if !from.is_dir() {
+            return Err(FileOpError::InvalidPath("not a directory".to_string()));
+        }
+        let mut files = Vec::new();
+        collect_files(from, &mut files).map_err(|e| FileOpError::from_io(e, from))?;
+        FileOps::rename(from, to)?;
+        self.build_tree();
+        Ok(files
+            .into_iter()
+            .filter_map(|old| {
+                let new = to.join(old.strip_prefix(from).ok()?);
+                Some((old, new))
+            })
+            .collect())
+    }
+
+    /// Read a document, returning its text and a stamp of the file's current state to
+    /// pass back to `write_document`. The stamp is taken before reading, so a write that
+    /// lands in between shows up as a conflict rather than being recorded as read.
+    pub fn read_document(&mut self, path: &Path) -> FileOpResult<(String, DiskStamp)> {
+        let stamp = DiskStamp::from(FileOps::metadata(path)?);
+        let text = FileOps::read_file(path)?;
+        self.disk_stamps.insert(path.to_path_buf(), stamp);
+        Ok((text, stamp))
+    }
+
+    /// Write a document, failing with `FileOpError::Conflict` if the file no longer
+    /// matches `expected` (`None` expects it not to exist). Returns the new stamp.
+    pub fn write_document(
+        &mut self,
+        path: &Path,
+        text: &str,
+        expected: Option<DiskStamp>,
+    ) -> FileOpResult<DiskStamp> {
+        if documents::stat(path) != expected {
+            return Err(FileOpError::Conflict(path.to_path_buf()));
+        }
+        FileOps::write_file(path, text)?;
+        let stamp = DiskStamp::from(FileOps::metadata(path)?);
+        self.disk_stamps.insert(path.to_path_buf(), stamp);
+        Ok(stamp)
+    }
+
+    /// File state last seen by `read_document` or `write_document` for `path`.
+    pub fn disk_stamp(&self, path: &Path) -> Option<DiskStamp> {
+        self.disk_stamps.get(path).copied()
+    }
+
     /// Delete a file.
     pub fn delete_file(&mut self, path: &Path) -> FileOpResult<()> {
         FileOps::delete_file(path)?;
@@ -256,3 +402,115 @@ impl WorkspaceService {
         Ok(())
     }
 }
+
+/// Recursively collect the files under `dir`. Symlinks are collected as entries and
+/// never followed, so a link back to an ancestor can't recurse forever.
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_files(&path, out)?;
+        } else {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_rename_directory_remaps_files() {
+        let temp_dir = std::env::temp_dir().join("workspace_test_rename_dir");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("src/nested")).unwrap();
+        fs::write(temp_dir.join("src/a.rs"), "a").unwrap();
+        fs::write(temp_dir.join("src/nested/b.rs"), "b").unwrap();
+        fs::create_dir_all(temp_dir.join("taken")).unwrap();
+
+        let mut ws = WorkspaceService::open(temp_dir.clone()).unwrap();
+        assert!(matches!(
+            ws.rename_directory(&temp_dir.join("src"), &temp_dir.join("taken")),
+            Err(FileOpError::AlreadyExists(_))
+        ));
+
+        let moved = ws.rename_directory(&temp_dir.join("src"), &temp_dir.join("lib")).unwrap();
+        assert_eq!(moved.len(), 2);
+        assert_eq!(moved[&temp_dir.join("src/a.rs")], temp_dir.join("lib/a.rs"));
+        assert_eq!(moved[&temp_dir.join("src/nested/b.rs")], temp_dir.join("lib/nested/b.rs"));
+        assert_eq!(fs::read_to_string(temp_dir.join("lib/nested/b.rs")).unwrap(), "b");
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_collect_files_does_not_follow_symlink_loops() {
+        let temp_dir = std::env::temp_dir().join("workspace_test_collect_symlink");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("src")).unwrap();
+        fs::write(temp_dir.join("src/a.rs"), "a").unwrap();
+        std::os::unix::fs::symlink(&temp_dir, temp_dir.join("src/loop")).unwrap();
+
+        let mut files = Vec::new();
+        collect_files(&temp_dir, &mut files).unwrap();
+        files.sort();
+        assert_eq!(files, vec![temp_dir.join("src/a.rs"), temp_dir.join("src/loop")]);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_reveal_expands_ancestors() {
+        let temp_dir = std::env::temp_dir().join("workspace_test_reveal");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("a/b")).unwrap();
+        fs::create_dir_all(temp_dir.join("c")).unwrap();
+        fs::write(temp_dir.join("a/b/deep.rs"), "").unwrap();
+
+        let mut ws = WorkspaceService::open(temp_dir.clone()).unwrap();
+        ws.build_tree();
+        assert!(!ws.reveal(&temp_dir.join("a/missing.rs")));
+        assert!(ws.reveal(&temp_dir.join("a/b/deep.rs")));
+
+        let flat = ws.flat_tree();
+        let item = |name: &str| flat.iter().find(|i| i.node.name == name).unwrap();
+        assert!(item("a").node.expanded && item("b").node.expanded);
+        assert!(item("deep.rs").visible);
+        assert!(!item("c").node.expanded);
+        let expanded = &ws.settings().expanded_dirs;
+        assert!(expanded.contains(&temp_dir.join("a")) && expanded.contains(&temp_dir.join("a/b")));
+        assert!(!expanded.contains(&temp_dir.join("c")));
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_write_document_detects_stale_stamp() {
+        let temp_dir = std::env::temp_dir().join("workspace_test_document_stamp");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = temp_dir.join("notes.txt");
+        fs::write(&path, "draft").unwrap();
+
+        let mut ws = WorkspaceService::open(temp_dir.clone()).unwrap();
+        let (text, stamp) = ws.read_document(&path).unwrap();
+        assert_eq!(text, "draft");
+        let stamp = ws.write_document(&path, "second draft", Some(stamp)).unwrap();
+        assert_eq!(ws.disk_stamp(&path), Some(stamp));
+
+        fs::write(&path, "changed elsewhere").unwrap();
+        assert!(matches!(
+            ws.write_document(&path, "third draft", Some(stamp)),
+            Err(FileOpError::Conflict(_))
+        ));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "changed elsewhere");
+        assert!(ws.write_document(&temp_dir.join("new.txt"), "x", None).is_ok());
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+}
@@ -17,6 +17,8 @@ pub enum FileOpError {
     PermissionDenied(PathBuf),
     /// Invalid path or name
     InvalidPath(String),
+    /// File changed on disk since it was last loaded or saved
+    Conflict(PathBuf),
     /// IO error
     IoError(String),
 }
@@ -28,6 +30,7 @@ impl std::fmt::Display for FileOpError {
             FileOpError::AlreadyExists(p) => write!(f, "already exists: {}", p.display()),
             FileOpError::PermissionDenied(p) => write!(f, "permission denied: {}", p.display()),
             FileOpError::InvalidPath(s) => write!(f, "invalid path: {s}"),
+            FileOpError::Conflict(p) => write!(f, "modified on disk: {}", p.display()),
             FileOpError::IoError(s) => write!(f, "IO error: {s}"),
         }
     }
@@ -35,13 +38,14 @@ impl std::fmt::Display for FileOpError {
 
 impl std::error::Error for FileOpError {}
 
-impl From<std::io::Error> for FileOpError {
-    fn from(e: std::io::Error) -> Self {
+impl FileOpError {
+    /// Map an IO error from an operation on `path`, keeping the path in the error.
+    pub fn from_io(e: std::io::Error, path: &Path) -> Self {
         match e.kind() {
-            std::io::ErrorKind::NotFound => FileOpError::NotFound(PathBuf::new()),
-            std::io::ErrorKind::AlreadyExists => FileOpError::AlreadyExists(PathBuf::new()),
-            std::io::ErrorKind::PermissionDenied => FileOpError::PermissionDenied(PathBuf::new()),
-            _ => FileOpError::IoError(e.to_string()),
+            std::io::ErrorKind::NotFound => FileOpError::NotFound(path.to_path_buf()),
+            std::io::ErrorKind::AlreadyExists => FileOpError::AlreadyExists(path.to_path_buf()),
+            std::io::ErrorKind::PermissionDenied => FileOpError::PermissionDenied(path.to_path_buf()),
+            _ => FileOpError::IoError(format!("{}: {e}", path.display())),
         }
     }
 }
@@ -50,7 +54,63 @@ impl From<std::io::Error> for FileOpError {
 #[derive(Debug, Clone)]
 pub struct FileOps;
 
+/// Device names Windows reserves in every directory, with or without an extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7",
+    "COM8", "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
 impl FileOps {
+    /// Check that `name` is a single, portable file or directory name.
+    ///
+    /// Rejects empty or blank names, `.` and `..`, path separators, NUL, and names
+    /// reserved on Windows (checked on every platform so workspaces stay portable).
+    pub fn validate_name(name: &str) -> FileOpResult<()> {
+        let invalid = |reason: &str| Err(FileOpError::InvalidPath(format!("{reason}: {name:?}")));
+        if name.trim().is_empty() {
+            return invalid("empty name");
+        }
+        if name == "." || name == ".." {
+            return invalid("reserved name");
+        }
+        if name.contains(['/', '\\', '\0']) {
+            return invalid("name contains a path separator");
+        }
+        if cfg!(windows) && (name.contains(['<', '>', ':', '"', '|', '?', '*']) || name.ends_with(['.', ' '])) {
+            return invalid("name is not allowed on Windows");
+        }
+        let stem = name.split('.').next().unwrap_or(name).trim_end();
+        if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+            return invalid("reserved name");
+        }
+        Ok(())
+    }
+
+    /// Create a file named `name` inside `dir`, returning its path.
+    pub fn create_file_in(dir: &Path, name: &str, content: Option<&str>) -> FileOpResult<PathBuf> {
+        Self::validate_name(name)?;
+        let path = dir.join(name);
+        Self::create_file(&path, content)?;
+        Ok(path)
+    }
+
+    /// Create a directory named `name` inside `dir`, returning its path.
+    pub fn create_directory_in(dir: &Path, name: &str) -> FileOpResult<PathBuf> {
+        Self::validate_name(name)?;
+        let path = dir.join(name);
+        Self::create_directory(&path)?;
+        Ok(path)
+    }
+
+    /// Rename a file or directory to `new_name` in the same directory, returning the
+    /// new path.
+    pub fn rename_to(from: &Path, new_name: &str) -> FileOpResult<PathBuf> {
+        Self::validate_name(new_name)?;
+        let to = from.with_file_name(new_name);
+        Self::rename(from, &to)?;
+        Ok(to)
+    }
+
     /// Create a new file with optional initial content.
     pub fn create_file(path: &Path, content: Option<&str>) -> FileOpResult<()> {
         if path.exists() {
@@ -58,11 +118,11 @@ impl FileOps {
         }
         if let Some(parent) = path.parent() {
             if !parent.exists() {
-                fs::create_dir_all(parent).map_err(|e| FileOpError::IoError(e.to_string()))?;
+                fs::create_dir_all(parent).map_err(|e| FileOpError::from_io(e, parent))?;
             }
         }
         let content = content.unwrap_or("");
-        fs::write(path, content).map_err(|e| FileOpError::IoError(e.to_string()))
+        fs::write(path, content).map_err(|e| FileOpError::from_io(e, path))
     }
 
     /// Create a new directory.
@@ -70,7 +130,7 @@ impl FileOps {
         if path.exists() {
             return Err(FileOpError::AlreadyExists(path.to_path_buf()));
         }
-        fs::create_dir_all(path).map_err(|e| FileOpError::IoError(e.to_string()))
+        fs::create_dir_all(path).map_err(|e| FileOpError::from_io(e, path))
     }
 
     /// Rename a file or directory.
@@ -81,7 +141,7 @@ impl FileOps {
         if to.exists() {
             return Err(FileOpError::AlreadyExists(to.to_path_buf()));
         }
-        fs::rename(from, to).map_err(|e| FileOpError::IoError(e.to_string()))
+        fs::rename(from, to).map_err(|e| FileOpError::from_io(e, from))
     }
 
     /// Delete a file.
@@ -92,7 +152,7 @@ impl FileOps {
         if !path.is_file() {
             return Err(FileOpError::InvalidPath("not a file".to_string()));
         }
-        fs::remove_file(path).map_err(|e| FileOpError::IoError(e.to_string()))
+        fs::remove_file(path).map_err(|e| FileOpError::from_io(e, path))
     }
 
     /// Delete a directory and all its contents.
@@ -103,7 +163,7 @@ impl FileOps {
         if !path.is_dir() {
             return Err(FileOpError::InvalidPath("not a directory".to_string()));
         }
-        fs::remove_dir_all(path).map_err(|e| FileOpError::IoError(e.to_string()))
+        fs::remove_dir_all(path).map_err(|e| FileOpError::from_io(e, path))
     }
 
     /// Copy a file.
@@ -116,10 +176,10 @@ impl FileOps {
         }
         if let Some(parent) = to.parent() {
             if !parent.exists() {
-                fs::create_dir_all(parent).map_err(|e| FileOpError::IoError(e.to_string()))?;
+                fs::create_dir_all(parent).map_err(|e| FileOpError::from_io(e, parent))?;
             }
         }
-        fs::copy(from, to).map_err(|e| FileOpError::IoError(e.to_string()))?;
+        fs::copy(from, to).map_err(|e| FileOpError::from_io(e, from))?;
         Ok(())
     }
 
@@ -128,17 +188,25 @@ impl FileOps {
         if !path.exists() {
             return Err(FileOpError::NotFound(path.to_path_buf()));
         }
-        fs::read_to_string(path).map_err(|e| FileOpError::IoError(e.to_string()))
+        fs::read_to_string(path).map_err(|e| FileOpError::from_io(e, path))
     }
 
     /// Write content to file.
+    ///
+    /// The content goes to a temporary file in the same directory which is synced and
+    /// renamed over the target, so a crash never leaves a truncated file. Symlinks, and
+    /// file systems where that fails, are written in place instead.
     pub fn write_file(path: &Path, content: &str) -> FileOpResult<()> {
         if let Some(parent) = path.parent() {
             if !parent.exists() {
-                fs::create_dir_all(parent).map_err(|e| FileOpError::IoError(e.to_string()))?;
+                fs::create_dir_all(parent).map_err(|e| FileOpError::from_io(e, parent))?;
             }
         }
-        fs::write(path, content).map_err(|e| FileOpError::IoError(e.to_string()))
+        let is_symlink = fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+        if !is_symlink && write_atomic(path, content.as_bytes()).is_ok() {
+            return Ok(());
+        }
+        fs::write(path, content).map_err(|e| FileOpError::from_io(e, path))
     }
 
     /// Check if path exists.
@@ -158,7 +226,7 @@ impl FileOps {
 
     /// Get file metadata.
     pub fn metadata(path: &Path) -> FileOpResult<FileMetadata> {
-        let meta = fs::metadata(path).map_err(|e| FileOpError::IoError(e.to_string()))?;
+        let meta = fs::metadata(path).map_err(|e| FileOpError::from_io(e, path))?;
         Ok(FileMetadata {
             size: meta.len(),
             is_readonly: meta.permissions().readonly(),
@@ -167,6 +235,38 @@ impl FileOps {
     }
 }
 
+/// Write `content` to a temporary sibling of `path`, sync it, and rename it over
+/// `path`, keeping the existing file's permissions. The temporary file is removed on
+/// failure.
+fn write_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static NEXT_TEMP: AtomicUsize = AtomicUsize::new(0);
+    let name = path
+        .file_name()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "no file name"))?;
+    let temp = path.with_file_name(format!(
+        ".{}.{}-{}.tmp",
+        name.to_string_lossy(),
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = (|| {
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(content)?;
+        if let Ok(meta) = fs::metadata(path) {
+            file.set_permissions(meta.permissions())?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
 /// File metadata.
 #[derive(Debug, Clone)]
 pub struct FileMetadata {
@@ -201,4 +301,49 @@ mod tests {
 
         let _ = fs::remove_dir_all(&temp_dir);
     }
+
+    #[test]
+    fn test_validate_name() {
+        assert!(FileOps::validate_name("main.rs").is_ok());
+        assert!(FileOps::validate_name(".gitignore").is_ok());
+        for name in ["../evil", "a/b", "", "  ", "..", "CON", "nul.txt", "Com1"] {
+            assert!(
+                matches!(FileOps::validate_name(name), Err(FileOpError::InvalidPath(_))),
+                "{name:?} should be rejected"
+            );
+        }
+
+        let dir = std::env::temp_dir().join("workspace_test_validate_name");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        assert!(FileOps::create_file_in(&dir, "../evil", None).is_err());
+        assert!(!dir.parent().unwrap().join("evil").exists());
+        let path = FileOps::create_file_in(&dir, "ok.txt", Some("x")).unwrap();
+        assert_eq!(FileOps::rename_to(&path, "renamed.txt").unwrap(), dir.join("renamed.txt"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_io_errors_name_the_path() {
+        let missing = std::env::temp_dir().join("workspace_test_missing").join("gone.txt");
+        let err = FileOps::metadata(&missing).unwrap_err();
+        assert!(matches!(&err, FileOpError::NotFound(p) if p == &missing));
+        assert_eq!(err.to_string(), format!("not found: {}", missing.display()));
+    }
+
+    #[test]
+    fn test_write_file_replaces_atomically() {
+        let dir = std::env::temp_dir().join("workspace_test_atomic_write");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("big.txt");
+        FileOps::write_file(&path, "old").unwrap();
+
+        let content = "0123456789abcdef\n".repeat(200_000);
+        FileOps::write_file(&path, &content).unwrap();
+        assert_eq!(FileOps::read_file(&path).unwrap(), content);
+        let entries: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(entries, vec![std::ffi::OsString::from("big.txt")]);
+        let _ = fs::remove_dir_all(&dir);
+    }
 }
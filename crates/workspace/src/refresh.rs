@@ -0,0 +1,98 @@
+//! Keeps the cached file tree in sync with file watcher events.
+
+use crate::{WatchEvent, WorkspaceService};
+use editor_core::{Event, EventSender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Apply watcher events to the workspace tree and emit `Event::WorkspaceTreeUpdated`.
+///
+/// Events arriving within `coalesce` of each other are applied together and
+/// produce a single update event. Runs until the watcher channel or the event
+/// receiver is closed.
+pub async fn run_tree_refresh(
+    workspace: Arc<Mutex<WorkspaceService>>,
+    mut watch_rx: broadcast::Receiver<WatchEvent>,
+    events: EventSender,
+    coalesce: Duration,
+) {
+    loop {
+        let first = match watch_rx.recv().await {
+            Ok(event) => Some(event),
+            Err(broadcast::error::RecvError::Lagged(_)) => None,
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        let mut batch: Vec<WatchEvent> = first.into_iter().collect();
+        let mut lagged = batch.is_empty();
+        loop {
+            match tokio::time::timeout(coalesce, watch_rx.recv()).await {
+                Ok(Ok(event)) => batch.push(event),
+                Ok(Err(broadcast::error::RecvError::Lagged(_))) => lagged = true,
+                Ok(Err(broadcast::error::RecvError::Closed)) | Err(_) => break,
+            }
+        }
+
+        let mut changed = false;
+        {
+            let mut ws = workspace.lock().unwrap();
+            if lagged {
+                // Events were dropped, so the incremental view can't be trusted.
+                ws.build_tree();
+                changed = true;
+            } else {
+                for event in batch.iter() {
+                    changed |= ws.apply_watch_event(event);
+                }
+            }
+        }
+
+        if changed && events.send(Event::WorkspaceTreeUpdated).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_created_event_updates_tree() {
+        let temp_dir = std::env::temp_dir().join("workspace_test_refresh");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(temp_dir.join("src")).unwrap();
+
+        let mut service = WorkspaceService::open(temp_dir.clone()).unwrap();
+        service.build_tree();
+        let workspace = Arc::new(Mutex::new(service));
+
+        let (watch_tx, watch_rx) = broadcast::channel(16);
+        let (event_tx, mut event_rx) = tokio::sync::mpsc::channel(16);
+        tokio::spawn(run_tree_refresh(
+            Arc::clone(&workspace),
+            watch_rx,
+            event_tx,
+            Duration::from_millis(20),
+        ));
+
+        let new_file = temp_dir.join("src").join("new.rs");
+        fs::write(&new_file, "").unwrap();
+        watch_tx.send(WatchEvent::Created(new_file.clone())).unwrap();
+        watch_tx.send(WatchEvent::Modified(new_file.clone())).unwrap();
+
+        let event = event_rx.recv().await.unwrap();
+        assert!(matches!(event, Event::WorkspaceTreeUpdated));
+        assert!(workspace
+            .lock()
+            .unwrap()
+            .tree()
+            .unwrap()
+            .find_by_path(&new_file)
+            .is_some());
+        assert!(event_rx.try_recv().is_err());
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+}
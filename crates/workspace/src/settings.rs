@@ -1,5 +1,7 @@
 //! Workspace settings and persistence.
 
+use crate::ignore_rules::IgnoreConfig;
+use crate::watcher::WatchMode;
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
@@ -17,6 +19,15 @@ pub struct WorkspaceSettings {
     pub active_tab_index: Option<usize>,
     /// Expanded directories in explorer
     pub expanded_dirs: Vec<PathBuf>,
+    /// Autosave debounce in milliseconds (`None` disables autosave)
+    #[serde(default)]
+    pub autosave_ms: Option<u64>,
+    /// Paths left out of the explorer tree and file watching
+    #[serde(default)]
+    pub ignore: IgnoreConfig,
+    /// How file changes are detected
+    #[serde(default)]
+    pub watch_mode: WatchMode,
 }
 
 impl WorkspaceSettings {
@@ -28,6 +39,9 @@ impl WorkspaceSettings {
             last_open_tabs: Vec::new(),
             active_tab_index: None,
             expanded_dirs: Vec::new(),
+            autosave_ms: None,
+            ignore: IgnoreConfig::default(),
+            watch_mode: WatchMode::default(),
         }
     }
 
@@ -51,6 +65,11 @@ impl WorkspaceSettings {
         self.expanded_dirs = dirs;
     }
 
+    /// Autosave debounce interval, if autosave is enabled.
+    pub fn autosave_delay(&self) -> Option<std::time::Duration> {
+        self.autosave_ms.map(std::time::Duration::from_millis)
+    }
+
     /// Get settings file path for a workspace.
     fn settings_path(root: &Path) -> Option<PathBuf> {
         let dirs = ProjectDirs::from("dev", "text_editor", "ai_code_editor")?;
@@ -59,7 +78,7 @@ impl WorkspaceSettings {
     }
 
     /// Hash a path to a filename-safe string.
-    fn hash_path(path: &Path) -> String {
+    pub(crate) fn hash_path(path: &Path) -> String {
         use std::collections::hash_map::DefaultHasher;
         use std::hash::{Hash, Hasher};
         let mut hasher = DefaultHasher::new();
@@ -85,11 +104,41 @@ impl WorkspaceSettings {
     }
 }
 
+/// Model used when the user has not picked one.
+pub const DEFAULT_MODEL_ID: &str = "openai/gpt-4o-mini";
+
 /// Global application settings (across all workspaces).
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GlobalSettings {
     /// Recently opened workspaces (most recent first)
     pub recent_workspaces: Vec<PathBuf>,
+    /// Model id used for new chat requests (`None` until the user picks one)
+    pub default_model: Option<String>,
+    /// Name of the UI theme
+    pub theme: String,
+    /// Editor font size in logical pixels
+    pub font_size: f32,
+    /// Columns per tab stop
+    pub tab_width: usize,
+}
+
+impl Default for GlobalSettings {
+    fn default() -> Self {
+        Self {
+            recent_workspaces: Vec::new(),
+            default_model: None,
+            theme: "dark".to_string(),
+            font_size: 14.0,
+            tab_width: 4,
+        }
+    }
+}
+
+/// The model-only config file written by earlier versions of the app.
+#[derive(Debug, Deserialize)]
+struct LegacyAppConfig {
+    model: String,
 }
 
 impl GlobalSettings {
@@ -102,23 +151,66 @@ impl GlobalSettings {
         }
     }
 
+    /// Model id for new chat requests, falling back to `DEFAULT_MODEL_ID`.
+    pub fn model(&self) -> &str {
+        self.default_model.as_deref().unwrap_or(DEFAULT_MODEL_ID)
+    }
+
     /// Get global settings file path.
     fn settings_path() -> Option<PathBuf> {
         let dirs = ProjectDirs::from("dev", "text_editor", "ai_code_editor")?;
         Some(dirs.data_dir().join("global_settings.json"))
     }
 
+    /// Get the path of the legacy app config.
+    fn legacy_config_path() -> Option<PathBuf> {
+        let dirs = ProjectDirs::from("dev", "text_editor", "ai_code_editor")?;
+        Some(dirs.config_dir().join("config.json"))
+    }
+
     /// Load global settings.
     pub fn load() -> Self {
-        Self::settings_path()
-            .and_then(|p| std::fs::read_to_string(p).ok())
+        match (Self::settings_path(), Self::legacy_config_path()) {
+            (Some(path), legacy) => Self::load_from(&path, legacy.as_deref()),
+            (None, _) => Self::default(),
+        }
+    }
+
+    /// Load global settings from `path`. If no model has been picked yet, the model from
+    /// the legacy app config at `legacy`, if any, is carried over and saved, and the
+    /// legacy file is renamed to `config.json.migrated` so it is not read again.
+    pub fn load_from(path: &Path, legacy: Option<&Path>) -> Self {
+        let mut settings: Self = std::fs::read_to_string(path)
+            .ok()
             .and_then(|s| serde_json::from_str(&s).ok())
-            .unwrap_or_default()
+            .unwrap_or_default();
+        if settings.default_model.is_some() {
+            return settings;
+        }
+        let Some(legacy) = legacy else {
+            return settings;
+        };
+        let legacy_model = std::fs::read_to_string(legacy)
+            .ok()
+            .and_then(|s| serde_json::from_str::<LegacyAppConfig>(&s).ok())
+            .map(|c| c.model);
+        if let Some(model) = legacy_model {
+            settings.default_model = Some(model);
+            if settings.save_to(path).is_ok() {
+                let _ = std::fs::rename(legacy, legacy.with_extension("json.migrated"));
+            }
+        }
+        settings
     }
 
     /// Save global settings.
     pub fn save(&self) -> Result<(), String> {
         let path = Self::settings_path().ok_or("no settings path")?;
+        self.save_to(&path)
+    }
+
+    /// Save global settings to `path`.
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
         }
@@ -141,4 +233,33 @@ mod tests {
         assert_eq!(settings.recent_files.len(), 2);
         assert_eq!(settings.recent_files[0], PathBuf::from("/test/a.rs"));
     }
+
+    #[test]
+    fn test_global_settings_round_trip_and_migration() {
+        let dir = std::env::temp_dir().join("workspace_test_global_settings");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("global_settings.json");
+        let legacy = dir.join("config.json");
+        std::fs::create_dir_all(&dir).unwrap();
+        // Written by earlier versions on every workspace open, before models were kept here
+        std::fs::write(&path, r#"{ "recent_workspaces": ["/ws"] }"#).unwrap();
+        std::fs::write(&legacy, r#"{ "model": "anthropic/claude-3.5-sonnet" }"#).unwrap();
+
+        let mut settings = GlobalSettings::load_from(&path, Some(&legacy));
+        assert_eq!(settings.model(), "anthropic/claude-3.5-sonnet");
+        assert_eq!(settings.recent_workspaces, vec![PathBuf::from("/ws")]);
+        assert_eq!(settings.tab_width, 4);
+        assert!(!legacy.exists());
+        assert_eq!(GlobalSettings::load_from(&path, Some(&legacy)), settings);
+
+        settings.default_model = Some("openai/gpt-4o".to_string());
+        settings.theme = "light".to_string();
+        settings.font_size = 16.5;
+        settings.tab_width = 2;
+        settings.save_to(&path).unwrap();
+        std::fs::write(&legacy, r#"{ "model": "anthropic/claude-3.5-sonnet" }"#).unwrap();
+        assert_eq!(GlobalSettings::load_from(&path, Some(&legacy)), settings);
+        assert!(legacy.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
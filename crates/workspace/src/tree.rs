@@ -115,18 +115,35 @@ impl TreeNode {
         None
     }
 
+    /// Iterate over this node and its descendants in pre-order, with depth (0 = self).
+    pub fn iter(&self) -> TreeIter<'_> {
+        TreeIter { stack: vec![(self, 0)] }
+    }
+
     /// Count total nodes in tree.
     pub fn count(&self) -> usize {
-        1 + self.children.iter().map(|c| c.count()).sum::<usize>()
+        self.iter().count()
     }
 
     /// Flatten the tree to a list of paths.
     pub fn flatten(&self) -> Vec<&PathBuf> {
-        let mut result = vec![&self.path];
-        for child in &self.children {
-            result.extend(child.flatten());
-        }
-        result
+        self.iter().map(|(node, _)| &node.path).collect()
+    }
+}
+
+/// Depth-first, pre-order iterator over a tree, yielding each node with its depth.
+#[derive(Debug, Clone)]
+pub struct TreeIter<'a> {
+    stack: Vec<(&'a TreeNode, usize)>,
+}
+
+impl<'a> Iterator for TreeIter<'a> {
+    type Item = (&'a TreeNode, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, depth) = self.stack.pop()?;
+        self.stack.extend(node.children.iter().rev().map(|child| (child, depth + 1)));
+        Some((node, depth))
     }
 }
 
@@ -204,4 +221,17 @@ mod tests {
         assert_eq!(root.children[1].name, "a.rs");
         assert_eq!(root.children[2].name, "z.rs");
     }
+
+    #[test]
+    fn test_iter_pre_order_with_depth() {
+        let mut src = TreeNode::directory(PathBuf::from("/test/src"));
+        src.children = vec![TreeNode::file(PathBuf::from("/test/src/lib.rs"))];
+        let mut root = TreeNode::directory(PathBuf::from("/test"));
+        root.children = vec![src, TreeNode::file(PathBuf::from("/test/README.md"))];
+
+        let visited: Vec<(&str, usize)> = root.iter().map(|(n, d)| (n.name.as_str(), d)).collect();
+        assert_eq!(visited, vec![("test", 0), ("src", 1), ("lib.rs", 2), ("README.md", 1)]);
+        assert_eq!(root.count(), 4);
+        assert_eq!(root.flatten()[2], &PathBuf::from("/test/src/lib.rs"));
+    }
 }
@@ -1,6 +1,8 @@
 //! File system watching for workspace changes.
 
-use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use crate::ignore_rules::IgnoreConfig;
+use notify::{Config, Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::time::Duration;
@@ -21,37 +23,62 @@ pub enum WatchEvent {
     Error(String),
 }
 
+/// How the watcher learns about file system changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum WatchMode {
+    /// Native OS notifications only
+    Native,
+    /// Scan the tree at the given interval; works on network file systems
+    Poll(Duration),
+    /// Native notifications, falling back to polling every second if they are unavailable
+    #[default]
+    Auto,
+}
+
+/// Interval used when `WatchMode::Auto` falls back to polling.
+const AUTO_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Message to the event processing thread.
+enum Signal {
+    Event(notify::Result<Event>),
+    Stop,
+}
+
 /// File system watcher for a workspace.
+///
+/// Dropping the watcher stops the notify backend and waits for the event processing
+/// thread to exit, after which subscribers see the channel as closed.
 pub struct FileWatcher {
-    _watcher: RecommendedWatcher,
+    watcher: Option<Box<dyn Watcher + Send>>,
     event_tx: broadcast::Sender<WatchEvent>,
+    signal_tx: mpsc::Sender<Signal>,
+    thread: Option<std::thread::JoinHandle<()>>,
 }
 
 impl FileWatcher {
     /// Create a new file watcher for the given root path.
-    pub fn new(root: &Path) -> Result<Self, String> {
+    ///
+    /// Events whose paths are all ignored by `ignore` are dropped before broadcasting.
+    pub fn new(root: &Path, ignore: &IgnoreConfig, mode: WatchMode) -> Result<Self, String> {
+        let matcher = ignore.matcher(root);
         let (event_tx, _) = broadcast::channel(256);
         let tx_clone = event_tx.clone();
 
-        let (sync_tx, sync_rx) = mpsc::channel::<notify::Result<Event>>();
-
-        let mut watcher = RecommendedWatcher::new(
-            move |res| {
-                let _ = sync_tx.send(res);
-            },
-            Config::default().with_poll_interval(Duration::from_secs(1)),
-        )
+        let (signal_tx, signal_rx) = mpsc::channel::<Signal>();
+        let watcher = match mode {
+            WatchMode::Native => Self::native_watcher(root, &signal_tx),
+            WatchMode::Poll(interval) => Self::poll_watcher(root, &signal_tx, interval),
+            WatchMode::Auto => Self::native_watcher(root, &signal_tx)
+                .or_else(|_| Self::poll_watcher(root, &signal_tx, AUTO_POLL_INTERVAL)),
+        }
         .map_err(|e| e.to_string())?;
 
-        watcher
-            .watch(root, RecursiveMode::Recursive)
-            .map_err(|e| e.to_string())?;
-
         // Spawn thread to process events
-        std::thread::spawn(move || {
-            while let Ok(res) = sync_rx.recv() {
+        let thread = std::thread::spawn(move || {
+            while let Ok(Signal::Event(res)) = signal_rx.recv() {
                 match res {
-                    Ok(event) => {
+                    Ok(mut event) => {
+                        event.paths.retain(|p| !matcher.is_ignored(p));
                         let watch_events = Self::convert_event(event);
                         for we in watch_events {
                             let _ = tx_clone.send(we);
@@ -65,11 +92,57 @@ impl FileWatcher {
         });
 
         Ok(Self {
-            _watcher: watcher,
+            watcher: Some(watcher),
             event_tx,
+            signal_tx,
+            thread: Some(thread),
         })
     }
 
+    fn native_watcher(
+        root: &Path,
+        signal_tx: &mpsc::Sender<Signal>,
+    ) -> notify::Result<Box<dyn Watcher + Send>> {
+        let sync_tx = signal_tx.clone();
+        let mut watcher = RecommendedWatcher::new(
+            move |res| {
+                let _ = sync_tx.send(Signal::Event(res));
+            },
+            Config::default(),
+        )?;
+        watcher.watch(root, RecursiveMode::Recursive)?;
+        Ok(Box::new(watcher))
+    }
+
+    fn poll_watcher(
+        root: &Path,
+        signal_tx: &mpsc::Sender<Signal>,
+        interval: Duration,
+    ) -> notify::Result<Box<dyn Watcher + Send>> {
+        let sync_tx = signal_tx.clone();
+        let mut watcher = PollWatcher::new(
+            move |res| {
+                let _ = sync_tx.send(Signal::Event(res));
+            },
+            Config::default().with_poll_interval(interval),
+        )?;
+        watcher.watch(root, RecursiveMode::Recursive)?;
+        Ok(Box::new(watcher))
+    }
+
+    /// Stop watching and wait for the event processing thread to exit.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.watcher = None;
+        let _ = self.signal_tx.send(Signal::Stop);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+
     /// Subscribe to watch events.
     pub fn subscribe(&self) -> broadcast::Receiver<WatchEvent> {
         self.event_tx.subscribe()
@@ -93,8 +166,88 @@ impl FileWatcher {
     }
 }
 
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
 impl std::fmt::Debug for FileWatcher {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("FileWatcher").finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    #[test]
+    fn test_ignored_paths_emit_no_events() {
+        let root = std::env::temp_dir().join("workspace_test_watch_ignore");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        let root = root.canonicalize().unwrap();
+        let ignore = IgnoreConfig { git_ignore: true, exclude: vec!["target/".to_string()] };
+        let watcher = FileWatcher::new(&root, &ignore, WatchMode::Native).unwrap();
+        let mut events = watcher.subscribe();
+
+        std::fs::write(root.join("target").join("out.o"), "x").unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
+
+        std::fs::write(root.join("main.rs"), "x").unwrap();
+        let mut seen = false;
+        for _ in 0..50 {
+            std::thread::sleep(Duration::from_millis(20));
+            if let Ok(WatchEvent::Created(path) | WatchEvent::Modified(path)) = events.try_recv() {
+                assert_eq!(path, root.join("main.rs"));
+                seen = true;
+                break;
+            }
+        }
+        assert!(seen);
+        drop(watcher);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_drop_stops_processing_thread() {
+        let root = std::env::temp_dir().join("workspace_test_watch_drop");
+        std::fs::create_dir_all(&root).unwrap();
+        let watcher = FileWatcher::new(&root, &IgnoreConfig::default(), WatchMode::Auto).unwrap();
+        let mut events = watcher.subscribe();
+        assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
+
+        // The thread holds a sender, so the channel only closes once it has exited.
+        drop(watcher);
+        assert!(matches!(events.try_recv(), Err(TryRecvError::Closed)));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_poll_mode_reports_changes() {
+        let root = std::env::temp_dir().join("workspace_test_watch_poll");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+        let mode = WatchMode::Poll(Duration::from_millis(50));
+        let watcher = FileWatcher::new(&root, &IgnoreConfig::default(), mode).unwrap();
+        let mut events = watcher.subscribe();
+
+        std::fs::write(root.join("polled.txt"), "x").unwrap();
+        let mut seen = false;
+        for _ in 0..50 {
+            std::thread::sleep(Duration::from_millis(20));
+            if let Ok(WatchEvent::Created(path)) = events.try_recv() {
+                assert_eq!(path, root.join("polled.txt"));
+                seen = true;
+                break;
+            }
+        }
+        assert!(seen);
+        drop(watcher);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}